@@ -2,8 +2,7 @@ extern crate webplatform;
 
 
 fn main() {
-    let document = webplatform::init();
-    {
+    webplatform::run(|document, _| {
         let body = document.element_query("body").unwrap();
 
         let hr = document.element_create("hr").unwrap();
@@ -14,18 +13,14 @@ fn main() {
 
         let button = document.element_query("button").unwrap();
 
-        let bodyref = body.root_ref();
-        let bodyref2 = body.root_ref();
+        // The listener outlives this closure, so it gets its own handle to the body.
+        let body2 = body.clone();
         button.on("click", move |_| {
-            bodyref2.prop_set_str("bgColor", "blue");
-            println!(r#"This should be string "blue": {:?}"#, bodyref2.prop_get_str("bgColor"));
-        });
+            body2.prop_set_str("bgColor", "blue");
+            println!(r#"This should be string "blue": {:?}"#, body2.prop_get_str("bgColor"));
+        }).forget();
 
-        println!("This should be empty string: {:?}", bodyref.prop_get_str("bgColor"));
-        println!("Width?: {:?}", bodyref.prop_get_i32("clientWidth"));
-
-        webplatform::spin();
-    }
-
-    unreachable!();
+        println!("This should be empty string: {:?}", body.prop_get_str("bgColor"));
+        println!("Width?: {:?}", body.prop_get_i32("clientWidth"));
+    });
 }
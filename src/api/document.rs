@@ -1,9 +1,8 @@
 use std::cell::RefCell;
-use std::ffi::CStr;
+use std::mem;
 use std::rc::Rc;
-use std::str;
-use libc;
 use ::api::*;
+use ::error::*;
 use ::event::*;
 use ::html_node::*;
 
@@ -12,6 +11,33 @@ pub fn init<'a>() -> Document<'a> {
     js_guarded! { "\
         window.WEBPLATFORM || (window.WEBPLATFORM = {\
             rs_refs: [],\
+            rs_free: [],\
+            ref_push: function (value) {\
+                var id = WEBPLATFORM.rs_free.length ? WEBPLATFORM.rs_free.pop() : WEBPLATFORM.rs_refs.length;\
+                WEBPLATFORM.rs_refs[id] = value;\
+                return id;\
+            },\
+            ref_free: function (id) {\
+                WEBPLATFORM.rs_refs[id] = null;\
+                WEBPLATFORM.rs_free.push(id);\
+            },\
+            u8_slice: function (ptr) {\
+                var start = HEAPU32[ptr >> 2];\
+                var length = HEAPU32[(ptr >> 2) + 1];\
+                return HEAPU8.subarray(start, start + length);\
+            },\
+            option: function (ptr) {\
+                return HEAP32[(ptr >> 2) + 2] ? HEAPF64[ptr >> 3] : null;\
+            },\
+            listen: function (target, type, capture, listener) {\
+                target.addEventListener(type, listener, capture);\
+                return WEBPLATFORM.ref_push({ target: target, type: type, listener: listener, capture: capture });\
+            },\
+            unlisten: function (id) {\
+                var l = WEBPLATFORM.rs_refs[id];\
+                l.target.removeEventListener(l.type, l.listener, l.capture);\
+                WEBPLATFORM.ref_free(id);\
+            },\
         });\
     " };
     Document {
@@ -19,6 +45,8 @@ pub fn init<'a>() -> Document<'a> {
         refs_v: Rc::new(RefCell::new(Vec::new())),
         refs_v_u8array: Rc::new(RefCell::new(Vec::new())),
         refs_v_string: Rc::new(RefCell::new(Vec::new())),
+        listeners: Rc::new(RefCell::new(Vec::new())),
+        timers: Rc::new(RefCell::new(Vec::new())),
     }
 }
 
@@ -27,49 +55,43 @@ pub struct Document<'a> {
     refs_v: Rc<RefCell<Vec<Box<FnMut() + 'a>>>>,
     refs_v_u8array: Rc<RefCell<Vec<Box<FnMut(&[u8]) + 'a>>>>,
     refs_v_string: Rc<RefCell<Vec<Box<FnMut(String) + 'a>>>>,
+    listeners: Rc<RefCell<Vec<ListenerHandle<'a>>>>,
+    timers: Rc<RefCell<Vec<TimerHandle<'a>>>>,
 }
 
 impl<'a> Document<'a> {
-    pub fn websocket_create<'b>(&'b self, url: &str) -> Option<WebSocket<'a>> {
-        let id = js_guarded! { (url) "\
-            var value = new WebSocket(UTF8ToString($0));\
-            if (!value) {\
-                return -1;\
-            }\
-            value.binaryType = 'arraybuffer';\
-            return WEBPLATFORM.rs_refs.push(value) - 1;\
-        " };
+    pub fn window(&self) -> Window<'a> {
+        Window::new(&*self)
+    }
 
-        if id < 0 {
-            None
-        } else {
-            Some(WebSocket::new (id, &*self))
-        }
+    #[deprecated(note = "use `Window::websocket_create`")]
+    pub fn websocket_create<'b>(&'b self, url: &str) -> Option<WebSocket<'a>> {
+        self.window().websocket_create(url)
     }
 
     pub fn element_create<'b>(&'b self, s: &str) -> Option<HtmlNode<'a>> {
-        let id = js_guarded! { (s) "\
+        self.try_element_create(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_element_create<'b>(&'b self, s: &str) -> Result<Option<HtmlNode<'a>>, JsError> {
+        let id = js_try! { (s) "\
             var value = document.createElement(UTF8ToString($0));\
             if (!value) {\
                 return -1;\
             }\
-            return WEBPLATFORM.rs_refs.push(value) - 1;\
-        " };
+            return WEBPLATFORM.ref_push(value);\
+        " }?;
 
         if id < 0 {
-            None
+            Ok(None)
         } else {
-            Some(HtmlNode::new( id, &*self ))
+            Ok(Some(HtmlNode::new( id, &*self )))
         }
     }
 
+    #[deprecated(note = "use `Window::location_hash_get`")]
     pub fn location_hash_get(&self) -> String {
-        let a = js_guarded! { "\
-            return allocate(intArrayFromString(window.location.hash), 'i8', ALLOC_STACK);\
-        " };
-        unsafe {
-            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-        }
+        self.window().location_hash_get()
     }
 
     pub fn push_ref(&self, value: Box<FnMut(Event<'a>) + 'a>) {
@@ -88,35 +110,58 @@ impl<'a> Document<'a> {
         self.refs_v_u8array.borrow_mut().push(value);
     }
 
-    pub fn on<F: FnMut(Event) + 'a>(&self, s: &str, f: F) {
-        unsafe {
-            let b = Box::new(f);
-            let a = &*b as *const _;
-            js_guarded! { (0, s, a as *const libc::c_void,
-                rust_caller::<F> as *const libc::c_void,
-                &*self as *const _ as *const libc::c_void)
-                "\
-                window.addEventListener(UTF8ToString($1), function (e) {\
-                    Runtime.dynCall('viii', $3, [$2, $4, e.target ? WEBPLATFORM.rs_refs.push(e.target) - 1 : -1]);\
-                }, false);\
-            " };
-            self.refs.borrow_mut().push(b);
-        }
+    pub(crate) fn push_listener(&self, value: ListenerHandle<'a>) {
+        self.listeners.borrow_mut().push(value);
+    }
+
+    pub(crate) fn push_timer(&self, value: TimerHandle<'a>) {
+        self.timers.borrow_mut().push(value);
+    }
+
+    /// Removes a forgotten timer by its `TimerHandle::key`.
+    pub(crate) fn take_timer(&self, key: u32) -> Option<TimerHandle<'a>> {
+        let mut timers = self.timers.borrow_mut();
+        let index = timers.iter().position(|timer| timer.key() == key);
+        index.map(|index| timers.swap_remove(index))
+    }
+
+    /// Drops every forgotten listener, timer and pushed closure, which unlistens and clears
+    /// them. Called when the main loop quits.
+    pub(crate) fn release_all(&self) {
+        // Taken out first, so that the handles can reach the document while they are dropped.
+        let listeners = mem::take(&mut *self.listeners.borrow_mut());
+        let timers = mem::take(&mut *self.timers.borrow_mut());
+        drop(listeners);
+        drop(timers);
+        let refs = mem::take(&mut *self.refs.borrow_mut());
+        let refs_v = mem::take(&mut *self.refs_v.borrow_mut());
+        let refs_v_u8array = mem::take(&mut *self.refs_v_u8array.borrow_mut());
+        let refs_v_string = mem::take(&mut *self.refs_v_string.borrow_mut());
+        drop((refs, refs_v, refs_v_u8array, refs_v_string));
+    }
+
+    #[deprecated(note = "use `Window::on`")]
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.window().on(s, f)
     }
 
     pub fn element_query<'b>(&'b self, s: &str) -> Option<HtmlNode<'a>> {
-        let id = js_guarded! { (s) "\
+        self.try_element_query(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_element_query<'b>(&'b self, s: &str) -> Result<Option<HtmlNode<'a>>, JsError> {
+        let id = js_try! { (s) "\
             var value = document.querySelector(UTF8ToString($0));\
             if (!value) {\
                 return -1;\
             }\
-            return WEBPLATFORM.rs_refs.push(value) - 1;\
-        " };
+            return WEBPLATFORM.ref_push(value);\
+        " }?;
 
         if id < 0 {
-            None
+            Ok(None)
         } else {
-            Some(HtmlNode::new( id, &*self ))
+            Ok(Some(HtmlNode::new( id, &*self )))
         }
     }
 }
@@ -0,0 +1,423 @@
+use std::slice;
+use libc;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use serde_json;
+use ::api::*;
+use ::error::*;
+use ::event::*;
+use ::interop::*;
+use ::take_last_js_exception;
+
+
+/// HTTP headers with lowercase names, kept in insertion order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Headers { entries: Vec::new() }
+    }
+
+    pub fn append(&mut self, name: &str, value: &str) {
+        self.entries.push((name.to_lowercase(), value.to_string()));
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.remove(name);
+        self.append(name, value);
+    }
+
+    /// Returns all values of `name` joined with `", "`, like `Headers.get` in JS.
+    pub fn get(&self, name: &str) -> Option<String> {
+        let name = name.to_lowercase();
+        let values: Vec<&str> = self.entries.iter()
+            .filter(|&&(ref n, _)| *n == name)
+            .map(|&(_, ref v)| v.as_str())
+            .collect();
+        if values.is_empty() {
+            None
+        } else {
+            Some(values.join(", "))
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.entries.iter().any(|&(ref n, _)| *n == name)
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        let name = name.to_lowercase();
+        self.entries.retain(|&(ref n, _)| *n != name);
+    }
+
+    pub fn iter(&self) -> slice::Iter<(String, String)> {
+        self.entries.iter()
+    }
+
+    fn to_lines(&self) -> String {
+        self.entries.iter().map(|&(ref n, ref v)| format!("{}: {}\r\n", n, v)).collect()
+    }
+}
+
+impl From<Vec<(String, String)>> for Headers {
+    fn from(entries: Vec<(String, String)>) -> Self {
+        Headers { entries }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Body {
+    Empty,
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub headers: Headers,
+    pub body: Body,
+}
+
+impl Request {
+    pub fn new(method: &str, url: &str) -> Self {
+        Request {
+            method: method.to_string(),
+            url: url.to_string(),
+            headers: Headers::new(),
+            body: Body::Empty,
+        }
+    }
+
+    pub fn get(url: &str) -> Self {
+        Request::new("GET", url)
+    }
+
+    pub fn post(url: &str) -> Self {
+        Request::new("POST", url)
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn text(mut self, body: &str) -> Self {
+        self.body = Body::Text(body.to_string());
+        self
+    }
+
+    pub fn binary(mut self, body: Vec<u8>) -> Self {
+        self.body = Body::Binary(body);
+        self
+    }
+}
+
+// =================================================================================================
+
+pub struct Response {
+    id: libc::c_int,
+    status: i32,
+    status_text: String,
+    ok: bool,
+    url: String,
+    headers: Headers,
+}
+
+impl Drop for Response {
+    fn drop(&mut self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.ref_free($0);\
+        " };
+    }
+}
+
+impl Response {
+    /// Takes ownership of the `rs_refs` slot `id`, which is freed if reading the response fails.
+    fn from_ref(id: libc::c_int) -> Result<Self, JsError> {
+        Response::read(id).map_err(|error| {
+            js_guarded! { (id) "\
+                WEBPLATFORM.ref_free($0);\
+            " };
+            error
+        })
+    }
+
+    fn read(id: libc::c_int) -> Result<Self, JsError> {
+        let status = js_try! { (id) "\
+            return WEBPLATFORM.rs_refs[$0].status;\
+        " }?;
+        let ok = js_try! { (id) -> bool, "\
+            return WEBPLATFORM.rs_refs[$0].ok;\
+        " }?;
+        let status_text = js_try! { (id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].statusText;\
+        " }?;
+        let url = js_try! { (id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].url;\
+        " }?;
+        let headers = js_try! { (id) -> String, "\
+            var str = '';\
+            WEBPLATFORM.rs_refs[$0].headers.forEach(function (value, name) {\
+                str += name + ': ' + value + '\\r\\n';\
+            });\
+            return str;\
+        " }?;
+        Ok(Response {
+            id,
+            status,
+            status_text,
+            ok,
+            url,
+            headers: Headers::from(parse_headers(&headers)),
+        })
+    }
+
+    pub fn status(&self) -> i32 {
+        self.status
+    }
+
+    pub fn status_text(&self) -> &str {
+        &self.status_text
+    }
+
+    /// Whether the status is in the range 200-299.
+    pub fn ok(&self) -> bool {
+        self.ok
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Reads the whole body as text.
+    pub fn text<'a, F: FnOnce(Result<String, JsError>) + 'a>(self, f: F) {
+        self.try_text(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_text<'a, F: FnOnce(Result<String, JsError>) + 'a>(self, f: F) -> Result<(), JsError> {
+        self.read_body("text", move |r| f(r.and_then(try_ref_string)))
+    }
+
+    /// Reads the whole body as bytes.
+    pub fn bytes<'a, F: FnOnce(Result<Vec<u8>, JsError>) + 'a>(self, f: F) {
+        self.try_bytes(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_bytes<'a, F: FnOnce(Result<Vec<u8>, JsError>) + 'a>(self, f: F) -> Result<(), JsError> {
+        self.read_body("arrayBuffer", move |r| f(r.and_then(try_ref_bytes)))
+    }
+
+    /// Reads the whole body and parses it as JSON. Malformed JSON, or JSON that does not match
+    /// `T`, is reported as a `SyntaxError`.
+    #[cfg(feature = "serde")]
+    pub fn json<'a, T: DeserializeOwned, F: FnOnce(Result<T, JsError>) + 'a>(self, f: F) {
+        self.try_json(f).unwrap_or_else(JsError::raise)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn try_json<'a, T: DeserializeOwned, F: FnOnce(Result<T, JsError>) + 'a>(self, f: F) -> Result<(), JsError> {
+        self.try_text(move |r| f(r.and_then(|text| serde_json::from_str(&text).map_err(|error| JsError {
+            name: "SyntaxError".to_string(),
+            message: error.to_string(),
+            stack: None,
+        }))))
+    }
+
+    /// Streams the body chunk by chunk, then calls `done`.
+    pub fn stream<'a, C, D>(self, chunk: C, done: D)
+        where C: FnMut(&[u8]) + 'a, D: FnOnce(Result<(), JsError>) + 'a
+    {
+        self.try_stream(chunk, done).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_stream<'a, C, D>(self, chunk: C, done: D) -> Result<(), JsError>
+        where C: FnMut(&[u8]) + 'a, D: FnOnce(Result<(), JsError>) + 'a
+    {
+        let chunk = Box::new(chunk);
+        let c = &*chunk as *const _;
+        let done = CallbackOnceI32::new(move |status| {
+            drop(chunk);
+            done(if status < 0 { Err(take_fetch_error()) } else { Ok(()) })
+        });
+        let result = js_try! { (self.id, c as *const libc::c_void,
+            rust_caller_v_u8array::<C> as *const libc::c_void, done.ptr, done.caller)
+            "\
+            var reader = WEBPLATFORM.rs_refs[$0].body.getReader();\
+            var pump = function () {\
+                reader.read().then(function (r) {\
+                    if (r.done) {\
+                        Runtime.dynCall('vii', $4, [$3, 0]);\
+                        return;\
+                    }\
+                    var buf = Module._malloc(r.value.length);\
+                    HEAPU8.set(r.value, buf);\
+                    Runtime.dynCall('viii', $2, [$1, buf, r.value.length]);\
+                    Module._free(buf);\
+                    pump();\
+                }, function (e) {\
+                    WEBPLATFORM.last_exc = e;\
+                    Runtime.dynCall('vii', $4, [$3, -1]);\
+                });\
+            };\
+            pump();\
+        " };
+        if let Err(error) = result {
+            unsafe { done.free() };
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    fn read_body<'a, F: FnOnce(Result<libc::c_int, JsError>) + 'a>(self, method: &str, f: F) -> Result<(), JsError> {
+        let done = CallbackOnceI32::new(move |id| f(if id < 0 { Err(take_fetch_error()) } else { Ok(id) }));
+        let result = js_try! { (self.id, method, done.ptr, done.caller)
+            "\
+            WEBPLATFORM.rs_refs[$0][UTF8ToString($1)]().then(function (value) {\
+                Runtime.dynCall('vii', $3, [$2, WEBPLATFORM.ref_push(value)]);\
+            }, function (e) {\
+                WEBPLATFORM.last_exc = e;\
+                Runtime.dynCall('vii', $3, [$2, -1]);\
+            });\
+        " };
+        if let Err(error) = result {
+            unsafe { done.free() };
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+// =================================================================================================
+
+/// Aborts an in-flight fetch. Dropping the handle lets the request finish.
+pub struct AbortHandle {
+    id: libc::c_int,
+}
+
+impl Drop for AbortHandle {
+    fn drop(&mut self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.ref_free($0);\
+        " };
+    }
+}
+
+impl AbortHandle {
+    pub fn abort(&self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].abort();\
+        " };
+    }
+}
+
+pub(crate) fn fetch<'a, F: FnOnce(Result<Response, JsError>) + 'a>(request: Request, f: F) -> Result<AbortHandle, JsError> {
+    let done = CallbackOnceI32::new(move |id| f(if id < 0 { Err(take_fetch_error()) } else { Response::from_ref(id) }));
+    let (kind, text, binary): (i32, &str, &[u8]) = match request.body {
+        Body::Empty => (0, "", &[]),
+        Body::Text(ref s) => (1, s, &[]),
+        Body::Binary(ref b) => (2, "", b),
+    };
+    let result = js_try! { (request.method.as_str(), request.url.as_str(), request.headers.to_lines().as_str(),
+        kind, text, binary, done.ptr, done.caller)
+        "\
+        var controller = new AbortController();\
+        var init = { method: UTF8ToString($0), headers: new Headers(), signal: controller.signal };\
+        UTF8ToString($2).split('\\r\\n').forEach(function (line) {\
+            var i = line.indexOf(':');\
+            if (i > 0) init.headers.append(line.slice(0, i), line.slice(i + 1).trim());\
+        });\
+        if ($3 == 1) {\
+            init.body = UTF8ToString($4);\
+        } else if ($3 == 2) {\
+            init.body = WEBPLATFORM.u8_slice($5).slice();\
+        }\
+        fetch(UTF8ToString($1), init).then(function (response) {\
+            Runtime.dynCall('vii', $7, [$6, WEBPLATFORM.ref_push(response)]);\
+        }, function (e) {\
+            WEBPLATFORM.last_exc = e;\
+            Runtime.dynCall('vii', $7, [$6, -1]);\
+        });\
+        return WEBPLATFORM.ref_push(controller);\
+    " };
+    match result {
+        Ok(id) => Ok(AbortHandle { id }),
+        Err(error) => {
+            unsafe { done.free() };
+            Err(error)
+        }
+    }
+}
+
+fn take_fetch_error() -> JsError {
+    take_last_js_exception().unwrap_or_else(|| JsError {
+        name: "TypeError".to_string(),
+        message: "fetch failed".to_string(),
+        stack: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headers() {
+        let mut headers = Headers::new();
+        headers.append("Accept", "text/html");
+        headers.append("accept", "application/json");
+        headers.set("X-Token", "1");
+        assert_eq!(headers.get("ACCEPT"), Some("text/html, application/json".to_string()));
+        assert!(headers.contains("x-token"));
+        headers.remove("Accept");
+        assert_eq!(headers.get("accept"), None);
+        assert_eq!(headers.to_lines(), "x-token: 1\r\n");
+    }
+
+    #[test]
+    fn test_request() {
+        let request = Request::post("/api").header("Content-Type", "text/plain").text("hi");
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.headers.get("content-type"), Some("text/plain".to_string()));
+        assert_eq!(request.body, Body::Text("hi".to_string()));
+    }
+
+    #[test]
+    fn test_response() {
+        let _document = init();
+        let id = js_guarded! { "\
+            var headers = { 'Content-Type': 'text/plain', 'X-Id': '7' };\
+            var response = new Response('hi', { status: 201, statusText: 'Created', headers: headers });\
+            return WEBPLATFORM.ref_push(response);\
+        " };
+        let response = Response::from_ref(id).ok().unwrap();
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.status_text(), "Created");
+        assert!(response.ok());
+        assert_eq!(response.headers().get("x-id"), Some("7".to_string()));
+        assert_eq!(response.headers().get("content-type"), Some("text/plain".to_string()));
+    }
+
+    #[test]
+    fn test_response_error_frees_ref() {
+        let _document = init();
+        let id = js_guarded! { "\
+            return WEBPLATFORM.ref_push({ status: 200, ok: true, statusText: '', url: '', headers: null });\
+        " };
+        assert_eq!(Response::from_ref(id).err().unwrap().name, "TypeError");
+        let freed = js_guarded! { (id) -> bool, "\
+            return WEBPLATFORM.rs_refs[$0] === null;\
+        " };
+        assert!(freed);
+    }
+}
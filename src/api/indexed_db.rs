@@ -0,0 +1,887 @@
+use std::marker::PhantomData;
+use std::str;
+use libc;
+use ::api::*;
+use ::error::*;
+use ::event::*;
+use ::interop::*;
+use ::take_last_js_exception;
+
+
+/// A key of an object store record or index entry.
+///
+/// Only number and string keys are supported; date, array and binary keys are rejected with a
+/// `DataError` when read back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Key {
+    Number(f64),
+    String(String),
+}
+
+impl<'k> From<&'k str> for Key {
+    fn from(s: &'k str) -> Key {
+        Key::String(s.to_string())
+    }
+}
+
+impl From<String> for Key {
+    fn from(s: String) -> Key {
+        Key::String(s)
+    }
+}
+
+impl From<i32> for Key {
+    fn from(n: i32) -> Key {
+        Key::Number(n as f64)
+    }
+}
+
+impl From<f64> for Key {
+    fn from(n: f64) -> Key {
+        Key::Number(n)
+    }
+}
+
+impl Key {
+    /// Puts the key into a `rs_refs` slot.
+    fn try_push(&self) -> Result<libc::c_int, JsError> {
+        match *self {
+            Key::Number(n) => js_try! { (n) "\
+                return WEBPLATFORM.ref_push($0);\
+            " },
+            Key::String(ref s) => js_try! { (&s[..]) "\
+                return WEBPLATFORM.ref_push(UTF8ToString($0));\
+            " },
+        }
+    }
+
+    /// Takes a key out of a `rs_refs` slot, failing on key types other than number and string.
+    fn take(id: libc::c_int) -> Result<Key, JsError> {
+        let kind = js_guarded! { (id) "\
+            switch (typeof WEBPLATFORM.rs_refs[$0]) {\
+                case 'number': return 1;\
+                case 'string': return 2;\
+                default: WEBPLATFORM.ref_free($0); return 0;\
+            }\
+        " };
+        match kind {
+            1 => Ok(Key::Number(f64::from_ref(id)?)),
+            2 => Ok(Key::String(try_ref_string(id)?)),
+            _ => Err(JsError {
+                name: "DataError".to_string(),
+                message: "only number and string keys are supported".to_string(),
+                stack: None,
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionMode {
+    ReadOnly,
+    ReadWrite,
+}
+
+impl TransactionMode {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            TransactionMode::ReadOnly => "readonly",
+            TransactionMode::ReadWrite => "readwrite",
+        }
+    }
+}
+
+/// Versions passed to `on_upgrade_needed`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionChange {
+    pub old_version: u32,
+    pub new_version: u32,
+}
+
+/// Takes the `error` of the request in a `rs_refs` slot.
+fn take_request_error(id: libc::c_int) -> JsError {
+    js_guarded! { (id) "\
+        WEBPLATFORM.last_exc = WEBPLATFORM.rs_refs[$0].error;\
+        WEBPLATFORM.ref_free($0);\
+    " };
+    take_last_js_exception().unwrap_or_else(|| JsError {
+        name: "UnknownError".to_string(),
+        message: "IndexedDB request failed".to_string(),
+        stack: None,
+    })
+}
+
+fn try_listen_v<'a, F: FnMut() + 'a>(target: libc::c_int, doc: *const Document<'a>, s: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
+    unsafe {
+        let b = Box::new(f);
+        let a = &*b as *const _;
+        let id = js_try! { (target, s, a as *const libc::c_void,
+            rust_caller_v::<F> as *const libc::c_void)
+            "\
+            return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], UTF8ToString($1), false, function (e) {\
+                Runtime.dynCall('vi', $3, [$2]);\
+            });\
+        " }?;
+        Ok(ListenerHandle::new(id, doc, b))
+    }
+}
+
+fn try_listen_error<'a, F: FnMut(JsError) + 'a>(target: libc::c_int, doc: *const Document<'a>, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+    let b = Box::new(move |id| f(take_request_error(id)));
+    let a = &*b as *const _;
+    let id = js_try! { (target, a as *const libc::c_void, caller_v_i32(&*b)) "\
+        return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], 'error', false, function (e) {\
+            Runtime.dynCall('vii', $2, [$1, WEBPLATFORM.ref_push(e.target)]);\
+        });\
+    " }?;
+    Ok(ListenerHandle::new(id, doc, b))
+}
+
+macro_rules! idb_handle {
+    ($name:ident) => {
+        impl<'a> Clone for $name<'a> {
+            fn clone(&self) -> $name<'a> {
+                let id = js_guarded! { (self.id) "\
+                    return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0]);\
+                " };
+                $name { id, doc: self.doc }
+            }
+        }
+
+        impl<'a> Drop for $name<'a> {
+            fn drop(&mut self) {
+                js_guarded! { (self.id) "\
+                    WEBPLATFORM.ref_free($0);\
+                " };
+            }
+        }
+    };
+}
+
+// =================================================================================================
+
+/// The value a request delivers to `on_success`, taken out of the `result` of the request.
+pub trait IdbResult<'a>: Sized {
+    #[doc(hidden)]
+    fn take(id: libc::c_int, doc: *const Document<'a>) -> Self;
+}
+
+impl<'a> IdbResult<'a> for () {
+    fn take(id: libc::c_int, _: *const Document<'a>) {
+        js_guarded! { (id) "\
+            WEBPLATFORM.ref_free($0);\
+        " };
+    }
+}
+
+impl<'a> IdbResult<'a> for u32 {
+    fn take(id: libc::c_int, _: *const Document<'a>) -> u32 {
+        f64::from_ref(id).unwrap_or_else(JsError::raise) as u32
+    }
+}
+
+/// The key `put` stored the record under: the given one or a generated number.
+impl<'a> IdbResult<'a> for Key {
+    fn take(id: libc::c_int, _: *const Document<'a>) -> Key {
+        Key::take(id).unwrap_or_else(JsError::raise)
+    }
+}
+
+/// A key read from the store, which may have been written with an unsupported key type.
+impl<'a> IdbResult<'a> for Result<Key, JsError> {
+    fn take(id: libc::c_int, _: *const Document<'a>) -> Result<Key, JsError> {
+        Key::take(id)
+    }
+}
+
+/// A record value; `None` when no record matched.
+impl<'a> IdbResult<'a> for Option<Vec<u8>> {
+    fn take(id: libc::c_int, _: *const Document<'a>) -> Option<Vec<u8>> {
+        let found = js_guarded! { (id) "\
+            var record = WEBPLATFORM.rs_refs[$0];\
+            if (record === undefined) {\
+                WEBPLATFORM.ref_free($0);\
+                return 0;\
+            }\
+            WEBPLATFORM.rs_refs[$0] = record.value;\
+            return 1;\
+        " };
+        if found == 0 {
+            None
+        } else {
+            Some(ref_bytes(id))
+        }
+    }
+}
+
+impl<'a> IdbResult<'a> for Database<'a> {
+    fn take(id: libc::c_int, doc: *const Document<'a>) -> Database<'a> {
+        Database { id, doc }
+    }
+}
+
+/// The cursor positioned on the next record; `None` once the range is exhausted.
+impl<'a> IdbResult<'a> for Option<Cursor<'a>> {
+    fn take(id: libc::c_int, doc: *const Document<'a>) -> Option<Cursor<'a>> {
+        let found = js_guarded! { (id) "\
+            if (WEBPLATFORM.rs_refs[$0] == null) {\
+                WEBPLATFORM.ref_free($0);\
+                return 0;\
+            }\
+            return 1;\
+        " };
+        if found == 0 {
+            None
+        } else {
+            Some(Cursor { id, doc })
+        }
+    }
+}
+
+/// A pending IndexedDB request delivering a `T` on success.
+pub struct IdbRequest<'a, T: IdbResult<'a>> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+    result: PhantomData<T>,
+}
+
+impl<'a, T: IdbResult<'a>> Drop for IdbRequest<'a, T> {
+    fn drop(&mut self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.ref_free($0);\
+        " };
+    }
+}
+
+impl<'a, T: IdbResult<'a>> IdbRequest<'a, T> {
+    fn new(id: libc::c_int, doc: *const Document<'a>) -> Self {
+        IdbRequest { id, doc, result: PhantomData }
+    }
+
+    /// Calls `f` with the result. Cursor requests succeed once per record.
+    pub fn on_success<F: FnMut(T) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_success(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_success<F: FnMut(T) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let doc = self.doc;
+        let b = Box::new(move |id| f(T::take(id, doc)));
+        let a = &*b as *const _;
+        let id = js_try! { (self.id, a as *const libc::c_void, caller_v_i32(&*b)) "\
+            return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], 'success', false, function (e) {\
+                Runtime.dynCall('vii', $2, [$1, WEBPLATFORM.ref_push(e.target.result)]);\
+            });\
+        " }?;
+        Ok(ListenerHandle::new(id, self.doc, b))
+    }
+
+    pub fn on_error<F: FnMut(JsError) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_error(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_error<F: FnMut(JsError) + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        try_listen_error(self.id, self.doc, f)
+    }
+}
+
+impl<'a> IdbRequest<'a, Database<'a>> {
+    /// Calls `f` when the database is created or its version is raised. Object stores and
+    /// indexes can only be created or deleted from here.
+    pub fn on_upgrade_needed<F: FnMut(Database<'a>, VersionChange) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_upgrade_needed(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_upgrade_needed<F: FnMut(Database<'a>, VersionChange) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let doc = self.doc;
+        let b = Box::new(move |id: libc::c_int| {
+            let old_version = js_guarded! { (id) "\
+                return WEBPLATFORM.rs_refs[$0].oldVersion;\
+            " };
+            let new_version = js_guarded! { (id) "\
+                return WEBPLATFORM.rs_refs[$0].newVersion;\
+            " };
+            let db = js_guarded! { (id) "\
+                var e = WEBPLATFORM.rs_refs[$0];\
+                WEBPLATFORM.ref_free($0);\
+                return WEBPLATFORM.ref_push(e.result);\
+            " };
+            f(Database { id: db, doc }, VersionChange {
+                old_version: old_version as u32,
+                new_version: new_version as u32,
+            })
+        });
+        let a = &*b as *const _;
+        let id = js_try! { (self.id, a as *const libc::c_void, caller_v_i32(&*b)) "\
+            var request = WEBPLATFORM.rs_refs[$0];\
+            return WEBPLATFORM.listen(request, 'upgradeneeded', false, function (e) {\
+                Runtime.dynCall('vii', $2, [$1, WEBPLATFORM.ref_push({\
+                    oldVersion: e.oldVersion, newVersion: e.newVersion, result: request.result,\
+                })]);\
+            });\
+        " }?;
+        Ok(ListenerHandle::new(id, self.doc, b))
+    }
+
+    /// Calls `f` when the upgrade waits for connections in other tabs to close.
+    pub fn on_blocked<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_blocked(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_blocked<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        try_listen_v(self.id, self.doc, "blocked", f)
+    }
+}
+
+pub(crate) fn open<'a>(doc: *const Document<'a>, name: &str, version: u32) -> Result<IdbRequest<'a, Database<'a>>, JsError> {
+    let id = js_try! { (name, version as i32) "\
+        return WEBPLATFORM.ref_push(indexedDB.open(UTF8ToString($0), $1));\
+    " }?;
+    Ok(IdbRequest::new(id, doc))
+}
+
+pub(crate) fn delete_database<'a>(doc: *const Document<'a>, name: &str) -> Result<IdbRequest<'a, ()>, JsError> {
+    let id = js_try! { (name) "\
+        return WEBPLATFORM.ref_push(indexedDB.deleteDatabase(UTF8ToString($0)));\
+    " }?;
+    Ok(IdbRequest::new(id, doc))
+}
+
+// =================================================================================================
+
+/// An open connection to a database.
+pub struct Database<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+idb_handle!(Database);
+
+impl<'a> Database<'a> {
+    pub fn name(&self) -> String {
+        self.try_name().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_name(&self) -> Result<String, JsError> {
+        js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].name;\
+        " }
+    }
+
+    pub fn version(&self) -> u32 {
+        self.try_version().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_version(&self) -> Result<u32, JsError> {
+        let a = js_try! { (self.id) "\
+            return WEBPLATFORM.rs_refs[$0].version;\
+        " }?;
+        Ok(a as u32)
+    }
+
+    pub fn object_store_names(&self) -> Vec<String> {
+        self.try_object_store_names().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_object_store_names(&self) -> Result<Vec<String>, JsError> {
+        let len = js_try! { (self.id) "\
+            return WEBPLATFORM.rs_refs[$0].objectStoreNames.length;\
+        " }?;
+        let mut names = Vec::with_capacity(len as usize);
+        for i in 0..len {
+            let id = js_try! { (self.id, i) "\
+                return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0].objectStoreNames.item($1));\
+            " }?;
+            names.push(try_ref_string(id)?);
+        }
+        Ok(names)
+    }
+
+    /// Creates an object store. Only allowed in `on_upgrade_needed`.
+    ///
+    /// Records are binary, so the keys are given on `put` unless `auto_increment` is set.
+    pub fn create_object_store(&self, name: &str, auto_increment: bool) -> ObjectStore<'a> {
+        self.try_create_object_store(name, auto_increment).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_create_object_store(&self, name: &str, auto_increment: bool) -> Result<ObjectStore<'a>, JsError> {
+        let id = js_try! { (self.id, name, auto_increment as i32) "\
+            var store = WEBPLATFORM.rs_refs[$0].createObjectStore(UTF8ToString($1), { autoIncrement: !!$2 });\
+            return WEBPLATFORM.ref_push(store);\
+        " }?;
+        Ok(ObjectStore { id, doc: self.doc })
+    }
+
+    /// Deletes an object store. Only allowed in `on_upgrade_needed`.
+    pub fn delete_object_store(&self, name: &str) {
+        self.try_delete_object_store(name).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_delete_object_store(&self, name: &str) -> Result<(), JsError> {
+        js_try! { (self.id, name) "\
+            WEBPLATFORM.rs_refs[$0].deleteObjectStore(UTF8ToString($1));\
+        " }?;
+        Ok(())
+    }
+
+    /// Starts a transaction over `stores`. It commits once no request is pending.
+    pub fn transaction(&self, stores: &[&str], mode: TransactionMode) -> Transaction<'a> {
+        self.try_transaction(stores, mode).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_transaction(&self, stores: &[&str], mode: TransactionMode) -> Result<Transaction<'a>, JsError> {
+        let names = js_try! { "\
+            return WEBPLATFORM.ref_push([]);\
+        " }?;
+        for store in stores {
+            js_try! { (names, *store) "\
+                WEBPLATFORM.rs_refs[$0].push(UTF8ToString($1));\
+            " }?;
+        }
+        let id = js_try! { (self.id, names, mode.as_str()) "\
+            var names = WEBPLATFORM.rs_refs[$1];\
+            WEBPLATFORM.ref_free($1);\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0].transaction(names, UTF8ToString($2)));\
+        " }?;
+        Ok(Transaction { id, doc: self.doc })
+    }
+
+    /// Calls `f` when another connection wants to upgrade the database; close this one to let
+    /// it proceed.
+    pub fn on_version_change<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_version_change(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_version_change<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        try_listen_v(self.id, self.doc, "versionchange", f)
+    }
+
+    pub fn close(&self) {
+        self.try_close().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_close(&self) -> Result<(), JsError> {
+        js_try! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].close();\
+        " }?;
+        Ok(())
+    }
+}
+
+// =================================================================================================
+
+pub struct Transaction<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+idb_handle!(Transaction);
+
+impl<'a> Transaction<'a> {
+    pub fn object_store(&self, name: &str) -> ObjectStore<'a> {
+        self.try_object_store(name).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_object_store(&self, name: &str) -> Result<ObjectStore<'a>, JsError> {
+        let id = js_try! { (self.id, name) "\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0].objectStore(UTF8ToString($1)));\
+        " }?;
+        Ok(ObjectStore { id, doc: self.doc })
+    }
+
+    pub fn abort(&self) {
+        self.try_abort().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_abort(&self) -> Result<(), JsError> {
+        js_try! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].abort();\
+        " }?;
+        Ok(())
+    }
+
+    pub fn on_complete<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_complete(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_complete<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        try_listen_v(self.id, self.doc, "complete", f)
+    }
+
+    pub fn on_abort<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_abort(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_abort<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        try_listen_v(self.id, self.doc, "abort", f)
+    }
+
+    /// Calls `f` with the error of any failed request of the transaction.
+    pub fn on_error<F: FnMut(JsError) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_error(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_error<F: FnMut(JsError) + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        try_listen_error(self.id, self.doc, f)
+    }
+}
+
+// =================================================================================================
+
+/// Shared by object stores and indexes, which differ only in how records are keyed.
+fn try_request<'a, T: IdbResult<'a>>(target: libc::c_int, doc: *const Document<'a>, method: &str, key: Option<&Key>) -> Result<IdbRequest<'a, T>, JsError> {
+    let key = match key {
+        Some(key) => key.try_push()?,
+        None => -1,
+    };
+    let id = js_try! { (target, method, key) "\
+        var key;\
+        if ($2 >= 0) {\
+            key = WEBPLATFORM.rs_refs[$2];\
+            WEBPLATFORM.ref_free($2);\
+        }\
+        return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0][UTF8ToString($1)](key));\
+    " }?;
+    Ok(IdbRequest::new(id, doc))
+}
+
+pub struct ObjectStore<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+idb_handle!(ObjectStore);
+
+impl<'a> ObjectStore<'a> {
+    /// Stores `value` under `key`, or under the next generated key if `key` is `None` and
+    /// the store auto-increments. The request delivers the key.
+    ///
+    /// `fields` are stored next to the value and are what indexes created with
+    /// `create_index` look up.
+    pub fn put(&self, key: Option<&Key>, value: &[u8], fields: &[(&str, &Key)]) -> IdbRequest<'a, Key> {
+        self.try_put(key, value, fields).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_put(&self, key: Option<&Key>, value: &[u8], fields: &[(&str, &Key)]) -> Result<IdbRequest<'a, Key>, JsError> {
+        let record = js_try! { (value) "\
+            return WEBPLATFORM.ref_push({ value: WEBPLATFORM.u8_slice($0).slice().buffer, fields: {} });\
+        " }?;
+        for &(name, field) in fields {
+            let field = field.try_push()?;
+            js_try! { (record, name, field) "\
+                WEBPLATFORM.rs_refs[$0].fields[UTF8ToString($1)] = WEBPLATFORM.rs_refs[$2];\
+                WEBPLATFORM.ref_free($2);\
+            " }?;
+        }
+        let key = match key {
+            Some(key) => key.try_push()?,
+            None => -1,
+        };
+        let id = js_try! { (self.id, record, key) "\
+            var record = WEBPLATFORM.rs_refs[$1];\
+            WEBPLATFORM.ref_free($1);\
+            var store = WEBPLATFORM.rs_refs[$0];\
+            if ($2 < 0) {\
+                return WEBPLATFORM.ref_push(store.put(record));\
+            }\
+            var key = WEBPLATFORM.rs_refs[$2];\
+            WEBPLATFORM.ref_free($2);\
+            return WEBPLATFORM.ref_push(store.put(record, key));\
+        " }?;
+        Ok(IdbRequest::new(id, self.doc))
+    }
+
+    pub fn get(&self, key: &Key) -> IdbRequest<'a, Option<Vec<u8>>> {
+        self.try_get(key).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_get(&self, key: &Key) -> Result<IdbRequest<'a, Option<Vec<u8>>>, JsError> {
+        try_request(self.id, self.doc, "get", Some(key))
+    }
+
+    pub fn delete(&self, key: &Key) -> IdbRequest<'a, ()> {
+        self.try_delete(key).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_delete(&self, key: &Key) -> Result<IdbRequest<'a, ()>, JsError> {
+        try_request(self.id, self.doc, "delete", Some(key))
+    }
+
+    pub fn clear(&self) -> IdbRequest<'a, ()> {
+        self.try_clear().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_clear(&self) -> Result<IdbRequest<'a, ()>, JsError> {
+        try_request(self.id, self.doc, "clear", None)
+    }
+
+    pub fn count(&self) -> IdbRequest<'a, u32> {
+        self.try_count().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_count(&self) -> Result<IdbRequest<'a, u32>, JsError> {
+        try_request(self.id, self.doc, "count", None)
+    }
+
+    /// Opens a cursor over all records, or over the record under `key`.
+    pub fn open_cursor(&self, key: Option<&Key>) -> IdbRequest<'a, Option<Cursor<'a>>> {
+        self.try_open_cursor(key).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_open_cursor(&self, key: Option<&Key>) -> Result<IdbRequest<'a, Option<Cursor<'a>>>, JsError> {
+        try_request(self.id, self.doc, "openCursor", key)
+    }
+
+    /// Creates an index over the `field` given to `put`. Records stored without the field are
+    /// left out of the index. Only allowed in `on_upgrade_needed`.
+    pub fn create_index(&self, name: &str, field: &str, unique: bool) -> Index<'a> {
+        self.try_create_index(name, field, unique).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_create_index(&self, name: &str, field: &str, unique: bool) -> Result<Index<'a>, JsError> {
+        let id = js_try! { (self.id, name, field, unique as i32) "\
+            var index = WEBPLATFORM.rs_refs[$0].createIndex(UTF8ToString($1), ['fields', UTF8ToString($2)].join('.'), { unique: !!$3 });\
+            return WEBPLATFORM.ref_push(index);\
+        " }?;
+        Ok(Index { id, doc: self.doc })
+    }
+
+    pub fn index(&self, name: &str) -> Index<'a> {
+        self.try_index(name).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_index(&self, name: &str) -> Result<Index<'a>, JsError> {
+        let id = js_try! { (self.id, name) "\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0].index(UTF8ToString($1)));\
+        " }?;
+        Ok(Index { id, doc: self.doc })
+    }
+
+    /// Deletes an index. Only allowed in `on_upgrade_needed`.
+    pub fn delete_index(&self, name: &str) {
+        self.try_delete_index(name).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_delete_index(&self, name: &str) -> Result<(), JsError> {
+        js_try! { (self.id, name) "\
+            WEBPLATFORM.rs_refs[$0].deleteIndex(UTF8ToString($1));\
+        " }?;
+        Ok(())
+    }
+}
+
+// =================================================================================================
+
+pub struct Index<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+idb_handle!(Index);
+
+impl<'a> Index<'a> {
+    /// Gets the first record whose index key is `key`.
+    pub fn get(&self, key: &Key) -> IdbRequest<'a, Option<Vec<u8>>> {
+        self.try_get(key).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_get(&self, key: &Key) -> Result<IdbRequest<'a, Option<Vec<u8>>>, JsError> {
+        try_request(self.id, self.doc, "get", Some(key))
+    }
+
+    /// Gets the primary key of the first record whose index key is `key`.
+    pub fn get_key(&self, key: &Key) -> IdbRequest<'a, Result<Key, JsError>> {
+        self.try_get_key(key).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_get_key(&self, key: &Key) -> Result<IdbRequest<'a, Result<Key, JsError>>, JsError> {
+        try_request(self.id, self.doc, "getKey", Some(key))
+    }
+
+    pub fn count(&self) -> IdbRequest<'a, u32> {
+        self.try_count().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_count(&self) -> Result<IdbRequest<'a, u32>, JsError> {
+        try_request(self.id, self.doc, "count", None)
+    }
+
+    /// Opens a cursor over all records in index order, or over those whose index key is `key`.
+    pub fn open_cursor(&self, key: Option<&Key>) -> IdbRequest<'a, Option<Cursor<'a>>> {
+        self.try_open_cursor(key).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_open_cursor(&self, key: Option<&Key>) -> Result<IdbRequest<'a, Option<Cursor<'a>>>, JsError> {
+        try_request(self.id, self.doc, "openCursor", key)
+    }
+}
+
+// =================================================================================================
+
+/// A position in a cursor request. Call `continue_` to have the request succeed again with
+/// the next record.
+pub struct Cursor<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+idb_handle!(Cursor);
+
+impl<'a> Cursor<'a> {
+    /// The key of the record in the source: the index key for index cursors.
+    pub fn key(&self) -> Key {
+        self.try_key().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_key(&self) -> Result<Key, JsError> {
+        let id = js_try! { (self.id) "\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0].key);\
+        " }?;
+        Key::take(id)
+    }
+
+    pub fn primary_key(&self) -> Key {
+        self.try_primary_key().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_primary_key(&self) -> Result<Key, JsError> {
+        let id = js_try! { (self.id) "\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0].primaryKey);\
+        " }?;
+        Key::take(id)
+    }
+
+    pub fn value(&self) -> Vec<u8> {
+        self.try_value().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_value(&self) -> Result<Vec<u8>, JsError> {
+        let id = js_try! { (self.id) "\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0].value.value);\
+        " }?;
+        try_ref_bytes(id)
+    }
+
+    pub fn continue_(&self) {
+        self.try_continue_().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_continue_(&self) -> Result<(), JsError> {
+        js_try! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].continue();\
+        " }?;
+        Ok(())
+    }
+
+    pub fn delete(&self) -> IdbRequest<'a, ()> {
+        self.try_delete().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_delete(&self) -> Result<IdbRequest<'a, ()>, JsError> {
+        try_request(self.id, self.doc, "delete", None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_conversions() {
+        assert_eq!(Key::from("a"), Key::String("a".to_string()));
+        assert_eq!(Key::from(1), Key::Number(1.0));
+        assert_eq!(Key::from(0.5), Key::Number(0.5));
+    }
+
+    #[test]
+    fn test_key_round_trip() {
+        let _document = init();
+        for key in vec![Key::Number(7.0), Key::Number(-2.5), Key::Number(3e10), Key::String("seven".to_string())] {
+            assert_eq!(Key::take(key.try_push().unwrap()).unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_unsupported_key_rejected() {
+        let _document = init();
+        let id = js_guarded! { "\
+            return WEBPLATFORM.ref_push(new Date(0));\
+        " };
+        assert_eq!(Key::take(id).err().unwrap().name, "DataError");
+        let freed = js_guarded! { (id) -> bool, "\
+            return WEBPLATFORM.rs_refs[$0] === null;\
+        " };
+        assert!(freed);
+    }
+
+    // The requests complete on later turns of the event loop, so the checks run in the
+    // callbacks and a failure aborts the runtime. The document is leaked so that the forgotten
+    // callbacks outlive the test function, and `on_complete` checks that all of them ran.
+    #[test]
+    fn test_open_put_get() {
+        use std::cell::{Cell, RefCell};
+        use std::rc::Rc;
+
+        let document: &'static Document<'static> = Box::leak(Box::new(init()));
+        let window = document.window();
+        window.indexed_db_delete("webplatform-test").on_success(|()| {}).forget();
+        let request = window.indexed_db_open("webplatform-test", 1);
+        request.on_upgrade_needed(|db, _| {
+            let store = db.create_object_store("records", false);
+            store.create_index("by_name", "name", true);
+        }).forget();
+        request.on_success(|db: Database<'static>| {
+            let done = Rc::new(Cell::new(0));
+            let tx = db.transaction(&["records"], TransactionMode::ReadWrite);
+            let store = tx.object_store("records");
+            let seven = Key::from("seven");
+            let eight = Key::from("eight");
+            let d = done.clone();
+            store.put(Some(&Key::from(7.5)), &[1, 2, 3], &[("name", &seven)]).on_success(move |key| {
+                assert_eq!(key, Key::Number(7.5));
+                d.set(d.get() + 1);
+            }).forget();
+            let d = done.clone();
+            store.put(Some(&Key::from(8)), &[4], &[("name", &eight)]).on_success(move |key| {
+                assert_eq!(key, Key::Number(8.0));
+                d.set(d.get() + 1);
+            }).forget();
+            let d = done.clone();
+            store.get(&Key::from(7.5)).on_success(move |value| {
+                assert_eq!(value, Some(vec![1, 2, 3]));
+                d.set(d.get() + 1);
+            }).forget();
+            let d = done.clone();
+            store.index("by_name").get_key(&seven).on_success(move |key| {
+                assert_eq!(key.unwrap(), Key::Number(7.5));
+                d.set(d.get() + 1);
+            }).forget();
+            let d = done.clone();
+            store.get(&Key::from(9)).on_success(move |value| {
+                assert_eq!(value, None);
+                d.set(d.get() + 1);
+            }).forget();
+            let d = done.clone();
+            store.count().on_success(move |count| {
+                assert_eq!(count, 2);
+                d.set(d.get() + 1);
+            }).forget();
+            let d = done.clone();
+            let seen = Rc::new(RefCell::new(Vec::new()));
+            store.open_cursor(None).on_success(move |cursor: Option<Cursor<'static>>| {
+                match cursor {
+                    Some(cursor) => {
+                        seen.borrow_mut().push((cursor.key(), cursor.value()));
+                        cursor.continue_();
+                    }
+                    None => {
+                        assert_eq!(*seen.borrow(), vec![(Key::Number(7.5), vec![1, 2, 3]), (Key::Number(8.0), vec![4])]);
+                        d.set(d.get() + 1);
+                    }
+                }
+            }).forget();
+            tx.on_complete(move || {
+                assert_eq!(done.get(), 7);
+            }).forget();
+        }).forget();
+    }
+}
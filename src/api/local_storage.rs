@@ -1,88 +1,196 @@
 use std::iter::IntoIterator;
 
-use std::ffi::CStr;
-use std::str;
-use libc;
+use ::api::*;
+use ::error::*;
+use ::event::*;
 
 
 pub struct LocalStorageInterface;
 
-pub struct LocalStorageIterator {
-    index: i32,
-}
+/// What iterating over `LocalStorage` yields: the values, as before the `Storage` trait.
+pub type LocalStorageIterator = LocalStorageValues;
+
+pub type LocalStorageKeys = Keys<'static, LocalStorageInterface>;
+
+pub type LocalStorageValues = Values<'static, LocalStorageInterface>;
+
+pub type LocalStorageEntries = Entries<'static, LocalStorageInterface>;
 
 impl LocalStorageInterface {
+    /// Calls `f` when another document changes `localStorage`.
+    pub fn on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, f: F) -> ListenerHandle<'a> {
+        self.try_on_change(window, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        window.try_on_storage_change(move |e| if e.area == StorageArea::Local { f(e) })
+    }
+
+    // The `Storage` methods, also available without the trait in scope.
+
     pub fn len(&self) -> i32 {
-        js_guarded! { "\
-            return window.localStorage.length;\
-        " }
+        Storage::len(self)
+    }
+
+    pub fn try_len(&self) -> Result<i32, JsError> {
+        Storage::try_len(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Storage::is_empty(self)
     }
 
     pub fn clear(&self) {
-        js_guarded! { "\
-            window.localStorage.clear();\
-        " };
+        Storage::clear(self)
+    }
+
+    pub fn try_clear(&self) -> Result<(), JsError> {
+        Storage::try_clear(self)
     }
 
     pub fn remove(&self, s: &str) {
-        js_guarded! { (s) "\
-            window.localStorage.removeItem(UTF8ToString($0));\
-        " };
+        Storage::remove(self, s)
+    }
+
+    pub fn try_remove(&self, s: &str) -> Result<(), JsError> {
+        Storage::try_remove(self, s)
     }
 
     pub fn set(&self, s: &str, v: &str) {
-        js_guarded! { (s, v) "\
-            window.localStorage.setItem(UTF8ToString($0), UTF8ToString($1));\
-        " };
+        Storage::set(self, s, v)
+    }
+
+    pub fn try_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        Storage::try_set(self, s, v)
     }
 
     pub fn get(&self, name: &str) -> Option<String> {
-        let a = js_guarded! { (name) "\
-            var str = window.localStorage.getItem(UTF8ToString($0));\
-            if (str == null) {\
-                return -1;\
-            }\
-            return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
-        " };
-        if a == -1 {
-            None
-        } else {
-            Some(unsafe {
-                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-            })
-        }
-    }
-
-    pub fn key(&self, index: i32) -> String {
-        let a = js_guarded! { (index) "\
-            var key = window.localStorage.key($0);\
-            return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
-        " };
-        unsafe {
-            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-        }
+        Storage::get(self, name)
+    }
+
+    pub fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        Storage::try_get(self, name)
+    }
+
+    /// Returns the name of the key at `index`, or `None` past the end.
+    pub fn key(&self, index: i32) -> Option<String> {
+        Storage::key(self, index)
+    }
+
+    pub fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        Storage::try_key(self, index)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        Storage::contains_key(self, name)
+    }
+
+    pub fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        Storage::try_contains_key(self, name)
+    }
+
+    pub fn keys(&self) -> LocalStorageKeys {
+        StorageExt::keys(&LocalStorage)
+    }
+
+    pub fn values(&self) -> LocalStorageValues {
+        StorageExt::values(&LocalStorage)
+    }
+
+    pub fn entries(&self) -> LocalStorageEntries {
+        StorageExt::entries(&LocalStorage)
     }
 }
 
-impl IntoIterator for LocalStorageInterface {
-    type Item = String;
-    type IntoIter = LocalStorageIterator;
+impl Storage for LocalStorageInterface {
+    fn try_len(&self) -> Result<i32, JsError> {
+        web_len("localStorage")
+    }
 
-    fn into_iter(self) -> LocalStorageIterator {
-        LocalStorageIterator { index: 0 }
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        web_key("localStorage", index)
+    }
+
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        web_get("localStorage", name)
+    }
+
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> {
+        web_set("localStorage", name, value)
+    }
+
+    fn try_remove(&self, name: &str) -> Result<(), JsError> {
+        web_remove("localStorage", name)
+    }
+
+    fn try_clear(&self) -> Result<(), JsError> {
+        web_clear("localStorage")
+    }
+
+    fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        web_contains_key("localStorage", name)
     }
 }
 
-impl Iterator for LocalStorageIterator {
+/// Yields the values; use `entries` for `(key, value)` pairs.
+impl IntoIterator for LocalStorageInterface {
     type Item = String;
-    fn next(&mut self) -> Option<String> {
-        if self.index >= LocalStorage.len() {
-            None
-        } else {
-            LocalStorage.get(&LocalStorage.key(self.index))
-        }
+    type IntoIter = LocalStorageIterator;
+
+    fn into_iter(self) -> LocalStorageIterator {
+        LocalStorage.values()
     }
 }
 
 #[allow(non_upper_case_globals)]
 pub const LocalStorage: LocalStorageInterface = LocalStorageInterface;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_past_end() {
+        LocalStorage.clear();
+        LocalStorage.set("a", "1");
+        assert_eq!(LocalStorage.key(0), Some("a".to_string()));
+        assert_eq!(LocalStorage.key(1), None);
+        LocalStorage.clear();
+    }
+
+    #[test]
+    fn test_contains_key() {
+        LocalStorage.clear();
+        LocalStorage.set("a", "");
+        assert!(LocalStorage.contains_key("a"));
+        assert!(!LocalStorage.contains_key("b"));
+        LocalStorage.clear();
+    }
+
+    #[test]
+    fn test_iterators() {
+        LocalStorage.clear();
+        LocalStorage.set("a", "1");
+        LocalStorage.set("b", "2");
+
+        let mut keys: Vec<String> = LocalStorage.keys().collect();
+        keys.sort();
+        assert_eq!(keys, vec!["a", "b"]);
+
+        let mut values: Vec<String> = LocalStorage.values().collect();
+        values.sort();
+        assert_eq!(values, vec!["1", "2"]);
+
+        let mut iterated: Vec<String> = LocalStorage.into_iter().collect();
+        iterated.sort();
+        assert_eq!(iterated, vec!["1", "2"]);
+
+        let mut entries: Vec<(String, String)> = LocalStorage.entries().collect();
+        entries.sort();
+        assert_eq!(entries, vec![
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), "2".to_string()),
+        ]);
+        LocalStorage.clear();
+    }
+}
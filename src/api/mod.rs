@@ -1,12 +1,29 @@
 pub mod document;
+pub mod fetch;
+pub mod indexed_db;
 pub mod local_storage;
+pub mod reconnecting_websocket;
+pub mod session_storage;
+pub mod storage;
+pub mod timer;
 pub mod websocket;
+pub mod window;
+pub mod xml_http_request;
 
 pub use self::document::*;
+pub use self::fetch::*;
+pub use self::indexed_db::*;
 pub use self::local_storage::*;
+pub use self::reconnecting_websocket::*;
+pub use self::session_storage::*;
+pub use self::storage::*;
+pub use self::timer::*;
 pub use self::websocket::*;
+pub use self::window::*;
+pub use self::xml_http_request::*;
 
 
+#[deprecated(note = "use `Window::alert`")]
 pub fn alert(s: &str) {
     js_guarded! { (s) "\
         alert(UTF8ToString($0));\
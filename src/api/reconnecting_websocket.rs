@@ -0,0 +1,325 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::mem;
+use std::rc::{Rc, Weak};
+use ::api::*;
+use ::event::*;
+
+
+/// Exponential backoff between reconnection attempts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// Delay before the first reconnection attempt, in milliseconds.
+    pub initial_ms: i32,
+    /// Upper bound for the delay, in milliseconds.
+    pub max_ms: i32,
+    /// Multiplier applied to the delay after every failed attempt.
+    pub factor: f64,
+    /// Random spread applied to every delay, as a fraction of it: `0.2` means ±20%.
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial_ms: 500,
+            max_ms: 30_000,
+            factor: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl Backoff {
+    /// The delay before attempt number `attempt` (starting at 0), given a random value in `[0, 1)`.
+    pub fn delay(&self, attempt: u32, random: f64) -> i32 {
+        let base = (self.initial_ms as f64 * self.factor.powi(attempt as i32)).min(self.max_ms as f64);
+        let spread = 1.0 - self.jitter + 2.0 * self.jitter * random;
+        (base * spread).max(0.0) as i32
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Open,
+    /// Waiting `delay_ms` before reconnection attempt number `attempt`.
+    Reconnecting { attempt: u32, delay_ms: i32 },
+    /// Closed with `close`; no further attempts are made.
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+struct Inner<'a> {
+    window: Window<'a>,
+    url: String,
+    protocols: Vec<String>,
+    backoff: Backoff,
+    attempt: u32,
+    state: ConnectionState,
+    socket: Option<WebSocket<'a>>,
+    listeners: Vec<ListenerHandle<'a>>,
+    timer: Option<TimerHandle<'a>>,
+    queue: VecDeque<Message>,
+    on_state_change: Option<Box<FnMut(ConnectionState) + 'a>>,
+    on_message_string: Option<Box<FnMut(String) + 'a>>,
+    on_message_binary: Option<Box<FnMut(&[u8]) + 'a>>,
+}
+
+/// A WebSocket that reconnects with exponential backoff after the connection is lost, queueing
+/// outgoing messages while it is down and sending them once it is back.
+pub struct ReconnectingWebSocket<'a> {
+    inner: Rc<RefCell<Inner<'a>>>,
+}
+
+impl<'a> ReconnectingWebSocket<'a> {
+    pub fn new(window: Window<'a>, url: &str, backoff: Backoff) -> Self {
+        ReconnectingWebSocket::with_protocols(window, url, &[], backoff)
+    }
+
+    pub fn with_protocols(window: Window<'a>, url: &str, protocols: &[&str], backoff: Backoff) -> Self {
+        let inner = Rc::new(RefCell::new(Inner {
+            window,
+            url: url.to_string(),
+            protocols: protocols.iter().map(|p| p.to_string()).collect(),
+            backoff,
+            attempt: 0,
+            state: ConnectionState::Connecting,
+            socket: None,
+            listeners: Vec::new(),
+            timer: None,
+            queue: VecDeque::new(),
+            on_state_change: None,
+            on_message_string: None,
+            on_message_binary: None,
+        }));
+        connect(&inner);
+        ReconnectingWebSocket { inner }
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.inner.borrow().state
+    }
+
+    /// Sets the callback for state transitions, replacing the previous one.
+    pub fn on_state_change<F: FnMut(ConnectionState) + 'a>(&self, f: F) {
+        self.inner.borrow_mut().on_state_change = Some(Box::new(f));
+    }
+
+    /// Sets the callback for text messages, replacing the previous one.
+    pub fn on_message_string<F: FnMut(String) + 'a>(&self, f: F) {
+        self.inner.borrow_mut().on_message_string = Some(Box::new(f));
+    }
+
+    /// Sets the callback for binary messages, replacing the previous one.
+    pub fn on_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) {
+        self.inner.borrow_mut().on_message_binary = Some(Box::new(f));
+    }
+
+    /// Sends `data` now if connected, or once the connection is back.
+    pub fn send(&self, data: &str) {
+        send(&self.inner, Message::Text(data.to_string()));
+    }
+
+    /// Sends `data` now if connected, or once the connection is back.
+    pub fn send_binary(&self, data: &[u8]) {
+        send(&self.inner, Message::Binary(data.to_vec()));
+    }
+
+    /// The number of messages waiting for the connection.
+    pub fn queued(&self) -> usize {
+        self.inner.borrow().queue.len()
+    }
+
+    /// Closes the connection for good and drops the queued messages.
+    pub fn close(&self) {
+        {
+            let mut inner = self.inner.borrow_mut();
+            if inner.state == ConnectionState::Closed {
+                return;
+            }
+            inner.timer = None;
+            inner.queue.clear();
+            if let Some(ref socket) = inner.socket {
+                let _ = socket.try_close("");
+            }
+        }
+        set_state(&self.inner, ConnectionState::Closed);
+    }
+}
+
+impl<'a> Drop for ReconnectingWebSocket<'a> {
+    fn drop(&mut self) {
+        self.close();
+        // We may be running inside one of the socket listeners, so release them on a fresh task.
+        let (window, socket, listeners) = {
+            let mut inner = self.inner.borrow_mut();
+            (inner.window, inner.socket.take(), mem::replace(&mut inner.listeners, Vec::new()))
+        };
+        window.set_timeout(0, move || {
+            drop(listeners);
+            drop(socket);
+        }).forget();
+    }
+}
+
+fn connect<'a>(rc: &Rc<RefCell<Inner<'a>>>) {
+    let (window, url, protocols) = {
+        let mut inner = rc.borrow_mut();
+        // Called from the reconnect timer or the constructor, never from a socket listener, so
+        // the old listeners can go right away.
+        inner.timer = None;
+        inner.socket = None;
+        inner.listeners.clear();
+        (inner.window, inner.url.clone(), inner.protocols.clone())
+    };
+    let protocols: Vec<&str> = protocols.iter().map(|p| p.as_str()).collect();
+    let socket = match window.try_websocket_create_with_protocols(&url, &protocols) {
+        Ok(Some(socket)) => socket,
+        _ => {
+            schedule_reconnect(rc);
+            return;
+        }
+    };
+
+    let weak = Rc::downgrade(rc);
+    let listeners = vec![
+        socket.on_open(with_inner(&weak, opened)),
+        socket.on_error(with_inner(&weak, schedule_reconnect)),
+        socket.on_close({
+            let weak = weak.clone();
+            move |_| if let Some(rc) = weak.upgrade() { schedule_reconnect(&rc) }
+        }),
+        socket.on_message_string({
+            let weak = weak.clone();
+            move |data| if let Some(rc) = weak.upgrade() {
+                with_callback(&rc, |i| &mut i.on_message_string, |f| f(data));
+            }
+        }),
+        socket.on_message_binary({
+            let weak = weak.clone();
+            move |data| if let Some(rc) = weak.upgrade() {
+                with_callback(&rc, |i| &mut i.on_message_binary, |f| f(data));
+            }
+        }),
+    ];
+    {
+        let mut inner = rc.borrow_mut();
+        inner.socket = Some(socket);
+        inner.listeners = listeners;
+    }
+    set_state(rc, ConnectionState::Connecting);
+}
+
+fn opened<'a>(rc: &Rc<RefCell<Inner<'a>>>) {
+    {
+        let mut inner = rc.borrow_mut();
+        inner.attempt = 0;
+        inner.state = ConnectionState::Open;
+        flush(&mut inner);
+    }
+    set_state(rc, ConnectionState::Open);
+}
+
+fn schedule_reconnect<'a>(rc: &Rc<RefCell<Inner<'a>>>) {
+    let state = {
+        let mut inner = rc.borrow_mut();
+        if inner.state == ConnectionState::Closed || inner.timer.is_some() {
+            return;
+        }
+        let random = js_guarded! { "return (Math.random() * 1000000) | 0;" } as f64 / 1000000.0;
+        let attempt = inner.attempt;
+        let delay_ms = inner.backoff.delay(attempt, random);
+        inner.attempt += 1;
+        let weak = Rc::downgrade(rc);
+        inner.timer = Some(inner.window.set_timeout(delay_ms, move || {
+            if let Some(rc) = weak.upgrade() {
+                connect(&rc);
+            }
+        }));
+        ConnectionState::Reconnecting { attempt: attempt + 1, delay_ms }
+    };
+    set_state(rc, state);
+}
+
+fn send<'a>(rc: &Rc<RefCell<Inner<'a>>>, message: Message) {
+    let mut inner = rc.borrow_mut();
+    inner.queue.push_back(message);
+    if inner.state == ConnectionState::Open {
+        flush(&mut inner);
+    }
+}
+
+/// Sends queued messages in order, keeping whatever the socket refuses.
+fn flush<'a>(inner: &mut Inner<'a>) {
+    let socket = match inner.socket {
+        Some(ref socket) => socket,
+        None => return,
+    };
+    while let Some(message) = inner.queue.pop_front() {
+        let sent = match message {
+            Message::Text(ref s) => socket.try_send(s),
+            Message::Binary(ref b) => socket.try_send_binary(b),
+        };
+        if sent.is_err() {
+            inner.queue.push_front(message);
+            break;
+        }
+    }
+}
+
+fn set_state<'a>(rc: &Rc<RefCell<Inner<'a>>>, state: ConnectionState) {
+    rc.borrow_mut().state = state;
+    with_callback(rc, |i| &mut i.on_state_change, |f| f(state));
+}
+
+/// Calls a user callback without holding the borrow, so that it may call back into the socket.
+fn with_callback<'a, C: ?Sized, G: FnOnce(&mut C)>(rc: &Rc<RefCell<Inner<'a>>>, slot: for<'r> fn(&'r mut Inner<'a>) -> &'r mut Option<Box<C>>, g: G) {
+    let callback = slot(&mut rc.borrow_mut()).take();
+    if let Some(mut callback) = callback {
+        g(&mut *callback);
+        let mut inner = rc.borrow_mut();
+        let slot = slot(&mut inner);
+        if slot.is_none() {
+            *slot = Some(callback);
+        }
+    }
+}
+
+fn with_inner<'a>(weak: &Weak<RefCell<Inner<'a>>>, f: fn(&Rc<RefCell<Inner<'a>>>)) -> impl FnMut() + 'a {
+    let weak = weak.clone();
+    move || if let Some(rc) = weak.upgrade() { f(&rc) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff { initial_ms: 100, max_ms: 1000, factor: 2.0, jitter: 0.5 };
+        assert_eq!(backoff.delay(0, 0.5), 100);
+        assert_eq!(backoff.delay(3, 0.5), 800);
+        assert_eq!(backoff.delay(10, 0.5), 1000);
+        assert_eq!(backoff.delay(0, 0.0), 50);
+        assert_eq!(backoff.delay(10, 0.999), 1499);
+    }
+
+    #[test]
+    fn test_queue_while_connecting() {
+        let document = init();
+        let socket = ReconnectingWebSocket::new(document.window(), "ws://localhost:9", Backoff::default());
+        assert_eq!(socket.state(), ConnectionState::Connecting);
+        socket.send("hello");
+        socket.send_binary(&[1, 2, 3]);
+        assert_eq!(socket.queued(), 2);
+        socket.close();
+        assert_eq!(socket.state(), ConnectionState::Closed);
+        assert_eq!(socket.queued(), 0);
+    }
+}
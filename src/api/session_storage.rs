@@ -0,0 +1,131 @@
+use ::api::*;
+use ::error::*;
+use ::event::*;
+
+
+pub struct SessionStorageInterface;
+
+pub type SessionStorageKeys = Keys<'static, SessionStorageInterface>;
+
+pub type SessionStorageValues = Values<'static, SessionStorageInterface>;
+
+pub type SessionStorageEntries = Entries<'static, SessionStorageInterface>;
+
+impl SessionStorageInterface {
+    /// Calls `f` when another document in the same session changes `sessionStorage`.
+    pub fn on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, f: F) -> ListenerHandle<'a> {
+        self.try_on_change(window, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        window.try_on_storage_change(move |e| if e.area == StorageArea::Session { f(e) })
+    }
+
+    // The `Storage` methods, also available without the trait in scope.
+
+    pub fn len(&self) -> i32 {
+        Storage::len(self)
+    }
+
+    pub fn try_len(&self) -> Result<i32, JsError> {
+        Storage::try_len(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Storage::is_empty(self)
+    }
+
+    pub fn clear(&self) {
+        Storage::clear(self)
+    }
+
+    pub fn try_clear(&self) -> Result<(), JsError> {
+        Storage::try_clear(self)
+    }
+
+    pub fn remove(&self, s: &str) {
+        Storage::remove(self, s)
+    }
+
+    pub fn try_remove(&self, s: &str) -> Result<(), JsError> {
+        Storage::try_remove(self, s)
+    }
+
+    pub fn set(&self, s: &str, v: &str) {
+        Storage::set(self, s, v)
+    }
+
+    pub fn try_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        Storage::try_set(self, s, v)
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        Storage::get(self, name)
+    }
+
+    pub fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        Storage::try_get(self, name)
+    }
+
+    /// Returns the name of the key at `index`, or `None` past the end.
+    pub fn key(&self, index: i32) -> Option<String> {
+        Storage::key(self, index)
+    }
+
+    pub fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        Storage::try_key(self, index)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        Storage::contains_key(self, name)
+    }
+
+    pub fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        Storage::try_contains_key(self, name)
+    }
+
+    pub fn keys(&self) -> SessionStorageKeys {
+        StorageExt::keys(&SessionStorage)
+    }
+
+    pub fn values(&self) -> SessionStorageValues {
+        StorageExt::values(&SessionStorage)
+    }
+
+    pub fn entries(&self) -> SessionStorageEntries {
+        StorageExt::entries(&SessionStorage)
+    }
+}
+
+impl Storage for SessionStorageInterface {
+    fn try_len(&self) -> Result<i32, JsError> {
+        web_len("sessionStorage")
+    }
+
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        web_key("sessionStorage", index)
+    }
+
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        web_get("sessionStorage", name)
+    }
+
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> {
+        web_set("sessionStorage", name, value)
+    }
+
+    fn try_remove(&self, name: &str) -> Result<(), JsError> {
+        web_remove("sessionStorage", name)
+    }
+
+    fn try_clear(&self) -> Result<(), JsError> {
+        web_clear("sessionStorage")
+    }
+
+    fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        web_contains_key("sessionStorage", name)
+    }
+}
+
+#[allow(non_upper_case_globals)]
+pub const SessionStorage: SessionStorageInterface = SessionStorageInterface;
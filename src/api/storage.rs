@@ -0,0 +1,507 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
+use std::vec;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+use std::str;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+#[cfg(feature = "serde")]
+use serde_json;
+use ::error::*;
+
+
+/// A key-value string store with the semantics of the Web Storage API.
+///
+/// Implemented by `LocalStorage`, `SessionStorage` and `MemoryStorage`, and usable as a trait
+/// object to pick the backend at runtime. Iteration is provided by `StorageExt`.
+pub trait Storage {
+    fn try_len(&self) -> Result<i32, JsError>;
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError>;
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError>;
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError>;
+    fn try_remove(&self, name: &str) -> Result<(), JsError>;
+    fn try_clear(&self) -> Result<(), JsError>;
+
+    fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        self.try_get(name).map(|value| value.is_some())
+    }
+
+    /// All keys from a single scan, for stores where `try_key` is not a cheap lookup.
+    /// `StorageExt` iteration uses the list when this returns `Some`.
+    fn try_all_keys(&self) -> Result<Option<Vec<String>>, JsError> {
+        Ok(None)
+    }
+
+    fn len(&self) -> i32 {
+        self.try_len().unwrap_or_else(JsError::raise)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the name of the key at `index`, or `None` past the end.
+    fn key(&self, index: i32) -> Option<String> {
+        self.try_key(index).unwrap_or_else(JsError::raise)
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        self.try_get(name).unwrap_or_else(JsError::raise)
+    }
+
+    fn set(&self, name: &str, value: &str) {
+        self.try_set(name, value).unwrap_or_else(JsError::raise)
+    }
+
+    fn remove(&self, name: &str) {
+        self.try_remove(name).unwrap_or_else(JsError::raise)
+    }
+
+    fn clear(&self) {
+        self.try_clear().unwrap_or_else(JsError::raise)
+    }
+
+    fn contains_key(&self, name: &str) -> bool {
+        self.try_contains_key(name).unwrap_or_else(JsError::raise)
+    }
+}
+
+/// Iteration over any `Storage`, including trait objects.
+pub trait StorageExt: Storage {
+    fn keys(&self) -> Keys<'_, Self> {
+        let snapshot = self.try_all_keys().unwrap_or_else(JsError::raise);
+        Keys { storage: self, index: 0, snapshot: snapshot.map(Vec::into_iter) }
+    }
+
+    fn values(&self) -> Values<'_, Self> {
+        Values { inner: self.entries() }
+    }
+
+    fn entries(&self) -> Entries<'_, Self> {
+        Entries { keys: self.keys() }
+    }
+
+    /// Decodes the JSON value stored under `name`.
+    #[cfg(feature = "serde")]
+    fn get_json<T: DeserializeOwned>(&self, name: &str) -> Result<Option<T>, StorageError> {
+        match self.try_get(name)? {
+            None => Ok(None),
+            Some(value) => serde_json::from_str(&value)
+                .map(Some)
+                .map_err(|error| StorageError::Decode { key: name.to_string(), error }),
+        }
+    }
+
+    /// Stores `value` under `name` encoded as JSON.
+    #[cfg(feature = "serde")]
+    fn set_json<T: Serialize + ?Sized>(&self, name: &str, value: &T) -> Result<(), StorageError> {
+        let value = serde_json::to_string(value).map_err(StorageError::Encode)?;
+        self.try_set(name, &value)?;
+        Ok(())
+    }
+}
+
+impl<S: Storage + ?Sized> StorageExt for S {}
+
+impl<'b, S: Storage + ?Sized> Storage for &'b S {
+    fn try_len(&self) -> Result<i32, JsError> { (**self).try_len() }
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> { (**self).try_key(index) }
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> { (**self).try_get(name) }
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> { (**self).try_set(name, value) }
+    fn try_remove(&self, name: &str) -> Result<(), JsError> { (**self).try_remove(name) }
+    fn try_clear(&self) -> Result<(), JsError> { (**self).try_clear() }
+    fn try_contains_key(&self, name: &str) -> Result<bool, JsError> { (**self).try_contains_key(name) }
+    fn try_all_keys(&self) -> Result<Option<Vec<String>>, JsError> { (**self).try_all_keys() }
+}
+
+/// Error returned by the typed storage accessors.
+#[derive(Debug)]
+pub enum StorageError {
+    /// The underlying storage threw, e.g. when the quota is exceeded.
+    Js(JsError),
+    /// The value could not be encoded.
+    #[cfg(feature = "serde")]
+    Encode(serde_json::Error),
+    /// The stored value under `key` is not valid for the requested type.
+    #[cfg(feature = "serde")]
+    Decode { key: String, error: serde_json::Error },
+}
+
+impl From<JsError> for StorageError {
+    fn from(error: JsError) -> Self {
+        StorageError::Js(error)
+    }
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StorageError::Js(ref error) => write!(f, "storage error: {}", error),
+            #[cfg(feature = "serde")]
+            StorageError::Encode(ref error) => write!(f, "cannot encode value: {}", error),
+            #[cfg(feature = "serde")]
+            StorageError::Decode { ref key, ref error } => write!(f, "cannot decode value of {:?}: {}", key, error),
+        }
+    }
+}
+
+impl Error for StorageError {
+    fn description(&self) -> &str {
+        match *self {
+            StorageError::Js(_) => "storage error",
+            #[cfg(feature = "serde")]
+            StorageError::Encode(_) => "cannot encode value",
+            #[cfg(feature = "serde")]
+            StorageError::Decode { .. } => "cannot decode value",
+        }
+    }
+}
+
+// =================================================================================================
+
+/// A view of a `Storage` restricted to the keys starting with `prefix`, with the prefix
+/// stripped. `clear` only removes the keys in the namespace.
+pub struct Namespaced<S: Storage> {
+    storage: S,
+    prefix: String,
+}
+
+impl<S: Storage> Namespaced<S> {
+    pub fn new(storage: S, prefix: &str) -> Self {
+        Namespaced { storage, prefix: prefix.to_string() }
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    fn full_key(&self, name: &str) -> String {
+        format!("{}{}", self.prefix, name)
+    }
+
+    fn try_own_keys(&self) -> Result<Vec<String>, JsError> {
+        let mut keys = Vec::new();
+        let mut index = 0;
+        while let Some(key) = self.storage.try_key(index)? {
+            if key.starts_with(&self.prefix) {
+                keys.push(key[self.prefix.len()..].to_string());
+            }
+            index += 1;
+        }
+        Ok(keys)
+    }
+}
+
+impl<S: Storage> Storage for Namespaced<S> {
+    fn try_len(&self) -> Result<i32, JsError> {
+        self.try_own_keys().map(|keys| keys.len() as i32)
+    }
+
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        if index < 0 {
+            return Ok(None);
+        }
+        self.try_own_keys().map(|keys| keys.into_iter().nth(index as usize))
+    }
+
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        self.storage.try_get(&self.full_key(name))
+    }
+
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> {
+        self.storage.try_set(&self.full_key(name), value)
+    }
+
+    fn try_remove(&self, name: &str) -> Result<(), JsError> {
+        self.storage.try_remove(&self.full_key(name))
+    }
+
+    fn try_clear(&self) -> Result<(), JsError> {
+        for key in self.try_own_keys()? {
+            self.storage.try_remove(&self.full_key(&key))?;
+        }
+        Ok(())
+    }
+
+    fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        self.storage.try_contains_key(&self.full_key(name))
+    }
+
+    fn try_all_keys(&self) -> Result<Option<Vec<String>>, JsError> {
+        self.try_own_keys().map(Some)
+    }
+}
+
+pub struct Keys<'s, S: Storage + ?Sized + 's> {
+    storage: &'s S,
+    index: i32,
+    snapshot: Option<vec::IntoIter<String>>,
+}
+
+impl<'s, S: Storage + ?Sized> Iterator for Keys<'s, S> {
+    type Item = String;
+    fn next(&mut self) -> Option<String> {
+        if let Some(ref mut snapshot) = self.snapshot {
+            return snapshot.next();
+        }
+        let key = self.storage.key(self.index);
+        if key.is_some() {
+            self.index += 1;
+        }
+        key
+    }
+}
+
+pub struct Values<'s, S: Storage + ?Sized + 's> {
+    inner: Entries<'s, S>,
+}
+
+impl<'s, S: Storage + ?Sized> Iterator for Values<'s, S> {
+    type Item = String;
+    fn next(&mut self) -> Option<String> {
+        self.inner.next().map(|(_, value)| value)
+    }
+}
+
+/// Iterates over key and value pairs.
+pub struct Entries<'s, S: Storage + ?Sized + 's> {
+    keys: Keys<'s, S>,
+}
+
+impl<'s, S: Storage + ?Sized> Iterator for Entries<'s, S> {
+    type Item = (String, String);
+    fn next(&mut self) -> Option<(String, String)> {
+        while let Some(key) = self.keys.next() {
+            if let Some(value) = self.keys.storage.get(&key) {
+                return Some((key, value));
+            }
+        }
+        None
+    }
+}
+
+// =================================================================================================
+
+/// Which browser storage a `StorageEvent` refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageArea {
+    Local,
+    Session,
+}
+
+/// A change made to a storage by another document, e.g. another tab of the same origin.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageEvent {
+    /// The changed key, or `None` when the storage was cleared.
+    pub key: Option<String>,
+    pub old_value: Option<String>,
+    /// The new value, or `None` when the key was removed.
+    pub new_value: Option<String>,
+    /// The URL of the document that made the change.
+    pub url: String,
+    pub area: StorageArea,
+}
+
+// =================================================================================================
+
+/// A `Storage` kept in memory, for tests and for running without a browser.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    entries: RefCell<Vec<(String, String)>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage { entries: RefCell::new(Vec::new()) }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn try_len(&self) -> Result<i32, JsError> {
+        Ok(self.entries.borrow().len() as i32)
+    }
+
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        if index < 0 {
+            return Ok(None);
+        }
+        Ok(self.entries.borrow().get(index as usize).map(|&(ref k, _)| k.clone()))
+    }
+
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        Ok(self.entries.borrow().iter().find(|&&(ref k, _)| k == name).map(|&(_, ref v)| v.clone()))
+    }
+
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> {
+        let mut entries = self.entries.borrow_mut();
+        if let Some(entry) = entries.iter_mut().find(|&&mut (ref k, _)| k == name) {
+            entry.1 = value.to_string();
+            return Ok(());
+        }
+        entries.push((name.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    fn try_remove(&self, name: &str) -> Result<(), JsError> {
+        self.entries.borrow_mut().retain(|&(ref k, _)| k != name);
+        Ok(())
+    }
+
+    fn try_clear(&self) -> Result<(), JsError> {
+        self.entries.borrow_mut().clear();
+        Ok(())
+    }
+}
+
+// =================================================================================================
+
+/* Shared by LocalStorage and SessionStorage; `area` is the name of the property on `window`. */
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub(crate) fn web_len(area: &str) -> Result<i32, JsError> {
+    js_try! { (area) "\
+        return window[UTF8ToString($0)].length;\
+    " }
+}
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub(crate) fn web_key(area: &str, index: i32) -> Result<Option<String>, JsError> {
+    js_try! { (area, index) -> Option<String>, "\
+        return window[UTF8ToString($0)].key($1);\
+    " }
+}
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub(crate) fn web_get(area: &str, name: &str) -> Result<Option<String>, JsError> {
+    js_try! { (area, name) -> Option<String>, "\
+        return window[UTF8ToString($0)].getItem(UTF8ToString($1));\
+    " }
+}
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub(crate) fn web_set(area: &str, name: &str, value: &str) -> Result<(), JsError> {
+    js_try! { (area, name, value) "\
+        window[UTF8ToString($0)].setItem(UTF8ToString($1), UTF8ToString($2));\
+    " }?;
+    Ok(())
+}
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub(crate) fn web_remove(area: &str, name: &str) -> Result<(), JsError> {
+    js_try! { (area, name) "\
+        window[UTF8ToString($0)].removeItem(UTF8ToString($1));\
+    " }?;
+    Ok(())
+}
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub(crate) fn web_clear(area: &str) -> Result<(), JsError> {
+    js_try! { (area) "\
+        window[UTF8ToString($0)].clear();\
+    " }?;
+    Ok(())
+}
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub(crate) fn web_contains_key(area: &str, name: &str) -> Result<bool, JsError> {
+    let a = js_try! { (area, name) "\
+        return window[UTF8ToString($0)].getItem(UTF8ToString($1)) === null ? 0 : 1;\
+    " }?;
+    Ok(a != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use super::*;
+
+    fn fill(storage: &Storage) {
+        storage.clear();
+        storage.set("a", "1");
+        storage.set("b", "2");
+        storage.set("a", "3");
+    }
+
+    #[test]
+    fn test_memory_storage() {
+        let storage = MemoryStorage::new();
+        fill(&storage);
+        assert_eq!(storage.len(), 2);
+        assert_eq!(storage.get("a"), Some("3".to_string()));
+        assert_eq!(storage.key(1), Some("b".to_string()));
+        assert_eq!(storage.key(2), None);
+        storage.remove("a");
+        assert!(!storage.contains_key("a"));
+    }
+
+    #[test]
+    fn test_trait_object_iteration() {
+        let storage = MemoryStorage::new();
+        let dynamic: &Storage = &storage;
+        fill(dynamic);
+        assert_eq!(dynamic.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(dynamic.values().collect::<Vec<_>>(), vec!["3", "2"]);
+    }
+
+    #[test]
+    fn test_namespaced() {
+        let storage = MemoryStorage::new();
+        storage.set("other", "x");
+        let app = Namespaced::new(&storage, "app.");
+        app.set("a", "1");
+        assert_eq!(storage.get("app.a"), Some("1".to_string()));
+        assert_eq!(app.keys().collect::<Vec<_>>(), vec!["a"]);
+        app.clear();
+        assert_eq!(storage.keys().collect::<Vec<_>>(), vec!["other"]);
+    }
+
+    /// Counts the `try_key` calls made on the wrapped storage.
+    struct Counting<'s> {
+        storage: &'s MemoryStorage,
+        key_calls: Cell<i32>,
+    }
+
+    impl<'s> Storage for Counting<'s> {
+        fn try_len(&self) -> Result<i32, JsError> { self.storage.try_len() }
+        fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+            self.key_calls.set(self.key_calls.get() + 1);
+            self.storage.try_key(index)
+        }
+        fn try_get(&self, name: &str) -> Result<Option<String>, JsError> { self.storage.try_get(name) }
+        fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> { self.storage.try_set(name, value) }
+        fn try_remove(&self, name: &str) -> Result<(), JsError> { self.storage.try_remove(name) }
+        fn try_clear(&self) -> Result<(), JsError> { self.storage.try_clear() }
+    }
+
+    #[test]
+    fn test_namespaced_iteration_scans_once() {
+        let storage = MemoryStorage::new();
+        for i in 0..50 {
+            storage.set(&format!("app.{}", i), "x");
+            storage.set(&format!("other.{}", i), "y");
+        }
+        let counting = Counting { storage: &storage, key_calls: Cell::new(0) };
+        let app = Namespaced::new(&counting, "app.");
+        assert_eq!(app.entries().count(), 50);
+        assert_eq!(counting.key_calls.get(), 101);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_json() {
+        let storage = MemoryStorage::new();
+        storage.set_json("list", &vec![1, 2, 3]).unwrap();
+        assert_eq!(storage.get("list"), Some("[1,2,3]".to_string()));
+        assert_eq!(storage.get_json::<Vec<u32>>("list").unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(storage.get_json::<Vec<u32>>("missing").unwrap(), None);
+
+        storage.set("list", "not json");
+        match storage.get_json::<Vec<u32>>("list") {
+            Err(StorageError::Decode { ref key, .. }) => assert_eq!(key, "list"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
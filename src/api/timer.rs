@@ -0,0 +1,106 @@
+use std::cell::Cell;
+use libc;
+use ::api::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimerKind {
+    Timeout,
+    Interval,
+    AnimationFrame,
+    Microtask,
+}
+
+trait Callback {}
+
+impl<T> Callback for T {}
+
+thread_local! {
+    static NEXT_KEY: Cell<u32> = Cell::new(0);
+}
+
+/// A key for the next timer, handed to JS so that `rust_release_timer` can find the handle.
+pub(crate) fn next_timer_key() -> u32 {
+    NEXT_KEY.with(|key| {
+        let next = key.get().wrapping_add(1);
+        key.set(next);
+        next
+    })
+}
+
+/// Owns the closure of a scheduled timer, animation frame or microtask. Dropping the handle
+/// cancels it.
+#[must_use = "the timer is cancelled as soon as the handle is dropped; use `forget` to keep it"]
+pub struct TimerHandle<'a> {
+    key: u32,
+    timer: libc::c_int,
+    kind: TimerKind,
+    doc: *const Document<'a>,
+    _closure: Box<Callback + 'a>,
+}
+
+impl<'a> TimerHandle<'a> {
+    fn new<T: 'a>(kind: TimerKind, key: u32, timer: libc::c_int, doc: *const Document<'a>, closure: Box<T>) -> Self {
+        TimerHandle { key, timer, kind, doc, _closure: closure }
+    }
+
+    pub(crate) fn timeout<T: 'a>(key: u32, timer: libc::c_int, doc: *const Document<'a>, closure: Box<T>) -> Self {
+        TimerHandle::new(TimerKind::Timeout, key, timer, doc, closure)
+    }
+
+    pub(crate) fn interval<T: 'a>(key: u32, timer: libc::c_int, doc: *const Document<'a>, closure: Box<T>) -> Self {
+        TimerHandle::new(TimerKind::Interval, key, timer, doc, closure)
+    }
+
+    pub(crate) fn animation_frame<T: 'a>(key: u32, timer: libc::c_int, doc: *const Document<'a>, closure: Box<T>) -> Self {
+        TimerHandle::new(TimerKind::AnimationFrame, key, timer, doc, closure)
+    }
+
+    /// `token` is a `rs_refs` slot holding `{ cancelled: false }`, checked before running.
+    pub(crate) fn microtask<T: 'a>(key: u32, token: libc::c_int, doc: *const Document<'a>, closure: Box<T>) -> Self {
+        TimerHandle::new(TimerKind::Microtask, key, token, doc, closure)
+    }
+
+    /// Identifies the timer in `rust_release_timer`.
+    pub(crate) fn key(&self) -> u32 {
+        self.key
+    }
+
+    /// Cancels the timer and frees its closure.
+    pub fn cancel(self) {}
+
+    /// Keeps the timer scheduled for as long as the `Document` lives. One-shot timers are
+    /// freed once they have fired.
+    pub fn forget(self) {
+        unsafe {
+            (&*self.doc).push_timer(self);
+        }
+    }
+}
+
+impl<'a> Drop for TimerHandle<'a> {
+    fn drop(&mut self) {
+        match self.kind {
+            TimerKind::Timeout => js_guarded! { (self.timer) "\
+                clearTimeout($0);\
+            " },
+            TimerKind::Interval => js_guarded! { (self.timer) "\
+                clearInterval($0);\
+            " },
+            TimerKind::AnimationFrame => js_guarded! { (self.timer) "\
+                cancelAnimationFrame($0);\
+            " },
+            TimerKind::Microtask => js_guarded! { (self.timer) "\
+                WEBPLATFORM.rs_refs[$0].cancelled = true;\
+                WEBPLATFORM.ref_free($0);\
+            " },
+        };
+    }
+}
+
+/* Called by JS after a one-shot callback has returned, so that a forgotten handle is dropped
+ * without freeing a closure that is still running. */
+pub(crate) extern fn rust_release_timer(doc: *const libc::c_void, key: libc::c_int) {
+    let doc = unsafe { &*(doc as *const Document) };
+    let _handle = doc.take_timer(key as u32);
+}
@@ -1,95 +1,372 @@
 use std::str;
 use libc;
 use ::api::*;
+use ::error::*;
 use ::event::*;
+use ::executor::*;
+use ::interop::*;
 
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadyState {
+    Connecting,
+    Open,
+    Closing,
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseEvent {
+    pub code: u16,
+    pub reason: String,
+    pub was_clean: bool,
+}
+
 pub struct WebSocket<'a> {
     id: libc::c_int,
     doc: *const Document<'a>,
 }
 
+impl<'a> Clone for WebSocket<'a> {
+    fn clone(&self) -> WebSocket<'a> {
+        let id = js_guarded! { (self.id) "\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0]);\
+        " };
+        WebSocket::new(id, self.doc)
+    }
+}
+
+impl<'a> Drop for WebSocket<'a> {
+    fn drop(&mut self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.ref_free($0);\
+        " };
+    }
+}
 
 impl<'a> WebSocket<'a> {
     pub fn new(id: libc::c_int, doc: *const Document<'a>) -> Self {
         WebSocket { id, doc }
     }
 
-    /* can and should we make this FnOnce? we'd need to remove the listener,
-     * and should only do that if js guarantees this only gets called once, or
-     * otherwise there might be uses of calling this more than once */
-    pub fn add_event_listener_open<F: FnMut() + 'a>(&self, f: F) {
+    pub fn on_open<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_open(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_open<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
         unsafe {
             let b = Box::new(f);
             let a = &*b as *const _;
-            js_guarded! { (self.id, a as *const libc::c_void,
+            let id = js_try! { (self.id, a as *const libc::c_void,
                 rust_caller_v::<F> as *const libc::c_void)
                 "\
-                WEBPLATFORM.rs_refs[$0].addEventListener('open', function (e) {\
+                return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], 'open', false, function (e) {\
                     Runtime.dynCall('vi', $2, [$1]);\
-                }, false);\
-            " };
-            (&*self.doc).push_ref_v(b);
-//            (&*self.doc).refs_v.borrow_mut().push(b);
+                });\
+            " }?;
+            Ok(ListenerHandle::new(id, self.doc, b))
         }
     }
 
-    pub fn add_event_listener_message_string<F: FnMut(String) + 'a>(&self, f: F) {
-        unsafe {
-            let b = Box::new(f);
-            let a = &*b as *const _;
-            js_guarded! { (self.id, a as *const libc::c_void,
-                rust_caller_v_string::<F> as *const libc::c_void)
-                "\
-                WEBPLATFORM.rs_refs[$0].addEventListener('message', function (e) {\
-                    if (typeof e.data != 'string') return;\
-                    Runtime.dynCall('vii', $2, [$1, allocate(intArrayFromString(e.data), 'i8', ALLOC_STACK)]);\
-                }, false);\
+    pub fn on_message_string<F: FnMut(String) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_message_string(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_message_string<F: FnMut(String) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let b = Box::new(move |id| f(ref_string(id)));
+        let a = &*b as *const _;
+        let id = js_try! { (self.id, a as *const libc::c_void, caller_v_i32(&*b))
+            "\
+            return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], 'message', false, function (e) {\
+                if (typeof e.data != 'string') return;\
+                Runtime.dynCall('vii', $2, [$1, WEBPLATFORM.ref_push(e.data)]);\
+            });\
+        " }?;
+        Ok(ListenerHandle::new(id, self.doc, b))
+    }
+
+    /// The slice passed to `f` points into a buffer owned by the listener, which is reused for every
+    /// message.
+    pub fn on_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_message_binary(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_message_binary<F: FnMut(&[u8]) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let mut buf = Vec::new();
+        let b = Box::new(move |id| {
+            ref_bytes_into(id, &mut buf);
+            f(&buf)
+        });
+        let a = &*b as *const _;
+        let id = js_try! { (self.id, a as *const libc::c_void, caller_v_i32(&*b))
+            "\
+            return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], 'message', false, function (e) {\
+                if (typeof e.data != 'object') return;\
+                Runtime.dynCall('vii', $2, [$1, WEBPLATFORM.ref_push(e.data)]);\
+            });\
+        " }?;
+        Ok(ListenerHandle::new(id, self.doc, b))
+    }
+
+    /// Yields the text messages until the socket closes.
+    pub fn messages(&self) -> EventStream<'a, String> {
+        self.try_messages().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_messages(&self) -> Result<EventStream<'a, String>, JsError> {
+        let (tx, rx) = channel();
+        let closed = tx.clone();
+        let listeners = vec![
+            self.try_on_message_string(move |message| tx.send(message))?,
+            self.try_on_close(move |_| closed.close())?,
+        ];
+        Ok(EventStream::new(listeners, rx))
+    }
+
+    /// Yields the binary messages until the socket closes.
+    pub fn binary_messages(&self) -> EventStream<'a, Vec<u8>> {
+        self.try_binary_messages().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_binary_messages(&self) -> Result<EventStream<'a, Vec<u8>>, JsError> {
+        let (tx, rx) = channel();
+        let closed = tx.clone();
+        let listeners = vec![
+            self.try_on_message_binary(move |message| tx.send(message.to_vec()))?,
+            self.try_on_close(move |_| closed.close())?,
+        ];
+        Ok(EventStream::new(listeners, rx))
+    }
+
+    pub fn on_close<F: FnMut(CloseEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_close(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_close<F: FnMut(CloseEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let b = Box::new(move |id: libc::c_int| {
+            let code = js_guarded! { (id) "\
+                return WEBPLATFORM.rs_refs[$0].code;\
             " };
-            (&*self.doc).push_ref_v_string(b);
-//            (&*self.doc).refs_v_string.borrow_mut().push(b);
-        }
+            let was_clean = js_guarded! { (id) "\
+                return WEBPLATFORM.rs_refs[$0].wasClean ? 1 : 0;\
+            " };
+            let reason = js_guarded! { (id) "\
+                var reason = WEBPLATFORM.rs_refs[$0].reason;\
+                WEBPLATFORM.ref_free($0);\
+                return WEBPLATFORM.ref_push(reason);\
+            " };
+            f(CloseEvent {
+                code: code as u16,
+                reason: ref_string(reason),
+                was_clean: was_clean != 0,
+            })
+        });
+        let a = &*b as *const _;
+        let id = js_try! { (self.id, a as *const libc::c_void, caller_v_i32(&*b))
+            "\
+            return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], 'close', false, function (e) {\
+                Runtime.dynCall('vii', $2, [$1, WEBPLATFORM.ref_push(e)]);\
+            });\
+        " }?;
+        Ok(ListenerHandle::new(id, self.doc, b))
     }
 
-    pub fn add_event_listener_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) {
+    pub fn on_error<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_error(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_error<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
         unsafe {
             let b = Box::new(f);
             let a = &*b as *const _;
-            // BIG FIXME this leaks memory, and i don't want to malloc there in the first place but just pass a pointer to the buffer
-            js_guarded! { (self.id, a as *const libc::c_void,
-                rust_caller_v_u8array::<F> as *const libc::c_void)
+            let id = js_try! { (self.id, a as *const libc::c_void,
+                rust_caller_v::<F> as *const libc::c_void)
                 "\
-                WEBPLATFORM.rs_refs[$0].addEventListener('message', function (e) {\
-                    if (typeof e.data != 'object') return;\
-                    var buf = Module._malloc(e.data.byteLength);\
-                    Module.writeArrayToMemory(new Int8Array(e.data), buf);\
-                    Runtime.dynCall('viii', $2, [$1, buf, e.data.byteLength]);\
-                }, false);\
-            " };
-            (&*self.doc).push_ref_v_u8array(b);
-//            (&*self.doc).refs_v_u8array.borrow_mut().push(b);
+                return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], 'error', false, function (e) {\
+                    Runtime.dynCall('vi', $2, [$1]);\
+                });\
+            " }?;
+            Ok(ListenerHandle::new(id, self.doc, b))
         }
     }
 
+    /* can and should we make this FnOnce? we'd need to remove the listener,
+     * and should only do that if js guarantees this only gets called once, or
+     * otherwise there might be uses of calling this more than once */
+    pub fn add_event_listener_open<F: FnMut() + 'a>(&self, f: F) {
+        self.on_open(f).forget()
+    }
+
+    pub fn try_add_event_listener_open<F: FnMut() + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_open(f).map(ListenerHandle::forget)
+    }
+
+    pub fn add_event_listener_message_string<F: FnMut(String) + 'a>(&self, f: F) {
+        self.on_message_string(f).forget()
+    }
+
+    pub fn try_add_event_listener_message_string<F: FnMut(String) + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_message_string(f).map(ListenerHandle::forget)
+    }
+
+    pub fn add_event_listener_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) {
+        self.on_message_binary(f).forget()
+    }
+
+    pub fn try_add_event_listener_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_message_binary(f).map(ListenerHandle::forget)
+    }
+
     pub fn send(&self, data: &str) {
-        js_guarded! { (self.id, data) "\
+        self.try_send(data).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_send(&self, data: &str) -> Result<(), JsError> {
+        js_try! { (self.id, data) "\
             WEBPLATFORM.rs_refs[$0].send(UTF8ToString($1));\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn send_binary(&self, data: &[u8]) {
-        /* FIXME first three lines should go into a U8ToSlice function like UTF8ToString */
-        js_guarded! { (self.id, data) "\
-            var start = HEAPU32[$1 / 4];\
-            var length = HEAPU32[$1 / 4 + 1];\
-            var sliced = HEAP8.slice(start, start + length * 1);\
-            WEBPLATFORM.rs_refs[$0].send(sliced);\
-        " };
+        self.try_send_binary(data).unwrap_or_else(JsError::raise)
     }
 
+    pub fn try_send_binary(&self, data: &[u8]) -> Result<(), JsError> {
+        js_try! { (self.id, data) "\
+            WEBPLATFORM.rs_refs[$0].send(WEBPLATFORM.u8_slice($1));\
+        " }?;
+        Ok(())
+    }
+
+    /// Closes the connection normally (code 1000) with `data` as the reason.
     pub fn close(&self, data: &str) {
-        js_guarded! { (self.id, data) "\
-            WEBPLATFORM.rs_refs[$0].close();\
+        self.close_with(1000, data)
+    }
+
+    pub fn try_close(&self, data: &str) -> Result<(), JsError> {
+        self.try_close_with(1000, data)
+    }
+
+    pub fn close_with(&self, code: u16, reason: &str) {
+        self.try_close_with(code, reason).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_close_with(&self, code: u16, reason: &str) -> Result<(), JsError> {
+        js_try! { (self.id, code as i32, reason) "\
+            WEBPLATFORM.rs_refs[$0].close($1, UTF8ToString($2));\
+        " }?;
+        Ok(())
+    }
+
+    pub fn ready_state(&self) -> ReadyState {
+        self.try_ready_state().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_ready_state(&self) -> Result<ReadyState, JsError> {
+        let state = js_try! { (self.id) "\
+            return WEBPLATFORM.rs_refs[$0].readyState;\
+        " }?;
+        Ok(match state {
+            0 => ReadyState::Connecting,
+            1 => ReadyState::Open,
+            2 => ReadyState::Closing,
+            _ => ReadyState::Closed,
+        })
+    }
+
+    /// The number of bytes queued by `send` but not yet transmitted.
+    pub fn buffered_amount(&self) -> u32 {
+        self.try_buffered_amount().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_buffered_amount(&self) -> Result<u32, JsError> {
+        let amount = js_try! { (self.id) "\
+            return WEBPLATFORM.rs_refs[$0].bufferedAmount;\
+        " }?;
+        Ok(amount as u32)
+    }
+
+    /// The subprotocol selected by the server.
+    pub fn protocol(&self) -> String {
+        self.try_protocol().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_protocol(&self) -> Result<String, JsError> {
+        js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].protocol;\
+        " }
+    }
+
+    /// The extensions selected by the server.
+    pub fn extensions(&self) -> String {
+        self.try_extensions().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_extensions(&self) -> Result<String, JsError> {
+        js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].extensions;\
+        " }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_message_binary() {
+        let document = init();
+        let socket = document.window().websocket_create("ws://localhost:9").unwrap();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let sink = received.clone();
+        let _handle = socket.on_message_binary(move |data| sink.borrow_mut().push(data.to_vec()));
+        js_guarded! { (socket.id) "\
+            var ws = WEBPLATFORM.rs_refs[$0];\
+            ws.dispatchEvent(new MessageEvent('message', { data: new Uint8Array([1, 2, 3]).buffer }));\
+            ws.dispatchEvent(new MessageEvent('message', { data: new Uint8Array([4]).buffer }));\
+            ws.dispatchEvent(new MessageEvent('message', { data: 'text' }));\
+        " };
+        assert_eq!(*received.borrow(), vec![vec![1, 2, 3], vec![4]]);
+    }
+
+    fn sent_binary(data: &[u8]) -> Vec<u8> {
+        let document = init();
+        let socket = document.window().websocket_create("ws://localhost:9").unwrap();
+        js_guarded! { (socket.id) "\
+            var ws = WEBPLATFORM.rs_refs[$0];\
+            ws.send = function (data) { ws.sent = data.slice(); };\
+        " };
+        socket.send_binary(data);
+        js_guarded! { (socket.id) -> Vec<u8>, "\
+            return WEBPLATFORM.rs_refs[$0].sent;\
+        " }
+    }
+
+    #[test]
+    fn test_send_binary_large() {
+        let data: Vec<u8> = (0..1 << 20).map(|i| (i % 251) as u8).collect();
+        assert_eq!(sent_binary(&data), data);
+    }
+
+    #[test]
+    fn test_send_binary_empty() {
+        assert_eq!(sent_binary(&[]), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn test_close_event() {
+        let document = init();
+        let socket = document.window().websocket_create("ws://localhost:9").unwrap();
+        let received = Rc::new(RefCell::new(None));
+        let sink = received.clone();
+        let _handle = socket.on_close(move |e| *sink.borrow_mut() = Some(e));
+        js_guarded! { (socket.id) "\
+            var e = new CloseEvent('close', { code: 4000, reason: 'bye', wasClean: true });\
+            WEBPLATFORM.rs_refs[$0].dispatchEvent(e);\
         " };
+        assert_eq!(*received.borrow(), Some(CloseEvent { code: 4000, reason: "bye".to_string(), was_clean: true }));
     }
 }
@@ -0,0 +1,544 @@
+use std::cell::RefCell;
+use std::str;
+use libc;
+use ::api::*;
+use ::error::*;
+use ::event::*;
+use ::executor::*;
+use ::interop::*;
+
+
+/// The browser window: location, history, timers, storage, dialogs and window-level events.
+///
+/// Obtained with `Document::window`.
+#[derive(Clone, Copy)]
+pub struct Window<'a> {
+    doc: *const Document<'a>,
+}
+
+impl<'a> Window<'a> {
+    pub(crate) fn new(doc: *const Document<'a>) -> Self {
+        Window { doc }
+    }
+
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_on(s, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        unsafe {
+            let b = Box::new(f);
+            let a = &*b as *const _;
+            let id = js_try! { (0i32, s, a as *const libc::c_void,
+                rust_caller::<F> as *const libc::c_void,
+                self.doc as *const libc::c_void)
+                "\
+                return WEBPLATFORM.listen(window, UTF8ToString($1), false, function (e) {\
+                    Runtime.dynCall('viiii', $3, [$2, $4, e.target ? WEBPLATFORM.ref_push(e.target) : -1, WEBPLATFORM.ref_push(e)]);\
+                });\
+            " }?;
+            Ok(ListenerHandle::new(id, self.doc, b))
+        }
+    }
+
+    pub fn websocket_create(&self, url: &str) -> Option<WebSocket<'a>> {
+        self.try_websocket_create(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_websocket_create(&self, url: &str) -> Result<Option<WebSocket<'a>>, JsError> {
+        let id = js_try! { (url) "\
+            var value = new WebSocket(UTF8ToString($0));\
+            if (!value) {\
+                return -1;\
+            }\
+            value.binaryType = 'arraybuffer';\
+            return WEBPLATFORM.ref_push(value);\
+        " }?;
+
+        if id < 0 {
+            Ok(None)
+        } else {
+            Ok(Some(WebSocket::new(id, self.doc)))
+        }
+    }
+
+    /// Starts a `fetch` and calls `f` with the response headers, or the network error.
+    pub fn fetch<F: FnOnce(Result<Response, JsError>) + 'a>(&self, request: Request, f: F) -> AbortHandle {
+        self.try_fetch(request, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_fetch<F: FnOnce(Result<Response, JsError>) + 'a>(&self, request: Request, f: F) -> Result<AbortHandle, JsError> {
+        fetch::fetch(request, f)
+    }
+
+    pub fn xml_http_request_create(&self) -> XmlHttpRequest<'a> {
+        self.try_xml_http_request_create().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_xml_http_request_create(&self) -> Result<XmlHttpRequest<'a>, JsError> {
+        let id = js_try! { "\
+            return WEBPLATFORM.ref_push(new XMLHttpRequest());\
+        " }?;
+        Ok(XmlHttpRequest::new(id, self.doc))
+    }
+
+    /// Opens a WebSocket offering the given subprotocols.
+    pub fn websocket_create_with_protocols(&self, url: &str, protocols: &[&str]) -> Option<WebSocket<'a>> {
+        self.try_websocket_create_with_protocols(url, protocols).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_websocket_create_with_protocols(&self, url: &str, protocols: &[&str]) -> Result<Option<WebSocket<'a>>, JsError> {
+        let protocols = protocols.join(",");
+        let id = js_try! { (url, protocols.as_str()) "\
+            var protocols = UTF8ToString($1);\
+            var value = new WebSocket(UTF8ToString($0), protocols ? protocols.split(',') : []);\
+            if (!value) {\
+                return -1;\
+            }\
+            value.binaryType = 'arraybuffer';\
+            return WEBPLATFORM.ref_push(value);\
+        " }?;
+
+        if id < 0 {
+            Ok(None)
+        } else {
+            Ok(Some(WebSocket::new(id, self.doc)))
+        }
+    }
+
+    pub fn local_storage(&self) -> LocalStorageInterface {
+        LocalStorage
+    }
+
+    pub fn session_storage(&self) -> SessionStorageInterface {
+        SessionStorage
+    }
+
+    /// Opens the IndexedDB database `name`, upgrading it if `version` is higher than the
+    /// stored one.
+    pub fn indexed_db_open(&self, name: &str, version: u32) -> IdbRequest<'a, Database<'a>> {
+        self.try_indexed_db_open(name, version).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_indexed_db_open(&self, name: &str, version: u32) -> Result<IdbRequest<'a, Database<'a>>, JsError> {
+        indexed_db::open(self.doc, name, version)
+    }
+
+    pub fn indexed_db_delete(&self, name: &str) -> IdbRequest<'a, ()> {
+        self.try_indexed_db_delete(name).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_indexed_db_delete(&self, name: &str) -> Result<IdbRequest<'a, ()>, JsError> {
+        indexed_db::delete_database(self.doc, name)
+    }
+
+    /// Calls `f` when another document of the same origin changes `localStorage` or
+    /// `sessionStorage`. Changes made by this document are not reported.
+    pub fn on_storage_change<F: FnMut(StorageEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_storage_change(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_storage_change<F: FnMut(StorageEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        fn opt_string(id: i32) -> Option<String> {
+            if id < 0 { None } else { Some(ref_string(id)) }
+        }
+
+        let b = Box::new(move |id: libc::c_int| {
+            let area = js_guarded! { (id) "\
+                var area = WEBPLATFORM.rs_refs[$0].storageArea;\
+                return area === window.localStorage ? 0 : area === window.sessionStorage ? 1 : -1;\
+            " };
+            let area = match area {
+                0 => StorageArea::Local,
+                1 => StorageArea::Session,
+                _ => {
+                    js_guarded! { (id) "WEBPLATFORM.ref_free($0);" };
+                    return;
+                }
+            };
+            let key = js_guarded! { (id) "\
+                var key = WEBPLATFORM.rs_refs[$0].key;\
+                return key == null ? -1 : WEBPLATFORM.ref_push(key);\
+            " };
+            let old_value = js_guarded! { (id) "\
+                var value = WEBPLATFORM.rs_refs[$0].oldValue;\
+                return value == null ? -1 : WEBPLATFORM.ref_push(value);\
+            " };
+            let new_value = js_guarded! { (id) "\
+                var value = WEBPLATFORM.rs_refs[$0].newValue;\
+                return value == null ? -1 : WEBPLATFORM.ref_push(value);\
+            " };
+            let url = js_guarded! { (id) "\
+                var url = WEBPLATFORM.rs_refs[$0].url;\
+                WEBPLATFORM.ref_free($0);\
+                return WEBPLATFORM.ref_push(url);\
+            " };
+            f(StorageEvent {
+                key: opt_string(key),
+                old_value: opt_string(old_value),
+                new_value: opt_string(new_value),
+                url: ref_string(url),
+                area,
+            })
+        });
+        let a = &*b as *const _;
+        let id = js_try! { (a as *const libc::c_void, caller_v_i32(&*b)) "\
+            return WEBPLATFORM.listen(window, 'storage', false, function (e) {\
+                Runtime.dynCall('vii', $1, [$0, WEBPLATFORM.ref_push(e)]);\
+            });\
+        " }?;
+        Ok(ListenerHandle::new(id, self.doc, b))
+    }
+
+    // Location
+
+    pub fn location_hash_get(&self) -> String {
+        self.try_location_hash_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_hash_get(&self) -> Result<String, JsError> {
+        js_try! { -> String, "\
+            return window.location.hash;\
+        " }
+    }
+
+    pub fn location_hash_set(&self, s: &str) {
+        self.try_location_hash_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_hash_set(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (s) "\
+            window.location.hash = UTF8ToString($0);\
+        " }?;
+        Ok(())
+    }
+
+    pub fn location_href_get(&self) -> String {
+        self.try_location_href_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_href_get(&self) -> Result<String, JsError> {
+        js_try! { -> String, "\
+            return window.location.href;\
+        " }
+    }
+
+    pub fn location_href_set(&self, s: &str) {
+        self.try_location_href_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_href_set(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (s) "\
+            window.location.href = UTF8ToString($0);\
+        " }?;
+        Ok(())
+    }
+
+    pub fn location_reload(&self) {
+        self.try_location_reload().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_reload(&self) -> Result<(), JsError> {
+        js_try! { "\
+            window.location.reload();\
+        " }?;
+        Ok(())
+    }
+
+    // History
+
+    pub fn history_length(&self) -> i32 {
+        self.try_history_length().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_length(&self) -> Result<i32, JsError> {
+        js_try! { "\
+            return window.history.length;\
+        " }
+    }
+
+    pub fn history_push(&self, url: &str) {
+        self.try_history_push(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_push(&self, url: &str) -> Result<(), JsError> {
+        js_try! { (url) "\
+            window.history.pushState(null, '', UTF8ToString($0));\
+        " }?;
+        Ok(())
+    }
+
+    pub fn history_replace(&self, url: &str) {
+        self.try_history_replace(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_replace(&self, url: &str) -> Result<(), JsError> {
+        js_try! { (url) "\
+            window.history.replaceState(null, '', UTF8ToString($0));\
+        " }?;
+        Ok(())
+    }
+
+    pub fn history_go(&self, delta: i32) {
+        self.try_history_go(delta).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_go(&self, delta: i32) -> Result<(), JsError> {
+        js_try! { (delta) "\
+            window.history.go($0);\
+        " }?;
+        Ok(())
+    }
+
+    pub fn history_back(&self) {
+        self.history_go(-1)
+    }
+
+    pub fn history_forward(&self) {
+        self.history_go(1)
+    }
+
+    // Timers
+
+    /// Calls `f` once after `ms` milliseconds.
+    pub fn set_timeout<F: FnOnce() + 'a>(&self, ms: i32, f: F) -> TimerHandle<'a> {
+        self.try_set_timeout(ms, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_set_timeout<F: FnOnce() + 'a>(&self, ms: i32, f: F) -> Result<TimerHandle<'a>, JsError> {
+        unsafe {
+            let b = Box::new(RefCell::new(Some(f)));
+            let a = &*b as *const _;
+            let key = next_timer_key();
+            let timer = js_try! { (a as *const libc::c_void,
+                rust_caller_v_once::<F> as *const libc::c_void, ms,
+                rust_release_timer as *const libc::c_void, self.doc as *const libc::c_void, key as i32)
+                "\
+                return setTimeout(function () {\
+                    Runtime.dynCall('vi', $1, [$0]);\
+                    Runtime.dynCall('vii', $3, [$4, $5]);\
+                }, $2);\
+            " }?;
+            Ok(TimerHandle::timeout(key, timer, self.doc, b))
+        }
+    }
+
+    /// Calls `f` every `ms` milliseconds.
+    pub fn set_interval<F: FnMut() + 'a>(&self, ms: i32, f: F) -> TimerHandle<'a> {
+        self.try_set_interval(ms, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_set_interval<F: FnMut() + 'a>(&self, ms: i32, f: F) -> Result<TimerHandle<'a>, JsError> {
+        unsafe {
+            let b = Box::new(f);
+            let a = &*b as *const _;
+            let timer = js_try! { (a as *const libc::c_void,
+                rust_caller_v::<F> as *const libc::c_void, ms)
+                "\
+                return setInterval(function () {\
+                    Runtime.dynCall('vi', $1, [$0]);\
+                }, $2);\
+            " }?;
+            Ok(TimerHandle::interval(next_timer_key(), timer, self.doc, b))
+        }
+    }
+
+    /// Resolves after `ms` milliseconds.
+    pub fn sleep(&self, ms: i32) -> Sleep<'a> {
+        self.try_sleep(ms).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_sleep(&self, ms: i32) -> Result<Sleep<'a>, JsError> {
+        let (tx, rx) = channel();
+        let timer = self.try_set_timeout(ms, move || tx.send(()))?;
+        Ok(Sleep::new(timer, rx))
+    }
+
+    /// Calls `f` before the next repaint with the `performance.now()` timestamp of the frame,
+    /// in milliseconds.
+    pub fn request_animation_frame<F: FnOnce(f64) + 'a>(&self, f: F) -> TimerHandle<'a> {
+        self.try_request_animation_frame(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_request_animation_frame<F: FnOnce(f64) + 'a>(&self, f: F) -> Result<TimerHandle<'a>, JsError> {
+        unsafe {
+            let b = Box::new(RefCell::new(Some(f)));
+            let a = &*b as *const _;
+            let key = next_timer_key();
+            let timer = js_try! { (a as *const libc::c_void,
+                rust_caller_v_once_f64::<F> as *const libc::c_void,
+                rust_release_timer as *const libc::c_void, self.doc as *const libc::c_void, key as i32)
+                "\
+                return requestAnimationFrame(function (t) {\
+                    Runtime.dynCall('vid', $1, [$0, t]);\
+                    Runtime.dynCall('vii', $2, [$3, $4]);\
+                });\
+            " }?;
+            Ok(TimerHandle::animation_frame(key, timer, self.doc, b))
+        }
+    }
+
+    /// Calls `f` once the current task has finished, before control returns to the event loop.
+    pub fn queue_microtask<F: FnOnce() + 'a>(&self, f: F) -> TimerHandle<'a> {
+        self.try_queue_microtask(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_queue_microtask<F: FnOnce() + 'a>(&self, f: F) -> Result<TimerHandle<'a>, JsError> {
+        unsafe {
+            let b = Box::new(RefCell::new(Some(f)));
+            let a = &*b as *const _;
+            let key = next_timer_key();
+            let token = js_try! { (a as *const libc::c_void,
+                rust_caller_v_once::<F> as *const libc::c_void,
+                rust_release_timer as *const libc::c_void, self.doc as *const libc::c_void, key as i32)
+                "\
+                var token = { cancelled: false };\
+                var run = function () {\
+                    if (token.cancelled) {\
+                        return;\
+                    }\
+                    Runtime.dynCall('vi', $1, [$0]);\
+                    Runtime.dynCall('vii', $2, [$3, $4]);\
+                };\
+                if (typeof queueMicrotask == 'function') {\
+                    queueMicrotask(run);\
+                } else {\
+                    Promise.resolve().then(run);\
+                }\
+                return WEBPLATFORM.ref_push(token);\
+            " }?;
+            Ok(TimerHandle::microtask(key, token, self.doc, b))
+        }
+    }
+
+    // Dialogs
+
+    pub fn alert(&self, s: &str) {
+        self.try_alert(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_alert(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (s) "\
+            alert(UTF8ToString($0));\
+        " }?;
+        Ok(())
+    }
+
+    pub fn confirm(&self, s: &str) -> bool {
+        self.try_confirm(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_confirm(&self, s: &str) -> Result<bool, JsError> {
+        let a = js_try! { (s) "\
+            return confirm(UTF8ToString($0)) ? 1 : 0;\
+        " }?;
+        Ok(a != 0)
+    }
+
+    pub fn prompt(&self, s: &str, default: &str) -> Option<String> {
+        self.try_prompt(s, default).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prompt(&self, s: &str, default: &str) -> Result<Option<String>, JsError> {
+        js_try! { (s, default) -> Option<String>, "\
+            return prompt(UTF8ToString($0), UTF8ToString($1));\
+        " }
+    }
+
+    // Viewport
+
+    pub fn inner_width(&self) -> i32 {
+        self.try_inner_width().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_inner_width(&self) -> Result<i32, JsError> {
+        js_try! { "\
+            return window.innerWidth;\
+        " }
+    }
+
+    pub fn inner_height(&self) -> i32 {
+        self.try_inner_height().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_inner_height(&self) -> Result<i32, JsError> {
+        js_try! { "\
+            return window.innerHeight;\
+        " }
+    }
+
+    pub fn device_pixel_ratio(&self) -> f64 {
+        self.try_device_pixel_ratio().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_device_pixel_ratio(&self) -> Result<f64, JsError> {
+        let mut ratio = 0f64;
+        js_try! { (&mut ratio as *mut f64 as *const libc::c_void) "\
+            HEAPF64[$0 >> 3] = window.devicePixelRatio;\
+        " }?;
+        Ok(ratio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_location_hash_set_get() {
+        let document = init();
+        let window = document.window();
+        window.location_hash_set("#section");
+        assert_eq!(window.location_hash_get(), "#section");
+        window.location_hash_set("");
+    }
+
+    #[test]
+    fn test_device_pixel_ratio() {
+        let document = init();
+        assert!(document.window().device_pixel_ratio() > 0.0);
+    }
+
+    #[test]
+    fn test_forgotten_timer_released() {
+        // Leaked so that the forgotten timers outlive the test function.
+        let document: &'static Document<'static> = Box::leak(Box::new(init()));
+        let window = document.window();
+        let microtask = window.queue_microtask(|| {});
+        let key = microtask.key();
+        microtask.forget();
+        // Microtasks run before the timeout, which finds the fired handle already released.
+        window.set_timeout(0, move || {
+            assert!(document.take_timer(key).is_none());
+        }).forget();
+    }
+
+    #[test]
+    fn test_on_storage_change() {
+        use std::rc::Rc;
+
+        let document = init();
+        let window = document.window();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let local = {
+            let events = events.clone();
+            LocalStorage.on_change(&window, move |e| events.borrow_mut().push(e))
+        };
+        js_guarded! { "\
+            window.dispatchEvent(new StorageEvent('storage', {\
+                key: 'a', oldValue: null, newValue: '1', url: 'http://a/', storageArea: localStorage,\
+            }));\
+            window.dispatchEvent(new StorageEvent('storage', {\
+                key: 'b', oldValue: '2', newValue: null, url: 'http://a/', storageArea: sessionStorage,\
+            }));\
+        " };
+        local.remove();
+        assert_eq!(*events.borrow(), vec![StorageEvent {
+            key: Some("a".to_string()),
+            old_value: None,
+            new_value: Some("1".to_string()),
+            url: "http://a/".to_string(),
+            area: StorageArea::Local,
+        }]);
+    }
+}
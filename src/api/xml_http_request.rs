@@ -0,0 +1,313 @@
+use std::str;
+use libc;
+use ::api::*;
+use ::error::*;
+use ::event::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseType {
+    Text,
+    ArrayBuffer,
+}
+
+impl ResponseType {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            ResponseType::Text => "text",
+            ResponseType::ArrayBuffer => "arraybuffer",
+        }
+    }
+}
+
+pub struct XmlHttpRequest<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+impl<'a> Clone for XmlHttpRequest<'a> {
+    fn clone(&self) -> XmlHttpRequest<'a> {
+        let id = js_guarded! { (self.id) "\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0]);\
+        " };
+        XmlHttpRequest::new(id, self.doc)
+    }
+}
+
+impl<'a> Drop for XmlHttpRequest<'a> {
+    fn drop(&mut self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.ref_free($0);\
+        " };
+    }
+}
+
+impl<'a> XmlHttpRequest<'a> {
+    pub fn new(id: libc::c_int, doc: *const Document<'a>) -> Self {
+        XmlHttpRequest { id, doc }
+    }
+
+    pub fn open(&self, method: &str, url: &str) {
+        self.try_open(method, url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_open(&self, method: &str, url: &str) -> Result<(), JsError> {
+        js_try! { (self.id, method, url) "\
+            WEBPLATFORM.rs_refs[$0].open(UTF8ToString($1), UTF8ToString($2), true);\
+        " }?;
+        Ok(())
+    }
+
+    pub fn set_request_header(&self, name: &str, value: &str) {
+        self.try_set_request_header(name, value).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_set_request_header(&self, name: &str, value: &str) -> Result<(), JsError> {
+        js_try! { (self.id, name, value) "\
+            WEBPLATFORM.rs_refs[$0].setRequestHeader(UTF8ToString($1), UTF8ToString($2));\
+        " }?;
+        Ok(())
+    }
+
+    /// Sets the request timeout in milliseconds; `0` means no timeout.
+    pub fn timeout_set(&self, ms: i32) {
+        self.try_timeout_set(ms).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_timeout_set(&self, ms: i32) -> Result<(), JsError> {
+        js_try! { (self.id, ms) "\
+            WEBPLATFORM.rs_refs[$0].timeout = $1;\
+        " }?;
+        Ok(())
+    }
+
+    pub fn response_type_set(&self, t: ResponseType) {
+        self.try_response_type_set(t).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_response_type_set(&self, t: ResponseType) -> Result<(), JsError> {
+        js_try! { (self.id, t.as_str()) "\
+            WEBPLATFORM.rs_refs[$0].responseType = UTF8ToString($1);\
+        " }?;
+        Ok(())
+    }
+
+    pub fn send(&self) {
+        self.try_send().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_send(&self) -> Result<(), JsError> {
+        js_try! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].send();\
+        " }?;
+        Ok(())
+    }
+
+    pub fn send_text(&self, data: &str) {
+        self.try_send_text(data).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_send_text(&self, data: &str) -> Result<(), JsError> {
+        js_try! { (self.id, data) "\
+            WEBPLATFORM.rs_refs[$0].send(UTF8ToString($1));\
+        " }?;
+        Ok(())
+    }
+
+    pub fn send_binary(&self, data: &[u8]) {
+        self.try_send_binary(data).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_send_binary(&self, data: &[u8]) -> Result<(), JsError> {
+        js_try! { (self.id, data) "\
+            WEBPLATFORM.rs_refs[$0].send(WEBPLATFORM.u8_slice($1));\
+        " }?;
+        Ok(())
+    }
+
+    pub fn abort(&self) {
+        self.try_abort().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_abort(&self) -> Result<(), JsError> {
+        js_try! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].abort();\
+        " }?;
+        Ok(())
+    }
+
+    pub fn ready_state(&self) -> i32 {
+        self.try_ready_state().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_ready_state(&self) -> Result<i32, JsError> {
+        js_try! { (self.id) "\
+            return WEBPLATFORM.rs_refs[$0].readyState;\
+        " }
+    }
+
+    pub fn status(&self) -> i32 {
+        self.try_status().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_status(&self) -> Result<i32, JsError> {
+        js_try! { (self.id) "\
+            return WEBPLATFORM.rs_refs[$0].status;\
+        " }
+    }
+
+    pub fn status_text(&self) -> String {
+        self.try_status_text().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_status_text(&self) -> Result<String, JsError> {
+        js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].statusText;\
+        " }
+    }
+
+    pub fn response_text(&self) -> String {
+        self.try_response_text().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_response_text(&self) -> Result<String, JsError> {
+        js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].responseText;\
+        " }
+    }
+
+    /// Returns the response body if the response type is `ArrayBuffer` and the request is done.
+    pub fn response_binary(&self) -> Option<Vec<u8>> {
+        self.try_response_binary().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_response_binary(&self) -> Result<Option<Vec<u8>>, JsError> {
+        js_try! { (self.id) -> Option<Vec<u8>>, "\
+            var value = WEBPLATFORM.rs_refs[$0].response;\
+            return value instanceof ArrayBuffer ? value : null;\
+        " }
+    }
+
+    pub fn response_header(&self, name: &str) -> Option<String> {
+        self.try_response_header(name).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_response_header(&self, name: &str) -> Result<Option<String>, JsError> {
+        js_try! { (self.id, name) -> Option<String>, "\
+            return WEBPLATFORM.rs_refs[$0].getResponseHeader(UTF8ToString($1));\
+        " }
+    }
+
+    /// Returns all response headers as lowercase name and value pairs.
+    pub fn response_headers(&self) -> Vec<(String, String)> {
+        self.try_response_headers().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_response_headers(&self) -> Result<Vec<(String, String)>, JsError> {
+        let headers = js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].getAllResponseHeaders();\
+        " }?;
+        Ok(parse_headers(&headers))
+    }
+
+    pub fn on_load<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_load(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_load<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_listen_v("load", f)
+    }
+
+    pub fn on_error<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_error(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_error<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_listen_v("error", f)
+    }
+
+    pub fn on_abort<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_abort(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_abort<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_listen_v("abort", f)
+    }
+
+    pub fn on_timeout<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_timeout(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_timeout<F: FnMut() + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_listen_v("timeout", f)
+    }
+
+    pub fn on_progress<F: FnMut(Progress) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_progress(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_progress<F: FnMut(Progress) + 'a>(&self, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        unsafe {
+            let b = Box::new(f);
+            let a = &*b as *const _;
+            let id = js_try! { (self.id, a as *const libc::c_void,
+                rust_caller_v_progress::<F> as *const libc::c_void)
+                "\
+                return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], 'progress', false, function (e) {\
+                    Runtime.dynCall('viiii', $2, [$1, e.loaded >>> 0, e.total >>> 0, e.lengthComputable ? 1 : 0]);\
+                });\
+            " }?;
+            Ok(ListenerHandle::new(id, self.doc, b))
+        }
+    }
+
+    fn try_listen_v<F: FnMut() + 'a>(&self, s: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        unsafe {
+            let b = Box::new(f);
+            let a = &*b as *const _;
+            let id = js_try! { (self.id, s, a as *const libc::c_void,
+                rust_caller_v::<F> as *const libc::c_void)
+                "\
+                return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], UTF8ToString($1), false, function (e) {\
+                    Runtime.dynCall('vi', $3, [$2]);\
+                });\
+            " }?;
+            Ok(ListenerHandle::new(id, self.doc, b))
+        }
+    }
+}
+
+pub(crate) fn parse_headers(s: &str) -> Vec<(String, String)> {
+    s.split("\r\n")
+        .filter_map(|line| {
+            let mut parts = line.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => Some((name.trim().to_lowercase(), value.trim().to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() {
+        let headers = parse_headers("Content-Type: text/plain\r\nX-Time: 12:30\r\n");
+        assert_eq!(headers, vec![
+            ("content-type".to_string(), "text/plain".to_string()),
+            ("x-time".to_string(), "12:30".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_response_type_set() {
+        let document = init();
+        let xhr = document.window().xml_http_request_create();
+        xhr.response_type_set(ResponseType::ArrayBuffer);
+        assert_eq!(xhr.ready_state(), 0);
+        assert_eq!(xhr.response_binary(), None);
+    }
+}
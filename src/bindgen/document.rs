@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+use std::mem;
+use web_sys;
+use ::bindgen::*;
+use ::error::*;
+
+
+pub fn init<'a>() -> Document<'a> {
+    Document {
+        document: web_window().document().expect("no global `document`"),
+        refs: RefCell::new(Vec::new()),
+        refs_v: RefCell::new(Vec::new()),
+        refs_v_u8array: RefCell::new(Vec::new()),
+        refs_v_string: RefCell::new(Vec::new()),
+        listeners: RefCell::new(Vec::new()),
+        timers: RefCell::new(Vec::new()),
+    }
+}
+
+pub struct Document<'a> {
+    pub(crate) document: web_sys::Document,
+    refs: RefCell<Vec<Box<FnMut(Event<'a>) + 'a>>>,
+    refs_v: RefCell<Vec<Box<FnMut() + 'a>>>,
+    refs_v_u8array: RefCell<Vec<Box<FnMut(&[u8]) + 'a>>>,
+    refs_v_string: RefCell<Vec<Box<FnMut(String) + 'a>>>,
+    listeners: RefCell<Vec<ListenerHandle<'a>>>,
+    timers: RefCell<Vec<TimerHandle<'a>>>,
+}
+
+impl<'a> Document<'a> {
+    pub fn window(&self) -> Window<'a> {
+        Window::new(&*self)
+    }
+
+    #[deprecated(note = "use `Window::websocket_create`")]
+    pub fn websocket_create<'b>(&'b self, url: &str) -> Option<WebSocket<'a>> {
+        self.window().websocket_create(url)
+    }
+
+    pub fn element_create<'b>(&'b self, s: &str) -> Option<HtmlNode<'a>> {
+        self.try_element_create(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_element_create<'b>(&'b self, s: &str) -> Result<Option<HtmlNode<'a>>, JsError> {
+        let element = self.document.create_element(s).map_err(js_error)?;
+        Ok(Some(HtmlNode::new(element, &*self)))
+    }
+
+    #[deprecated(note = "use `Window::location_hash_get`")]
+    pub fn location_hash_get(&self) -> String {
+        self.window().location_hash_get()
+    }
+
+    pub fn push_ref(&self, value: Box<FnMut(Event<'a>) + 'a>) {
+        self.refs.borrow_mut().push(value);
+    }
+
+    pub fn push_ref_v(&self, value: Box<FnMut() + 'a>) {
+        self.refs_v.borrow_mut().push(value);
+    }
+
+    pub fn push_ref_v_string(&self, value: Box<FnMut(String) + 'a>) {
+        self.refs_v_string.borrow_mut().push(value);
+    }
+
+    pub fn push_ref_v_u8array(&self, value: Box<FnMut(&[u8]) + 'a>) {
+        self.refs_v_u8array.borrow_mut().push(value);
+    }
+
+    pub(crate) fn push_listener(&self, value: ListenerHandle<'a>) {
+        self.listeners.borrow_mut().push(value);
+    }
+
+    pub(crate) fn push_timer(&self, value: TimerHandle<'a>) {
+        self.timers.borrow_mut().push(value);
+    }
+
+    /// Removes a forgotten timer by its `TimerHandle::key`.
+    pub(crate) fn take_timer(&self, key: u32) -> Option<TimerHandle<'a>> {
+        let mut timers = self.timers.borrow_mut();
+        let index = timers.iter().position(|timer| timer.key() == key);
+        index.map(|index| timers.swap_remove(index))
+    }
+
+    /// Drops every forgotten listener, timer and pushed closure, which unlistens and clears
+    /// them. Called when the main loop quits.
+    pub(crate) fn release_all(&self) {
+        // Taken out first, so that the handles can reach the document while they are dropped.
+        let listeners = mem::take(&mut *self.listeners.borrow_mut());
+        let timers = mem::take(&mut *self.timers.borrow_mut());
+        drop(listeners);
+        drop(timers);
+        let refs = mem::take(&mut *self.refs.borrow_mut());
+        let refs_v = mem::take(&mut *self.refs_v.borrow_mut());
+        let refs_v_u8array = mem::take(&mut *self.refs_v_u8array.borrow_mut());
+        let refs_v_string = mem::take(&mut *self.refs_v_string.borrow_mut());
+        drop((refs, refs_v, refs_v_u8array, refs_v_string));
+    }
+
+    #[deprecated(note = "use `Window::on`")]
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.window().on(s, f)
+    }
+
+    pub fn element_query<'b>(&'b self, s: &str) -> Option<HtmlNode<'a>> {
+        self.try_element_query(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_element_query<'b>(&'b self, s: &str) -> Result<Option<HtmlNode<'a>>, JsError> {
+        let element = self.document.query_selector(s).map_err(js_error)?;
+        Ok(element.map(|element| HtmlNode::new(element, &*self)))
+    }
+}
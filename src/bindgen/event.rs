@@ -0,0 +1,251 @@
+use std::mem;
+use std::ops::Deref;
+use wasm_bindgen::JsCast;
+use wasm_bindgen::closure::Closure;
+use web_sys;
+use ::bindgen::*;
+use ::error::*;
+
+
+/// Progress of a transfer, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub loaded: u32,
+    /// The total size, if the length is known.
+    pub total: Option<u32>,
+}
+
+// =================================================================================================
+
+pub struct Event<'a> {
+    pub target: Option<HtmlNode<'a>>,
+    event: web_sys::Event,
+    doc: *const Document<'a>,
+}
+
+impl<'a> Event<'a> {
+    pub(crate) fn new(event: web_sys::Event, doc: *const Document<'a>) -> Self {
+        let target = event.target()
+            .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+            .map(|element| HtmlNode::new(element, doc));
+        Event { target, event, doc }
+    }
+
+    /// Returns a `MouseEvent` view if the event is a mouse event.
+    pub fn mouse<'e>(&'e self) -> Option<MouseEvent<'e, 'a>> {
+        if self.event.is_instance_of::<web_sys::MouseEvent>() {
+            Some(MouseEvent { event: self })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a `KeyboardEvent` view if the event is a keyboard event.
+    pub fn keyboard<'e>(&'e self) -> Option<KeyboardEvent<'e, 'a>> {
+        if self.event.is_instance_of::<web_sys::KeyboardEvent>() {
+            Some(KeyboardEvent { event: self })
+        } else {
+            None
+        }
+    }
+
+    pub fn prevent_default(&self) {
+        self.event.prevent_default();
+    }
+
+    pub fn stop_propagation(&self) {
+        self.event.stop_propagation();
+    }
+
+    pub fn stop_immediate_propagation(&self) {
+        self.event.stop_immediate_propagation();
+    }
+
+    pub fn default_prevented(&self) -> bool {
+        self.event.default_prevented()
+    }
+
+    pub fn cancelable(&self) -> bool {
+        self.event.cancelable()
+    }
+}
+
+// =================================================================================================
+
+pub struct MouseEvent<'e, 'a: 'e> {
+    event: &'e Event<'a>,
+}
+
+impl<'e, 'a> Deref for MouseEvent<'e, 'a> {
+    type Target = Event<'a>;
+
+    fn deref(&self) -> &Event<'a> {
+        self.event
+    }
+}
+
+impl<'e, 'a> MouseEvent<'e, 'a> {
+    fn raw(&self) -> &web_sys::MouseEvent {
+        self.event.event.unchecked_ref()
+    }
+
+    pub fn client_x(&self) -> i32 {
+        self.raw().client_x()
+    }
+
+    pub fn client_y(&self) -> i32 {
+        self.raw().client_y()
+    }
+
+    pub fn page_x(&self) -> i32 {
+        self.raw().page_x()
+    }
+
+    pub fn page_y(&self) -> i32 {
+        self.raw().page_y()
+    }
+
+    pub fn offset_x(&self) -> i32 {
+        self.raw().offset_x()
+    }
+
+    pub fn offset_y(&self) -> i32 {
+        self.raw().offset_y()
+    }
+
+    pub fn button(&self) -> i32 {
+        self.raw().button() as i32
+    }
+
+    pub fn buttons(&self) -> i32 {
+        self.raw().buttons() as i32
+    }
+
+    pub fn alt_key(&self) -> bool {
+        self.raw().alt_key()
+    }
+
+    pub fn ctrl_key(&self) -> bool {
+        self.raw().ctrl_key()
+    }
+
+    pub fn shift_key(&self) -> bool {
+        self.raw().shift_key()
+    }
+
+    pub fn meta_key(&self) -> bool {
+        self.raw().meta_key()
+    }
+
+    /// The element the pointer came from or went to, for `mouseover`, `mouseout` and friends.
+    pub fn related_target(&self) -> Option<HtmlNode<'a>> {
+        self.raw().related_target()
+            .and_then(|target| target.dyn_into::<web_sys::Element>().ok())
+            .map(|element| HtmlNode::new(element, self.event.doc))
+    }
+}
+
+// =================================================================================================
+
+/* Owns the closure of a registered listener. The closure is handed to JS with its lifetime erased,
+ * which is sound because the handle unregisters it before anything it borrows can go away, and
+ * forgotten handles are dropped together with the `Document`. */
+
+#[must_use = "the listener is removed as soon as the handle is dropped; use `forget` to keep it"]
+pub struct ListenerHandle<'a> {
+    target: web_sys::EventTarget,
+    kind: String,
+    capture: bool,
+    closure: Closure<FnMut(web_sys::Event)>,
+    doc: *const Document<'a>,
+}
+
+impl<'a> ListenerHandle<'a> {
+    pub(crate) fn listen<F: FnMut(web_sys::Event) + 'a>(target: &web_sys::EventTarget, kind: &str, capture: bool, doc: *const Document<'a>, f: F) -> Result<Self, JsError> {
+        let f: Box<FnMut(web_sys::Event) + 'a> = Box::new(f);
+        let f: Box<FnMut(web_sys::Event)> = unsafe { mem::transmute(f) };
+        let closure = Closure::wrap(f);
+        target.add_event_listener_with_callback_and_bool(kind, closure.as_ref().unchecked_ref(), capture)
+            .map_err(js_error)?;
+        Ok(ListenerHandle {
+            target: target.clone(),
+            kind: kind.to_string(),
+            capture,
+            closure,
+            doc,
+        })
+    }
+
+    /// Detaches the listener and frees its closure.
+    pub fn remove(self) {}
+
+    /// Keeps the listener registered for as long as the `Document` lives.
+    pub fn forget(self) {
+        unsafe {
+            (&*self.doc).push_listener(self);
+        }
+    }
+}
+
+impl<'a> Drop for ListenerHandle<'a> {
+    fn drop(&mut self) {
+        let _ = self.target.remove_event_listener_with_callback_and_bool(
+            &self.kind, self.closure.as_ref().unchecked_ref(), self.capture);
+    }
+}
+
+// =================================================================================================
+
+pub struct KeyboardEvent<'e, 'a: 'e> {
+    event: &'e Event<'a>,
+}
+
+impl<'e, 'a> Deref for KeyboardEvent<'e, 'a> {
+    type Target = Event<'a>;
+
+    fn deref(&self) -> &Event<'a> {
+        self.event
+    }
+}
+
+impl<'e, 'a> KeyboardEvent<'e, 'a> {
+    fn raw(&self) -> &web_sys::KeyboardEvent {
+        self.event.event.unchecked_ref()
+    }
+
+    pub fn key(&self) -> String {
+        self.raw().key()
+    }
+
+    pub fn code(&self) -> String {
+        self.raw().code()
+    }
+
+    pub fn location(&self) -> i32 {
+        self.raw().location() as i32
+    }
+
+    pub fn repeat(&self) -> bool {
+        self.raw().repeat()
+    }
+
+    pub fn is_composing(&self) -> bool {
+        self.raw().is_composing()
+    }
+
+    pub fn alt_key(&self) -> bool {
+        self.raw().alt_key()
+    }
+
+    pub fn ctrl_key(&self) -> bool {
+        self.raw().ctrl_key()
+    }
+
+    pub fn shift_key(&self) -> bool {
+        self.raw().shift_key()
+    }
+
+    pub fn meta_key(&self) -> bool {
+        self.raw().meta_key()
+    }
+}
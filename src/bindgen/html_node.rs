@@ -0,0 +1,383 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys;
+use ::bindgen::*;
+use ::error::*;
+use ::executor::*;
+
+
+pub struct JSRef<'a> {
+    ptr: *const HtmlNode<'a>,
+}
+
+impl<'a> fmt::Debug for JSRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "JSRef({:?})", &**self)
+    }
+}
+
+impl<'a> Clone for JSRef<'a> {
+    fn clone(&self) -> JSRef<'a> {
+        JSRef {
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<'a> Deref for JSRef<'a> {
+    type Target = HtmlNode<'a>;
+
+    fn deref(&self) -> &HtmlNode<'a> {
+        unsafe {
+            &*self.ptr
+        }
+    }
+}
+
+// =================================================================================================
+
+/// An element of the page. Clones refer to the same element.
+#[derive(Clone)]
+pub struct HtmlNode<'a> {
+    pub(crate) element: web_sys::Element,
+    doc: *const Document<'a>,
+}
+
+impl<'a> fmt::Debug for HtmlNode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HtmlNode({})", self.element.tag_name().to_lowercase())
+    }
+}
+
+impl<'a> HtmlNode<'a> {
+    pub(crate) fn new(element: web_sys::Element, doc: *const Document<'a>) -> Self {
+        HtmlNode { element, doc }
+    }
+
+    pub fn root_ref(&self) -> JSRef<'a> {
+        JSRef {
+            ptr: &*self,
+        }
+    }
+
+    /* Properties the emscripten bindings read through `rs_refs[$0][name]` go through `Reflect`
+     * here too, so that SVG and other non-HTML elements behave the same. */
+    fn js(&self) -> &JsValue {
+        self.element.as_ref()
+    }
+}
+
+impl<'a> HtmlNode<'a> {
+    pub fn tagname(&self) -> String {
+        self.try_tagname().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_tagname(&self) -> Result<String, JsError> {
+        Ok(self.element.tag_name().to_lowercase())
+    }
+
+    pub fn focus(&self) {
+        self.try_focus().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_focus(&self) -> Result<(), JsError> {
+        self.element.unchecked_ref::<web_sys::HtmlElement>().focus().map_err(js_error)
+    }
+
+    pub fn html_set(&self, s: &str) {
+        self.try_html_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_set(&self, s: &str) -> Result<(), JsError> {
+        self.element.set_inner_html(s);
+        Ok(())
+    }
+
+    pub fn html_get(&self) -> String {
+        self.try_html_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_get(&self) -> Result<String, JsError> {
+        Ok(self.element.inner_html())
+    }
+
+    pub fn class_get(&self) -> HashSet<String> {
+        self.try_class_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_get(&self) -> Result<HashSet<String>, JsError> {
+        let class = self.element.class_name();
+        Ok(class.trim().split(char::is_whitespace).map(|x| x.to_string()).collect())
+    }
+
+    pub fn class_add(&self, s: &str) {
+        self.try_class_add(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_add(&self, s: &str) -> Result<(), JsError> {
+        self.element.class_list().add_1(s).map_err(js_error)
+    }
+
+    pub fn class_toggle(&self, s: &str) {
+        self.try_class_toggle(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_toggle(&self, s: &str) -> Result<(), JsError> {
+        self.element.class_list().toggle(s).map(|_| ()).map_err(js_error)
+    }
+
+    pub fn class_remove(&self, s: &str) {
+        self.try_class_remove(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_remove(&self, s: &str) -> Result<(), JsError> {
+        self.element.class_list().remove_1(s).map_err(js_error)
+    }
+
+    /// The parent element. Unlike with emscripten, the parent of `<html>` is `None` rather than
+    /// the document.
+    pub fn parent(&self) -> Option<HtmlNode<'a>> {
+        self.try_parent().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_parent(&self) -> Result<Option<HtmlNode<'a>>, JsError> {
+        Ok(self.element.parent_element().map(|element| HtmlNode::new(element, self.doc)))
+    }
+
+    pub fn data_set(&self, s: &str, v: &str) {
+        self.try_data_set(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_data_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        let dataset = js_get(self.js(), "dataset")?;
+        js_set(&dataset, s, &JsValue::from_str(v))
+    }
+
+    pub fn data_get(&self, s: &str) -> Option<String> {
+        self.try_data_get(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_data_get(&self, s: &str) -> Result<Option<String>, JsError> {
+        let dataset = js_get(self.js(), "dataset")?;
+        Ok(js_get(&dataset, s)?.as_string())
+    }
+
+    pub fn style_set_str(&self, s: &str, v: &str) {
+        self.try_style_set_str(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_style_set_str(&self, s: &str, v: &str) -> Result<(), JsError> {
+        let style = js_get(self.js(), "style")?;
+        js_set(&style, s, &JsValue::from_str(v))
+    }
+
+    pub fn style_get_str(&self, s: &str) -> String {
+        self.try_style_get_str(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_style_get_str(&self, s: &str) -> Result<String, JsError> {
+        let style = js_get(self.js(), "style")?;
+        Ok(js_string(&js_get(&style, s)?))
+    }
+
+    pub fn prop_set_i32(&self, s: &str, v: i32) {
+        self.try_prop_set_i32(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_i32(&self, s: &str, v: i32) -> Result<(), JsError> {
+        js_set(self.js(), s, &JsValue::from(v))
+    }
+
+    pub fn prop_set_str(&self, s: &str, v: &str) {
+        self.try_prop_set_str(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_str(&self, s: &str, v: &str) -> Result<(), JsError> {
+        js_set(self.js(), s, &JsValue::from_str(v))
+    }
+
+    /// Sets a numeric property without truncating it, as `scrollTop` or `opacity` need.
+    pub fn prop_set_f64(&self, s: &str, v: f64) {
+        self.try_prop_set_f64(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_f64(&self, s: &str, v: f64) -> Result<(), JsError> {
+        js_set(self.js(), s, &JsValue::from_f64(v))
+    }
+
+    pub fn prop_set_bool(&self, s: &str, v: bool) {
+        self.try_prop_set_bool(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_bool(&self, s: &str, v: bool) -> Result<(), JsError> {
+        js_set(self.js(), s, &JsValue::from_bool(v))
+    }
+
+    pub fn prop_get_i32(&self, s: &str) -> i32 {
+        self.try_prop_get_i32(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_i32(&self, s: &str) -> Result<i32, JsError> {
+        let value = js_get(self.js(), s)?;
+        Ok(value.unchecked_into_f64() as i32)
+    }
+
+    /// Reads a property as a number, `NaN` if it is not numeric.
+    pub fn prop_get_f64(&self, s: &str) -> f64 {
+        self.try_prop_get_f64(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_f64(&self, s: &str) -> Result<f64, JsError> {
+        let value = js_get(self.js(), s)?;
+        Ok(value.unchecked_into_f64())
+    }
+
+    /// Reads a property with JS truthiness.
+    pub fn prop_get_bool(&self, s: &str) -> bool {
+        self.try_prop_get_bool(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_bool(&self, s: &str) -> Result<bool, JsError> {
+        Ok(js_get(self.js(), s)?.is_truthy())
+    }
+
+    /// Unset properties read as an empty string.
+    pub fn prop_get_str(&self, s: &str) -> String {
+        self.try_prop_get_str(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_str(&self, s: &str) -> Result<String, JsError> {
+        Ok(js_string(&js_get(self.js(), s)?))
+    }
+
+    pub fn append(&self, s: &HtmlNode) {
+        self.try_append(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_append(&self, s: &HtmlNode) -> Result<(), JsError> {
+        self.element.append_child(&s.element).map(|_| ()).map_err(js_error)
+    }
+
+    pub fn html_append(&self, s: &str) {
+        self.try_html_append(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_append(&self, s: &str) -> Result<(), JsError> {
+        self.element.insert_adjacent_html("beforeEnd", s).map_err(js_error)
+    }
+
+    pub fn html_prepend(&self, s: &str) {
+        self.try_html_prepend(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_prepend(&self, s: &str) -> Result<(), JsError> {
+        self.element.insert_adjacent_html("afterBegin", s).map_err(js_error)
+    }
+
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_on(s, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let doc = self.doc;
+        ListenerHandle::listen(&self.element, s, false, doc, move |e| f(Event::new(e, doc)))
+    }
+
+    /// Resolves with the next `s` event on the node.
+    pub fn next_event(&self, s: &str) -> NextEvent<'a> {
+        self.try_next_event(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_next_event(&self, s: &str) -> Result<NextEvent<'a>, JsError> {
+        self.try_events(s).map(NextEvent::new)
+    }
+
+    /// Yields every `s` event on the node until the stream is dropped.
+    pub fn events(&self, s: &str) -> EventStream<'a, Event<'a>> {
+        self.try_events(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_events(&self, s: &str) -> Result<EventStream<'a, Event<'a>>, JsError> {
+        let (tx, rx) = channel();
+        let listener = self.try_on(s, move |e| tx.send(e))?;
+        Ok(EventStream::new(vec![listener], rx))
+    }
+
+    pub fn captured_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_captured_on(s, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_captured_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let doc = self.doc;
+        ListenerHandle::listen(&self.element, s, true, doc, move |e| f(Event::new(e, doc)))
+    }
+
+    pub fn on_keydown<F: FnMut(KeyboardEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_keydown(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_keydown<F: FnMut(KeyboardEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_on("keydown", move |e| if let Some(k) = e.keyboard() { f(k) })
+    }
+
+    pub fn on_keyup<F: FnMut(KeyboardEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_keyup(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_keyup<F: FnMut(KeyboardEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_on("keyup", move |e| if let Some(k) = e.keyboard() { f(k) })
+    }
+
+    pub fn remove_self(&self) {
+        self.try_remove_self().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_remove_self(&self) -> Result<(), JsError> {
+        match self.element.parent_node() {
+            Some(parent) => parent.remove_child(&self.element).map(|_| ()).map_err(js_error),
+            None => Err(JsError {
+                name: "TypeError".to_string(),
+                message: "the node has no parent".to_string(),
+                stack: None,
+            }),
+        }
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+#[path = "../tests/html_node.rs"]
+mod shared_tests;
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use wasm_bindgen_test::*;
+    use super::*;
+    use super::shared_tests::with_html;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn test_on() {
+        with_html(
+            r#"<div class="my_class"></div>"#,
+            |doc| {
+                let elem = doc.element_query(".my_class").unwrap();
+                let clicks = Rc::new(Cell::new(0));
+                let counter = clicks.clone();
+                let handle = elem.on("click", move |e| {
+                    assert_eq!(e.target.as_ref().unwrap().tagname(), "div");
+                    counter.set(counter.get() + 1)
+                });
+                elem.element.unchecked_ref::<web_sys::HtmlElement>().click();
+                assert_eq!(clicks.get(), 1);
+                handle.remove();
+                elem.element.unchecked_ref::<web_sys::HtmlElement>().click();
+                assert_eq!(clicks.get(), 1);
+            }
+        )
+    }
+}
@@ -0,0 +1,286 @@
+use std::iter::IntoIterator;
+use web_sys;
+use ::bindgen::*;
+use ::error::*;
+
+
+fn local() -> Result<web_sys::Storage, JsError> {
+    web_window().local_storage().map_err(js_error)?.ok_or_else(unavailable)
+}
+
+fn session() -> Result<web_sys::Storage, JsError> {
+    web_window().session_storage().map_err(js_error)?.ok_or_else(unavailable)
+}
+
+fn unavailable() -> JsError {
+    JsError {
+        name: "SecurityError".to_string(),
+        message: "storage is not available".to_string(),
+        stack: None,
+    }
+}
+
+pub struct LocalStorageInterface;
+
+/// What iterating over `LocalStorage` yields: the values, as before the `Storage` trait.
+pub type LocalStorageIterator = LocalStorageValues;
+
+pub type LocalStorageKeys = Keys<'static, LocalStorageInterface>;
+
+pub type LocalStorageValues = Values<'static, LocalStorageInterface>;
+
+pub type LocalStorageEntries = Entries<'static, LocalStorageInterface>;
+
+impl LocalStorageInterface {
+    /// Calls `f` when another document changes `localStorage`.
+    pub fn on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, f: F) -> ListenerHandle<'a> {
+        self.try_on_change(window, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        window.try_on_storage_change(move |e| if e.area == StorageArea::Local { f(e) })
+    }
+
+    // The `Storage` methods, also available without the trait in scope.
+
+    pub fn len(&self) -> i32 {
+        Storage::len(self)
+    }
+
+    pub fn try_len(&self) -> Result<i32, JsError> {
+        Storage::try_len(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Storage::is_empty(self)
+    }
+
+    pub fn clear(&self) {
+        Storage::clear(self)
+    }
+
+    pub fn try_clear(&self) -> Result<(), JsError> {
+        Storage::try_clear(self)
+    }
+
+    pub fn remove(&self, s: &str) {
+        Storage::remove(self, s)
+    }
+
+    pub fn try_remove(&self, s: &str) -> Result<(), JsError> {
+        Storage::try_remove(self, s)
+    }
+
+    pub fn set(&self, s: &str, v: &str) {
+        Storage::set(self, s, v)
+    }
+
+    pub fn try_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        Storage::try_set(self, s, v)
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        Storage::get(self, name)
+    }
+
+    pub fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        Storage::try_get(self, name)
+    }
+
+    /// Returns the name of the key at `index`, or `None` past the end.
+    pub fn key(&self, index: i32) -> Option<String> {
+        Storage::key(self, index)
+    }
+
+    pub fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        Storage::try_key(self, index)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        Storage::contains_key(self, name)
+    }
+
+    pub fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        Storage::try_contains_key(self, name)
+    }
+
+    pub fn keys(&self) -> LocalStorageKeys {
+        StorageExt::keys(&LocalStorage)
+    }
+
+    pub fn values(&self) -> LocalStorageValues {
+        StorageExt::values(&LocalStorage)
+    }
+
+    pub fn entries(&self) -> LocalStorageEntries {
+        StorageExt::entries(&LocalStorage)
+    }
+}
+
+impl Storage for LocalStorageInterface {
+    fn try_len(&self) -> Result<i32, JsError> {
+        local()?.length().map(|n| n as i32).map_err(js_error)
+    }
+
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        if index < 0 {
+            return Ok(None);
+        }
+        local()?.key(index as u32).map_err(js_error)
+    }
+
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        local()?.get_item(name).map_err(js_error)
+    }
+
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> {
+        local()?.set_item(name, value).map_err(js_error)
+    }
+
+    fn try_remove(&self, name: &str) -> Result<(), JsError> {
+        local()?.remove_item(name).map_err(js_error)
+    }
+
+    fn try_clear(&self) -> Result<(), JsError> {
+        local()?.clear().map_err(js_error)
+    }
+}
+
+/// Yields the values; use `entries` for `(key, value)` pairs.
+impl IntoIterator for LocalStorageInterface {
+    type Item = String;
+    type IntoIter = LocalStorageIterator;
+
+    fn into_iter(self) -> LocalStorageIterator {
+        LocalStorage.values()
+    }
+}
+
+#[allow(non_upper_case_globals)]
+pub const LocalStorage: LocalStorageInterface = LocalStorageInterface;
+
+pub struct SessionStorageInterface;
+
+pub type SessionStorageKeys = Keys<'static, SessionStorageInterface>;
+
+pub type SessionStorageValues = Values<'static, SessionStorageInterface>;
+
+pub type SessionStorageEntries = Entries<'static, SessionStorageInterface>;
+
+impl SessionStorageInterface {
+    /// Calls `f` when another document of the same tab changes `sessionStorage`.
+    pub fn on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, f: F) -> ListenerHandle<'a> {
+        self.try_on_change(window, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        window.try_on_storage_change(move |e| if e.area == StorageArea::Session { f(e) })
+    }
+
+    // The `Storage` methods, also available without the trait in scope.
+
+    pub fn len(&self) -> i32 {
+        Storage::len(self)
+    }
+
+    pub fn try_len(&self) -> Result<i32, JsError> {
+        Storage::try_len(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Storage::is_empty(self)
+    }
+
+    pub fn clear(&self) {
+        Storage::clear(self)
+    }
+
+    pub fn try_clear(&self) -> Result<(), JsError> {
+        Storage::try_clear(self)
+    }
+
+    pub fn remove(&self, s: &str) {
+        Storage::remove(self, s)
+    }
+
+    pub fn try_remove(&self, s: &str) -> Result<(), JsError> {
+        Storage::try_remove(self, s)
+    }
+
+    pub fn set(&self, s: &str, v: &str) {
+        Storage::set(self, s, v)
+    }
+
+    pub fn try_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        Storage::try_set(self, s, v)
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        Storage::get(self, name)
+    }
+
+    pub fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        Storage::try_get(self, name)
+    }
+
+    /// Returns the name of the key at `index`, or `None` past the end.
+    pub fn key(&self, index: i32) -> Option<String> {
+        Storage::key(self, index)
+    }
+
+    pub fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        Storage::try_key(self, index)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        Storage::contains_key(self, name)
+    }
+
+    pub fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        Storage::try_contains_key(self, name)
+    }
+
+    pub fn keys(&self) -> SessionStorageKeys {
+        StorageExt::keys(&SessionStorage)
+    }
+
+    pub fn values(&self) -> SessionStorageValues {
+        StorageExt::values(&SessionStorage)
+    }
+
+    pub fn entries(&self) -> SessionStorageEntries {
+        StorageExt::entries(&SessionStorage)
+    }
+}
+
+impl Storage for SessionStorageInterface {
+    fn try_len(&self) -> Result<i32, JsError> {
+        session()?.length().map(|n| n as i32).map_err(js_error)
+    }
+
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        if index < 0 {
+            return Ok(None);
+        }
+        session()?.key(index as u32).map_err(js_error)
+    }
+
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        session()?.get_item(name).map_err(js_error)
+    }
+
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> {
+        session()?.set_item(name, value).map_err(js_error)
+    }
+
+    fn try_remove(&self, name: &str) -> Result<(), JsError> {
+        session()?.remove_item(name).map_err(js_error)
+    }
+
+    fn try_clear(&self) -> Result<(), JsError> {
+        session()?.clear().map_err(js_error)
+    }
+}
+
+#[allow(non_upper_case_globals)]
+pub const SessionStorage: SessionStorageInterface = SessionStorageInterface;
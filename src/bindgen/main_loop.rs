@@ -0,0 +1,158 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen::closure::Closure;
+use ::bindgen::*;
+
+
+/// Does nothing: with wasm-bindgen, listeners, timers and spawned tasks keep running after the
+/// start function returns.
+pub fn keep_alive() {}
+
+#[derive(Debug, Clone, Copy)]
+enum Pending {
+    Timeout(i32),
+    AnimationFrame(i32),
+}
+
+struct LoopState {
+    doc: *const Document<'static>,
+    frame: RefCell<Option<Box<FnMut()>>>,
+    frame_generation: Cell<u32>,
+    fps: Cell<i32>,
+    started: Cell<bool>,
+    quitting: Cell<bool>,
+    pending: Cell<Option<Pending>>,
+    // Holds the state alive until `quit`, which breaks the cycle.
+    tick: RefCell<Option<Closure<FnMut(JsValue)>>>,
+}
+
+impl LoopState {
+    /// Schedules the next frame, if there is a frame callback and none is scheduled yet.
+    fn request(&self) {
+        if !self.started.get() || self.quitting.get() || self.pending.get().is_some() || self.frame.borrow().is_none() {
+            return;
+        }
+        let tick = self.tick.borrow();
+        let tick = match tick.as_ref() {
+            Some(tick) => tick.as_ref().unchecked_ref(),
+            None => return,
+        };
+        let window = web_window();
+        let fps = self.fps.get();
+        let pending = if fps > 0 {
+            window.set_timeout_with_callback_and_timeout_and_arguments_0(tick, 1000 / fps).ok().map(Pending::Timeout)
+        } else {
+            window.request_animation_frame(tick).ok().map(Pending::AnimationFrame)
+        };
+        self.pending.set(pending);
+    }
+
+    fn cancel(&self) {
+        let window = web_window();
+        match self.pending.take() {
+            Some(Pending::Timeout(id)) => window.clear_timeout_with_handle(id),
+            Some(Pending::AnimationFrame(id)) => {
+                let _ = window.cancel_animation_frame(id);
+            }
+            None => {}
+        }
+    }
+
+    fn frame(&self) {
+        self.pending.set(None);
+        let frame = self.frame.borrow_mut().take();
+        if let Some(mut frame) = frame {
+            let generation = self.frame_generation.get();
+            frame();
+            if self.frame_generation.get() == generation {
+                *self.frame.borrow_mut() = Some(frame);
+            }
+        }
+        self.request();
+    }
+}
+
+/// Controls the loop started by `run`.
+#[derive(Clone)]
+pub struct MainLoop {
+    state: Rc<LoopState>,
+}
+
+impl MainLoop {
+    /// Calls `f` on every frame, replacing the previous frame callback.
+    pub fn set_frame<F: FnMut() + 'static>(&self, f: F) {
+        *self.state.frame.borrow_mut() = Some(Box::new(f));
+        self.state.frame_generation.set(self.state.frame_generation.get().wrapping_add(1));
+        self.state.request();
+    }
+
+    /// Stops calling the frame callback. The runtime stays alive.
+    pub fn clear_frame(&self) {
+        *self.state.frame.borrow_mut() = None;
+        self.state.frame_generation.set(self.state.frame_generation.get().wrapping_add(1));
+    }
+
+    /// Runs the frame callback `fps` times per second, or on every animation frame if `fps` is
+    /// zero, which is the default.
+    pub fn set_fps(&self, fps: i32) {
+        self.state.fps.set(fps);
+        if self.state.pending.get().is_some() {
+            self.state.cancel();
+            self.state.request();
+        }
+    }
+
+    pub fn fps(&self) -> i32 {
+        self.state.fps.get()
+    }
+
+    /// Stops the loop once the current callback has returned, then drops the frame callback and
+    /// every forgotten listener and timer, and the closures passed to `Document::push_ref` and
+    /// friends.
+    ///
+    /// The `Document` itself stays alive, as spawned tasks and `Window`s may still refer to it.
+    pub fn quit(&self) {
+        if self.state.quitting.replace(true) {
+            return;
+        }
+        let state = self.state.clone();
+        let quit = Closure::once_into_js(move || {
+            state.cancel();
+            let tick = state.tick.borrow_mut().take();
+            drop(tick);
+            let frame = state.frame.borrow_mut().take();
+            drop(frame);
+            unsafe { &*state.doc }.release_all();
+        });
+        web_window().queue_microtask(quit.unchecked_ref());
+    }
+}
+
+/// Creates the `Document`, calls `app` to set up the page and runs the frame callback until
+/// `MainLoop::quit`.
+///
+/// Unlike with emscripten, this returns once `app` has returned; the loop keeps running from
+/// the browser event loop.
+pub fn run<F: FnOnce(&Document<'static>, &MainLoop)>(app: F) {
+    // Never freed: anything scheduled from `app` may refer to the document after `quit`.
+    let document: &'static Document<'static> = Box::leak(Box::new(init()));
+    let state = Rc::new(LoopState {
+        doc: document,
+        frame: RefCell::new(None),
+        frame_generation: Cell::new(0),
+        fps: Cell::new(0),
+        started: Cell::new(false),
+        quitting: Cell::new(false),
+        pending: Cell::new(None),
+        tick: RefCell::new(None),
+    });
+    let tick_state = state.clone();
+    let tick: Closure<FnMut(JsValue)> = Closure::wrap(Box::new(move |_| tick_state.frame()));
+    *state.tick.borrow_mut() = Some(tick);
+
+    let main_loop = MainLoop { state: state.clone() };
+    app(document, &main_loop);
+    state.started.set(true);
+    state.request();
+}
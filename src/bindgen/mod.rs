@@ -0,0 +1,77 @@
+//! Bindings for `wasm32-unknown-unknown` through wasm-bindgen, js-sys and web-sys, compiled
+//! instead of the emscripten bindings when the `wasm-bindgen` feature is enabled.
+//!
+//! `Document`, `HtmlNode`, `Event`, `WebSocket`, the storages, timers, `run` and the executor
+//! keep the API of their emscripten counterparts. `fetch`, `XmlHttpRequest`, IndexedDB and
+//! `ReconnectingWebSocket` are only available with emscripten for now.
+//!
+//! There is no `Runtime.dynCall` or handle table here: nodes and sockets hold their JS objects
+//! directly, and closures are wrapped in `wasm_bindgen::closure::Closure`.
+
+use js_sys;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys;
+use ::error::JsError;
+
+pub mod document;
+pub mod event;
+pub mod html_node;
+pub mod local_storage;
+pub mod main_loop;
+#[path = "../api/storage.rs"]
+pub mod storage;
+pub mod timer;
+pub mod websocket;
+pub mod window;
+
+pub use self::document::*;
+pub use self::event::*;
+pub use self::html_node::*;
+pub use self::local_storage::*;
+pub use self::main_loop::*;
+pub use self::storage::*;
+pub use self::timer::*;
+pub use self::websocket::*;
+pub use self::window::*;
+
+
+/// Converts a thrown JS value into a `JsError`, like `take_last_js_exception` does for emscripten.
+pub(crate) fn js_error(value: JsValue) -> JsError {
+    match value.dyn_into::<js_sys::Error>() {
+        Ok(error) => {
+            let stack = js_sys::Reflect::get(&error, &JsValue::from_str("stack")).ok()
+                .and_then(|stack| stack.as_string());
+            JsError {
+                name: String::from(error.name()),
+                message: String::from(error.message()),
+                stack,
+            }
+        }
+        Err(value) => JsError {
+            name: String::new(),
+            message: js_string(&value),
+            stack: None,
+        },
+    }
+}
+
+/// `String(value)`, except that `null` and `undefined` read as an empty string.
+pub(crate) fn js_string(value: &JsValue) -> String {
+    match value.as_string() {
+        Some(s) => s,
+        None if value.is_null() || value.is_undefined() => String::new(),
+        None => String::from(value.unchecked_ref::<js_sys::Object>().to_string()),
+    }
+}
+
+pub(crate) fn js_get(target: &JsValue, key: &str) -> Result<JsValue, JsError> {
+    js_sys::Reflect::get(target, &JsValue::from_str(key)).map_err(js_error)
+}
+
+pub(crate) fn js_set(target: &JsValue, key: &str, value: &JsValue) -> Result<(), JsError> {
+    js_sys::Reflect::set(target, &JsValue::from_str(key), value).map(|_| ()).map_err(js_error)
+}
+
+pub(crate) fn web_window() -> web_sys::Window {
+    web_sys::window().expect("no global `window`")
+}
@@ -0,0 +1,140 @@
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::rc::Rc;
+use js_sys;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen::closure::Closure;
+use ::bindgen::*;
+use ::error::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimerKind {
+    Timeout,
+    Interval,
+    AnimationFrame,
+    Microtask,
+}
+
+trait Callback {}
+
+impl<T> Callback for T {}
+
+thread_local! {
+    static NEXT_KEY: Cell<u32> = Cell::new(0);
+}
+
+/// Owns the closure of a scheduled timer, animation frame or microtask. Dropping the handle
+/// cancels it.
+#[must_use = "the timer is cancelled as soon as the handle is dropped; use `forget` to keep it"]
+pub struct TimerHandle<'a> {
+    key: u32,
+    kind: TimerKind,
+    timer: i32,
+    cancelled: Rc<Cell<bool>>,
+    doc: *const Document<'a>,
+    _closure: Box<Callback>,
+}
+
+impl<'a> TimerHandle<'a> {
+    /// Identifies the timer in `Document::take_timer`.
+    pub(crate) fn key(&self) -> u32 {
+        self.key
+    }
+
+    /// Cancels the timer and frees its closure.
+    pub fn cancel(self) {}
+
+    /// Keeps the timer scheduled for as long as the `Document` lives. One-shot timers are
+    /// freed once they have fired.
+    pub fn forget(self) {
+        unsafe {
+            (&*self.doc).push_timer(self);
+        }
+    }
+}
+
+impl<'a> Drop for TimerHandle<'a> {
+    fn drop(&mut self) {
+        self.cancelled.set(true);
+        let window = web_window();
+        match self.kind {
+            TimerKind::Timeout => window.clear_timeout_with_handle(self.timer),
+            TimerKind::Interval => window.clear_interval_with_handle(self.timer),
+            TimerKind::AnimationFrame => {
+                let _ = window.cancel_animation_frame(self.timer);
+            }
+            TimerKind::Microtask => {}
+        }
+    }
+}
+
+/* Like `ListenerHandle::listen`, these erase the lifetime of `f`: the handle cancels the timer
+ * before anything `f` borrows can go away. One-shot callbacks drop their own forgotten handle once
+ * they have run; wasm-bindgen defers freeing a closure until it has returned. */
+
+fn next_key() -> u32 {
+    NEXT_KEY.with(|key| {
+        let next = key.get().wrapping_add(1);
+        key.set(next);
+        next
+    })
+}
+
+fn erase<'a, A>(f: Box<FnMut(A) + 'a>) -> Box<FnMut(A)> {
+    unsafe { mem::transmute(f) }
+}
+
+/// Calls `f` at most once, then releases the handle of `key` if it was forgotten.
+fn once<'a, A: 'a, F: FnOnce(A) + 'a>(doc: *const Document<'a>, key: u32, cancelled: &Rc<Cell<bool>>, f: F) -> Box<FnMut(A) + 'a> {
+    let f = RefCell::new(Some(f));
+    let cancelled = cancelled.clone();
+    Box::new(move |a| {
+        if cancelled.get() {
+            return;
+        }
+        let f = f.borrow_mut().take();
+        if let Some(f) = f {
+            f(a);
+            let _handle = unsafe { &*doc }.take_timer(key);
+        }
+    })
+}
+
+pub(crate) fn set_timeout<'a, F: FnOnce() + 'a>(doc: *const Document<'a>, ms: i32, f: F) -> Result<TimerHandle<'a>, JsError> {
+    let key = next_key();
+    let cancelled = Rc::new(Cell::new(false));
+    let closure: Closure<FnMut(JsValue)> = Closure::wrap(erase(once(doc, key, &cancelled, move |_: JsValue| f())));
+    let timer = web_window()
+        .set_timeout_with_callback_and_timeout_and_arguments_0(closure.as_ref().unchecked_ref(), ms)
+        .map_err(js_error)?;
+    Ok(TimerHandle { key, kind: TimerKind::Timeout, timer, cancelled, doc, _closure: Box::new(closure) })
+}
+
+pub(crate) fn set_interval<'a, F: FnMut() + 'a>(doc: *const Document<'a>, ms: i32, mut f: F) -> Result<TimerHandle<'a>, JsError> {
+    let key = next_key();
+    let cancelled = Rc::new(Cell::new(false));
+    let closure: Closure<FnMut(JsValue)> = Closure::wrap(erase(Box::new(move |_: JsValue| f())));
+    let timer = web_window()
+        .set_interval_with_callback_and_timeout_and_arguments_0(closure.as_ref().unchecked_ref(), ms)
+        .map_err(js_error)?;
+    Ok(TimerHandle { key, kind: TimerKind::Interval, timer, cancelled, doc, _closure: Box::new(closure) })
+}
+
+pub(crate) fn request_animation_frame<'a, F: FnOnce(f64) + 'a>(doc: *const Document<'a>, f: F) -> Result<TimerHandle<'a>, JsError> {
+    let key = next_key();
+    let cancelled = Rc::new(Cell::new(false));
+    let closure: Closure<FnMut(f64)> = Closure::wrap(erase(once(doc, key, &cancelled, f)));
+    let timer = web_window()
+        .request_animation_frame(closure.as_ref().unchecked_ref())
+        .map_err(js_error)?;
+    Ok(TimerHandle { key, kind: TimerKind::AnimationFrame, timer, cancelled, doc, _closure: Box::new(closure) })
+}
+
+pub(crate) fn queue_microtask<'a, F: FnOnce() + 'a>(doc: *const Document<'a>, f: F) -> Result<TimerHandle<'a>, JsError> {
+    let key = next_key();
+    let cancelled = Rc::new(Cell::new(false));
+    let closure: Closure<FnMut(JsValue)> = Closure::wrap(erase(once(doc, key, &cancelled, move |_: JsValue| f())));
+    let _ = js_sys::Promise::resolve(&JsValue::NULL).then(&closure);
+    Ok(TimerHandle { key, kind: TimerKind::Microtask, timer: 0, cancelled, doc, _closure: Box::new(closure) })
+}
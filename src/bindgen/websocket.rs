@@ -0,0 +1,228 @@
+use js_sys;
+use wasm_bindgen::JsCast;
+use web_sys;
+use ::bindgen::*;
+use ::error::*;
+use ::executor::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadyState {
+    Connecting,
+    Open,
+    Closing,
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseEvent {
+    pub code: u16,
+    pub reason: String,
+    pub was_clean: bool,
+}
+
+#[derive(Clone)]
+pub struct WebSocket<'a> {
+    socket: web_sys::WebSocket,
+    doc: *const Document<'a>,
+}
+
+impl<'a> WebSocket<'a> {
+    pub(crate) fn new(socket: web_sys::WebSocket, doc: *const Document<'a>) -> Self {
+        socket.set_binary_type(web_sys::BinaryType::Arraybuffer);
+        WebSocket { socket, doc }
+    }
+
+    fn listen<F: FnMut(web_sys::Event) + 'a>(&self, kind: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        ListenerHandle::listen(&self.socket, kind, false, self.doc, f)
+    }
+
+    pub fn on_open<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_open(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_open<F: FnMut() + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.listen("open", move |_| f())
+    }
+
+    pub fn on_message_string<F: FnMut(String) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_message_string(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_message_string<F: FnMut(String) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.listen("message", move |e| {
+            if let Some(data) = e.unchecked_ref::<web_sys::MessageEvent>().data().as_string() {
+                f(data)
+            }
+        })
+    }
+
+    /// The slice passed to `f` points into a buffer owned by the listener, which is reused for every
+    /// message.
+    pub fn on_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_message_binary(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_message_binary<F: FnMut(&[u8]) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let mut buf = Vec::new();
+        self.listen("message", move |e| {
+            let data = e.unchecked_ref::<web_sys::MessageEvent>().data();
+            if let Some(data) = data.dyn_ref::<js_sys::ArrayBuffer>() {
+                let bytes = js_sys::Uint8Array::new(data);
+                buf.resize(bytes.length() as usize, 0);
+                bytes.copy_to(&mut buf);
+                f(&buf)
+            }
+        })
+    }
+
+    /// Yields the text messages until the socket closes.
+    pub fn messages(&self) -> EventStream<'a, String> {
+        self.try_messages().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_messages(&self) -> Result<EventStream<'a, String>, JsError> {
+        let (tx, rx) = channel();
+        let closed = tx.clone();
+        let listeners = vec![
+            self.try_on_message_string(move |message| tx.send(message))?,
+            self.try_on_close(move |_| closed.close())?,
+        ];
+        Ok(EventStream::new(listeners, rx))
+    }
+
+    /// Yields the binary messages until the socket closes.
+    pub fn binary_messages(&self) -> EventStream<'a, Vec<u8>> {
+        self.try_binary_messages().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_binary_messages(&self) -> Result<EventStream<'a, Vec<u8>>, JsError> {
+        let (tx, rx) = channel();
+        let closed = tx.clone();
+        let listeners = vec![
+            self.try_on_message_binary(move |message| tx.send(message.to_vec()))?,
+            self.try_on_close(move |_| closed.close())?,
+        ];
+        Ok(EventStream::new(listeners, rx))
+    }
+
+    pub fn on_close<F: FnMut(CloseEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_close(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_close<F: FnMut(CloseEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.listen("close", move |e| {
+            let e = e.unchecked_ref::<web_sys::CloseEvent>();
+            f(CloseEvent {
+                code: e.code(),
+                reason: e.reason(),
+                was_clean: e.was_clean(),
+            })
+        })
+    }
+
+    pub fn on_error<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_error(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_error<F: FnMut() + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.listen("error", move |_| f())
+    }
+
+    pub fn add_event_listener_open<F: FnMut() + 'a>(&self, f: F) {
+        self.on_open(f).forget()
+    }
+
+    pub fn try_add_event_listener_open<F: FnMut() + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_open(f).map(ListenerHandle::forget)
+    }
+
+    pub fn add_event_listener_message_string<F: FnMut(String) + 'a>(&self, f: F) {
+        self.on_message_string(f).forget()
+    }
+
+    pub fn try_add_event_listener_message_string<F: FnMut(String) + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_message_string(f).map(ListenerHandle::forget)
+    }
+
+    pub fn add_event_listener_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) {
+        self.on_message_binary(f).forget()
+    }
+
+    pub fn try_add_event_listener_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_message_binary(f).map(ListenerHandle::forget)
+    }
+
+    pub fn send(&self, data: &str) {
+        self.try_send(data).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_send(&self, data: &str) -> Result<(), JsError> {
+        self.socket.send_with_str(data).map_err(js_error)
+    }
+
+    pub fn send_binary(&self, data: &[u8]) {
+        self.try_send_binary(data).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_send_binary(&self, data: &[u8]) -> Result<(), JsError> {
+        self.socket.send_with_u8_array(data).map_err(js_error)
+    }
+
+    /// Closes the connection normally (code 1000) with `data` as the reason.
+    pub fn close(&self, data: &str) {
+        self.close_with(1000, data)
+    }
+
+    pub fn try_close(&self, data: &str) -> Result<(), JsError> {
+        self.try_close_with(1000, data)
+    }
+
+    pub fn close_with(&self, code: u16, reason: &str) {
+        self.try_close_with(code, reason).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_close_with(&self, code: u16, reason: &str) -> Result<(), JsError> {
+        self.socket.close_with_code_and_reason(code, reason).map_err(js_error)
+    }
+
+    pub fn ready_state(&self) -> ReadyState {
+        self.try_ready_state().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_ready_state(&self) -> Result<ReadyState, JsError> {
+        Ok(match self.socket.ready_state() {
+            0 => ReadyState::Connecting,
+            1 => ReadyState::Open,
+            2 => ReadyState::Closing,
+            _ => ReadyState::Closed,
+        })
+    }
+
+    /// The number of bytes queued by `send` but not yet transmitted.
+    pub fn buffered_amount(&self) -> u32 {
+        self.try_buffered_amount().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_buffered_amount(&self) -> Result<u32, JsError> {
+        Ok(self.socket.buffered_amount())
+    }
+
+    /// The subprotocol selected by the server.
+    pub fn protocol(&self) -> String {
+        self.try_protocol().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_protocol(&self) -> Result<String, JsError> {
+        Ok(self.socket.protocol())
+    }
+
+    /// The extensions selected by the server.
+    pub fn extensions(&self) -> String {
+        self.try_extensions().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_extensions(&self) -> Result<String, JsError> {
+        Ok(self.socket.extensions())
+    }
+}
@@ -0,0 +1,285 @@
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys;
+use ::bindgen::*;
+use ::error::*;
+use ::executor::*;
+
+
+/// The browser window: location, history, timers, storage, dialogs and window-level events.
+///
+/// Obtained with `Document::window`.
+#[derive(Clone, Copy)]
+pub struct Window<'a> {
+    doc: *const Document<'a>,
+}
+
+impl<'a> Window<'a> {
+    pub(crate) fn new(doc: *const Document<'a>) -> Self {
+        Window { doc }
+    }
+
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_on(s, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let doc = self.doc;
+        ListenerHandle::listen(&web_window(), s, false, doc, move |e| f(Event::new(e, doc)))
+    }
+
+    pub fn websocket_create(&self, url: &str) -> Option<WebSocket<'a>> {
+        self.try_websocket_create(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_websocket_create(&self, url: &str) -> Result<Option<WebSocket<'a>>, JsError> {
+        let socket = web_sys::WebSocket::new(url).map_err(js_error)?;
+        Ok(Some(WebSocket::new(socket, self.doc)))
+    }
+
+    /// Opens a WebSocket offering the given subprotocols.
+    pub fn websocket_create_with_protocols(&self, url: &str, protocols: &[&str]) -> Option<WebSocket<'a>> {
+        self.try_websocket_create_with_protocols(url, protocols).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_websocket_create_with_protocols(&self, url: &str, protocols: &[&str]) -> Result<Option<WebSocket<'a>>, JsError> {
+        let list = js_sys::Array::new();
+        for protocol in protocols {
+            list.push(&JsValue::from_str(protocol));
+        }
+        let socket = web_sys::WebSocket::new_with_str_sequence(url, &list).map_err(js_error)?;
+        Ok(Some(WebSocket::new(socket, self.doc)))
+    }
+
+    pub fn local_storage(&self) -> LocalStorageInterface {
+        LocalStorage
+    }
+
+    pub fn session_storage(&self) -> SessionStorageInterface {
+        SessionStorage
+    }
+
+    /// Calls `f` when another document of the same origin changes `localStorage` or
+    /// `sessionStorage`. Changes made by this document are not reported.
+    pub fn on_storage_change<F: FnMut(StorageEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_storage_change(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_storage_change<F: FnMut(StorageEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        ListenerHandle::listen(&web_window(), "storage", false, self.doc, move |e| {
+            let e = e.unchecked_ref::<web_sys::StorageEvent>();
+            let area = match e.storage_area() {
+                Some(area) => JsValue::from(area),
+                None => return,
+            };
+            let window = web_window();
+            let is = |storage: Result<Option<web_sys::Storage>, JsValue>| {
+                storage.ok().and_then(|s| s).map_or(false, |s| JsValue::from(s) == area)
+            };
+            let area = if is(window.local_storage()) {
+                StorageArea::Local
+            } else if is(window.session_storage()) {
+                StorageArea::Session
+            } else {
+                return;
+            };
+            f(StorageEvent {
+                key: e.key(),
+                old_value: e.old_value(),
+                new_value: e.new_value(),
+                url: e.url().unwrap_or_default(),
+                area,
+            })
+        })
+    }
+
+    // Location
+
+    pub fn location_hash_get(&self) -> String {
+        self.try_location_hash_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_hash_get(&self) -> Result<String, JsError> {
+        web_window().location().hash().map_err(js_error)
+    }
+
+    pub fn location_hash_set(&self, s: &str) {
+        self.try_location_hash_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_hash_set(&self, s: &str) -> Result<(), JsError> {
+        web_window().location().set_hash(s).map_err(js_error)
+    }
+
+    pub fn location_href_get(&self) -> String {
+        self.try_location_href_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_href_get(&self) -> Result<String, JsError> {
+        web_window().location().href().map_err(js_error)
+    }
+
+    pub fn location_href_set(&self, s: &str) {
+        self.try_location_href_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_href_set(&self, s: &str) -> Result<(), JsError> {
+        web_window().location().set_href(s).map_err(js_error)
+    }
+
+    pub fn location_reload(&self) {
+        self.try_location_reload().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_reload(&self) -> Result<(), JsError> {
+        web_window().location().reload().map_err(js_error)
+    }
+
+    // History
+
+    fn history(&self) -> Result<web_sys::History, JsError> {
+        web_window().history().map_err(js_error)
+    }
+
+    pub fn history_length(&self) -> i32 {
+        self.try_history_length().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_length(&self) -> Result<i32, JsError> {
+        self.history()?.length().map(|n| n as i32).map_err(js_error)
+    }
+
+    pub fn history_push(&self, url: &str) {
+        self.try_history_push(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_push(&self, url: &str) -> Result<(), JsError> {
+        self.history()?.push_state_with_url(&JsValue::NULL, "", Some(url)).map_err(js_error)
+    }
+
+    pub fn history_replace(&self, url: &str) {
+        self.try_history_replace(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_replace(&self, url: &str) -> Result<(), JsError> {
+        self.history()?.replace_state_with_url(&JsValue::NULL, "", Some(url)).map_err(js_error)
+    }
+
+    pub fn history_go(&self, delta: i32) {
+        self.try_history_go(delta).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_go(&self, delta: i32) -> Result<(), JsError> {
+        self.history()?.go_with_delta(delta).map_err(js_error)
+    }
+
+    pub fn history_back(&self) {
+        self.history_go(-1)
+    }
+
+    pub fn history_forward(&self) {
+        self.history_go(1)
+    }
+
+    // Timers
+
+    /// Calls `f` once after `ms` milliseconds.
+    pub fn set_timeout<F: FnOnce() + 'a>(&self, ms: i32, f: F) -> TimerHandle<'a> {
+        self.try_set_timeout(ms, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_set_timeout<F: FnOnce() + 'a>(&self, ms: i32, f: F) -> Result<TimerHandle<'a>, JsError> {
+        timer::set_timeout(self.doc, ms, f)
+    }
+
+    /// Calls `f` every `ms` milliseconds.
+    pub fn set_interval<F: FnMut() + 'a>(&self, ms: i32, f: F) -> TimerHandle<'a> {
+        self.try_set_interval(ms, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_set_interval<F: FnMut() + 'a>(&self, ms: i32, f: F) -> Result<TimerHandle<'a>, JsError> {
+        timer::set_interval(self.doc, ms, f)
+    }
+
+    /// Resolves after `ms` milliseconds.
+    pub fn sleep(&self, ms: i32) -> Sleep<'a> {
+        self.try_sleep(ms).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_sleep(&self, ms: i32) -> Result<Sleep<'a>, JsError> {
+        let (tx, rx) = channel();
+        let timer = self.try_set_timeout(ms, move || tx.send(()))?;
+        Ok(Sleep::new(timer, rx))
+    }
+
+    /// Calls `f` before the next repaint with the `performance.now()` timestamp of the frame,
+    /// in milliseconds.
+    pub fn request_animation_frame<F: FnOnce(f64) + 'a>(&self, f: F) -> TimerHandle<'a> {
+        self.try_request_animation_frame(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_request_animation_frame<F: FnOnce(f64) + 'a>(&self, f: F) -> Result<TimerHandle<'a>, JsError> {
+        timer::request_animation_frame(self.doc, f)
+    }
+
+    pub fn queue_microtask<F: FnOnce() + 'a>(&self, f: F) -> TimerHandle<'a> {
+        self.try_queue_microtask(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_queue_microtask<F: FnOnce() + 'a>(&self, f: F) -> Result<TimerHandle<'a>, JsError> {
+        timer::queue_microtask(self.doc, f)
+    }
+
+    // Dialogs
+
+    pub fn alert(&self, s: &str) {
+        self.try_alert(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_alert(&self, s: &str) -> Result<(), JsError> {
+        web_window().alert_with_message(s).map_err(js_error)
+    }
+
+    pub fn confirm(&self, s: &str) -> bool {
+        self.try_confirm(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_confirm(&self, s: &str) -> Result<bool, JsError> {
+        web_window().confirm_with_message(s).map_err(js_error)
+    }
+
+    pub fn prompt(&self, s: &str, default: &str) -> Option<String> {
+        self.try_prompt(s, default).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prompt(&self, s: &str, default: &str) -> Result<Option<String>, JsError> {
+        web_window().prompt_with_message_and_default(s, default).map_err(js_error)
+    }
+
+    // Viewport
+
+    pub fn inner_width(&self) -> i32 {
+        self.try_inner_width().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_inner_width(&self) -> Result<i32, JsError> {
+        let width = web_window().inner_width().map_err(js_error)?;
+        Ok(width.as_f64().unwrap_or(0.0) as i32)
+    }
+
+    pub fn inner_height(&self) -> i32 {
+        self.try_inner_height().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_inner_height(&self) -> Result<i32, JsError> {
+        let height = web_window().inner_height().map_err(js_error)?;
+        Ok(height.as_f64().unwrap_or(0.0) as i32)
+    }
+
+    pub fn device_pixel_ratio(&self) -> f64 {
+        self.try_device_pixel_ratio().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_device_pixel_ratio(&self) -> Result<f64, JsError> {
+        Ok(web_window().device_pixel_ratio())
+    }
+}
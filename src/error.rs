@@ -0,0 +1,35 @@
+use std::error::Error;
+use std::fmt;
+
+
+/// A JavaScript exception caught while calling into the web platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsError {
+    /// The exception name, e.g. `SyntaxError` or `QuotaExceededError`. Empty if a non-error
+    /// value was thrown.
+    pub name: String,
+    pub message: String,
+    pub stack: Option<String>,
+}
+
+impl JsError {
+    pub(crate) fn raise<T>(self) -> T {
+        panic!("{}", self)
+    }
+}
+
+impl fmt::Display for JsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.name, self.message)
+        }
+    }
+}
+
+impl Error for JsError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+}
@@ -1,19 +1,24 @@
-use std::ffi::CStr;
+use std::cell::RefCell;
 use std::mem;
+use std::ops::Deref;
 use std::slice;
 use std::str;
 use libc;
+use ::api::*;
 use ::html_node::*;
 
 
-pub(crate) extern fn rust_caller<F: FnMut(Event)>(a: *const libc::c_void, docptr: *const libc::c_void, id: i32) {
+pub(crate) extern fn rust_caller<'a, F: FnMut(Event<'a>)>(a: *const libc::c_void, docptr: *const libc::c_void, id: i32, event_id: i32) {
     let v:&mut F = unsafe { mem::transmute(a) };
+    let doc = docptr as *const Document<'a>;
     v(Event {
         target: if id == -1 {
             None
         } else {
-            Some(HtmlNode::new( id, unsafe { mem::transmute(docptr) } ))
-        }
+            Some(HtmlNode::new( id, doc ))
+        },
+        id: event_id,
+        doc,
     });
 }
 
@@ -29,20 +34,348 @@ pub(crate) extern fn rust_caller_v<F: FnMut()>(a: *const libc::c_void) {
     v();
 }
 
-pub(crate) extern fn rust_caller_v_string<F: FnMut(String)>(a: *const libc::c_void, b: *const libc::c_char) {
+pub(crate) extern fn rust_caller_v_once<F: FnOnce()>(a: *const libc::c_void) {
+    let v:&RefCell<Option<F>> = unsafe { mem::transmute(a) };
+    let f = v.borrow_mut().take();
+    if let Some(f) = f {
+        f();
+    }
+}
+
+pub(crate) extern fn rust_caller_v_once_f64<F: FnOnce(f64)>(a: *const libc::c_void, b: f64) {
+    let v:&RefCell<Option<F>> = unsafe { mem::transmute(a) };
+    let f = v.borrow_mut().take();
+    if let Some(f) = f {
+        f(b);
+    }
+}
+
+/* The _once versions own their closure: JS calls them exactly once, and the closure is freed after
+ * it returns. */
+
+pub(crate) extern fn rust_caller_once_i32<F: FnOnce(i32)>(a: *const libc::c_void, b: libc::c_int) {
+    let v: Box<F> = unsafe { Box::from_raw(a as *mut F) };
+    v(b);
+}
+
+/// A boxed `FnOnce(i32)` handed over to JS together with its `rust_caller_once_i32` trampoline.
+pub(crate) struct CallbackOnceI32 {
+    pub(crate) ptr: *const libc::c_void,
+    pub(crate) caller: *const libc::c_void,
+    free: unsafe fn(*const libc::c_void),
+}
+
+impl CallbackOnceI32 {
+    pub(crate) fn new<F: FnOnce(i32)>(f: F) -> Self {
+        CallbackOnceI32 {
+            ptr: Box::into_raw(Box::new(f)) as *const libc::c_void,
+            caller: rust_caller_once_i32::<F> as *const libc::c_void,
+            free: free_box::<F>,
+        }
+    }
+
+    /// Frees the closure when JS failed to take it over.
+    pub(crate) unsafe fn free(self) {
+        (self.free)(self.ptr)
+    }
+}
+
+unsafe fn free_box<F>(a: *const libc::c_void) {
+    drop(Box::from_raw(a as *mut F));
+}
+
+pub(crate) extern fn rust_caller_v_i32<F: FnMut(i32)>(a: *const libc::c_void, b: libc::c_int) {
     let v:&mut F = unsafe { mem::transmute(a) };
-    let b = unsafe { str::from_utf8(CStr::from_ptr(b).to_bytes()).unwrap().to_owned() };
     v(b);
 }
 
+/// Returns the `rust_caller_v_i32` trampoline for `F`, which may be an unnameable closure type.
+pub(crate) fn caller_v_i32<F: FnMut(i32)>(_: &F) -> *const libc::c_void {
+    rust_caller_v_i32::<F> as *const libc::c_void
+}
+
 pub(crate) extern fn rust_caller_v_u8array<F: FnMut(&[u8])>(a: *const libc::c_void, start: *const libc::c_void, length: libc::c_int) {
     let v:&mut F = unsafe { mem::transmute(a) };
     let b:&[u8] = unsafe { slice::from_raw_parts(start as *const u8, length as usize) };
     v(b);
 }
 
+pub(crate) extern fn rust_caller_v_progress<F: FnMut(Progress)>(a: *const libc::c_void, loaded: libc::c_int, total: libc::c_int, computable: libc::c_int) {
+    let v:&mut F = unsafe { mem::transmute(a) };
+    v(Progress {
+        loaded: loaded as u32,
+        total: if computable != 0 { Some(total as u32) } else { None },
+    });
+}
+
+// =================================================================================================
+
+/// Progress of a transfer, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub loaded: u32,
+    /// The total size, if the length is known.
+    pub total: Option<u32>,
+}
+
 // =================================================================================================
 
 pub struct Event<'a> {
-    pub target: Option<HtmlNode<'a>>
+    pub target: Option<HtmlNode<'a>>,
+    id: libc::c_int,
+    doc: *const Document<'a>,
+}
+
+impl<'a> Drop for Event<'a> {
+    fn drop(&mut self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.ref_free($0);\
+        " };
+    }
+}
+
+impl<'a> Event<'a> {
+    /// Returns a `MouseEvent` view if the event is a mouse event.
+    pub fn mouse<'e>(&'e self) -> Option<MouseEvent<'e, 'a>> {
+        let is_mouse = js_guarded! { (self.id) "\
+            return WEBPLATFORM.rs_refs[$0] instanceof MouseEvent ? 1 : 0;\
+        " };
+        if is_mouse == 0 {
+            None
+        } else {
+            Some(MouseEvent { event: self })
+        }
+    }
+
+    /// Returns a `KeyboardEvent` view if the event is a keyboard event.
+    pub fn keyboard<'e>(&'e self) -> Option<KeyboardEvent<'e, 'a>> {
+        let is_keyboard = js_guarded! { (self.id) "\
+            return WEBPLATFORM.rs_refs[$0] instanceof KeyboardEvent ? 1 : 0;\
+        " };
+        if is_keyboard == 0 {
+            None
+        } else {
+            Some(KeyboardEvent { event: self })
+        }
+    }
+
+    pub fn prevent_default(&self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].preventDefault();\
+        " };
+    }
+
+    pub fn stop_propagation(&self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].stopPropagation();\
+        " };
+    }
+
+    pub fn stop_immediate_propagation(&self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.rs_refs[$0].stopImmediatePropagation();\
+        " };
+    }
+
+    pub fn default_prevented(&self) -> bool {
+        self.prop_bool("defaultPrevented")
+    }
+
+    pub fn cancelable(&self) -> bool {
+        self.prop_bool("cancelable")
+    }
+
+    fn prop_i32(&self, s: &str) -> i32 {
+        js_guarded! { (self.id, s) "\
+            return WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] | 0;\
+        " }
+    }
+
+    fn prop_bool(&self, s: &str) -> bool {
+        let value = js_guarded! { (self.id, s) "\
+            return WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] ? 1 : 0;\
+        " };
+        value != 0
+    }
+
+    fn prop_str(&self, s: &str) -> String {
+        js_guarded! { (self.id, s) -> String, "\
+            return String(WEBPLATFORM.rs_refs[$0][UTF8ToString($1)]);\
+        " }
+    }
+
+    fn prop_node(&self, s: &str) -> Option<HtmlNode<'a>> {
+        let id = js_guarded! { (self.id, s) "\
+            var value = WEBPLATFORM.rs_refs[$0][UTF8ToString($1)];\
+            if (!value) {\
+                return -1;\
+            }\
+            return WEBPLATFORM.ref_push(value);\
+        " };
+        if id < 0 {
+            None
+        } else {
+            Some(HtmlNode::new( id, self.doc ))
+        }
+    }
+}
+
+// =================================================================================================
+
+pub struct MouseEvent<'e, 'a: 'e> {
+    event: &'e Event<'a>,
+}
+
+impl<'e, 'a> Deref for MouseEvent<'e, 'a> {
+    type Target = Event<'a>;
+
+    fn deref(&self) -> &Event<'a> {
+        self.event
+    }
+}
+
+impl<'e, 'a> MouseEvent<'e, 'a> {
+    pub fn client_x(&self) -> i32 {
+        self.event.prop_i32("clientX")
+    }
+
+    pub fn client_y(&self) -> i32 {
+        self.event.prop_i32("clientY")
+    }
+
+    pub fn page_x(&self) -> i32 {
+        self.event.prop_i32("pageX")
+    }
+
+    pub fn page_y(&self) -> i32 {
+        self.event.prop_i32("pageY")
+    }
+
+    pub fn offset_x(&self) -> i32 {
+        self.event.prop_i32("offsetX")
+    }
+
+    pub fn offset_y(&self) -> i32 {
+        self.event.prop_i32("offsetY")
+    }
+
+    pub fn button(&self) -> i32 {
+        self.event.prop_i32("button")
+    }
+
+    pub fn buttons(&self) -> i32 {
+        self.event.prop_i32("buttons")
+    }
+
+    pub fn alt_key(&self) -> bool {
+        self.event.prop_bool("altKey")
+    }
+
+    pub fn ctrl_key(&self) -> bool {
+        self.event.prop_bool("ctrlKey")
+    }
+
+    pub fn shift_key(&self) -> bool {
+        self.event.prop_bool("shiftKey")
+    }
+
+    pub fn meta_key(&self) -> bool {
+        self.event.prop_bool("metaKey")
+    }
+
+    pub fn related_target(&self) -> Option<HtmlNode<'a>> {
+        self.event.prop_node("relatedTarget")
+    }
+}
+
+// =================================================================================================
+
+/* Owns the boxed closure of a registered listener, so the closure lives exactly as long as the JS
+ * side may call into it. Dropping the handle from inside its own callback is not supported. */
+
+trait Listener {}
+
+impl<T> Listener for T {}
+
+#[must_use = "the listener is removed as soon as the handle is dropped; use `forget` to keep it"]
+pub struct ListenerHandle<'a> {
+    id: libc::c_int,
+    doc: *const Document<'a>,
+    _closure: Box<Listener + 'a>,
+}
+
+impl<'a> ListenerHandle<'a> {
+    pub(crate) fn new<T: 'a>(id: libc::c_int, doc: *const Document<'a>, closure: Box<T>) -> Self {
+        ListenerHandle { id, doc, _closure: closure }
+    }
+
+    /// Detaches the listener and frees its closure.
+    pub fn remove(self) {}
+
+    /// Keeps the listener registered for as long as the `Document` lives.
+    pub fn forget(self) {
+        unsafe {
+            (&*self.doc).push_listener(self);
+        }
+    }
+}
+
+impl<'a> Drop for ListenerHandle<'a> {
+    fn drop(&mut self) {
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.unlisten($0);\
+        " };
+    }
+}
+
+// =================================================================================================
+
+pub struct KeyboardEvent<'e, 'a: 'e> {
+    event: &'e Event<'a>,
+}
+
+impl<'e, 'a> Deref for KeyboardEvent<'e, 'a> {
+    type Target = Event<'a>;
+
+    fn deref(&self) -> &Event<'a> {
+        self.event
+    }
+}
+
+impl<'e, 'a> KeyboardEvent<'e, 'a> {
+    pub fn key(&self) -> String {
+        self.event.prop_str("key")
+    }
+
+    pub fn code(&self) -> String {
+        self.event.prop_str("code")
+    }
+
+    pub fn location(&self) -> i32 {
+        self.event.prop_i32("location")
+    }
+
+    pub fn repeat(&self) -> bool {
+        self.event.prop_bool("repeat")
+    }
+
+    pub fn is_composing(&self) -> bool {
+        self.event.prop_bool("isComposing")
+    }
+
+    pub fn alt_key(&self) -> bool {
+        self.event.prop_bool("altKey")
+    }
+
+    pub fn ctrl_key(&self) -> bool {
+        self.event.prop_bool("ctrlKey")
+    }
+
+    pub fn shift_key(&self) -> bool {
+        self.event.prop_bool("shiftKey")
+    }
+
+    pub fn meta_key(&self) -> bool {
+        self.event.prop_bool("metaKey")
+    }
 }
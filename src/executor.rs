@@ -0,0 +1,395 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::thread::{self, ThreadId};
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+use libc;
+#[cfg(feature = "futures")]
+use futures_core::Stream;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+use ::api::*;
+#[cfg(feature = "native")]
+use ::native::*;
+#[cfg(feature = "wasm-bindgen")]
+use ::bindgen::*;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+use ::event::*;
+
+
+/* A single-threaded executor. Woken tasks are queued and polled from a JS microtask, so they run
+ * as soon as the callback that woke them returns to the browser. With the native backend they
+ * run from `Window::advance` instead. */
+
+struct Task {
+    // The only thread that may poll, wake or drop the task.
+    thread: ThreadId,
+    future: RefCell<Option<Pin<Box<Future<Output = ()>>>>>,
+    queued: Cell<bool>,
+}
+
+// A `Waker` may be sent to other threads, so the task is shared through an `Arc`. Off its
+// thread, the waker only touches the reference count: waking panics and dropping leaks.
+unsafe impl Send for Task {}
+unsafe impl Sync for Task {}
+
+impl Task {
+    fn on_own_thread(&self) -> bool {
+        thread::current().id() == self.thread
+    }
+}
+
+thread_local! {
+    static QUEUE: RefCell<VecDeque<Arc<Task>>> = RefCell::new(VecDeque::new());
+    static FLUSH_PENDING: Cell<bool> = Cell::new(false);
+}
+
+/// Runs `future` to completion on the browser event loop.
+///
+/// The task belongs to the calling thread: its `Waker` may be sent elsewhere, but waking it from
+/// another thread panics.
+pub fn spawn_local<F: Future<Output = ()> + 'static>(future: F) {
+    schedule(Arc::new(Task {
+        thread: thread::current().id(),
+        future: RefCell::new(Some(Box::pin(future))),
+        queued: Cell::new(false),
+    }));
+}
+
+fn schedule(task: Arc<Task>) {
+    if task.queued.replace(true) {
+        return;
+    }
+    QUEUE.with(|queue| queue.borrow_mut().push_back(task));
+    if !FLUSH_PENDING.with(|pending| pending.replace(true)) {
+        flush_later();
+    }
+}
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+fn flush_later() {
+    js_guarded! { (rust_run_tasks as *const libc::c_void) "\
+        Promise.resolve().then(function () {\
+            Runtime.dynCall('v', $0, []);\
+        });\
+    " };
+}
+
+#[cfg(feature = "wasm-bindgen")]
+fn flush_later() {
+    use wasm_bindgen::JsCast;
+    use wasm_bindgen::closure::Closure;
+
+    let run = Closure::once_into_js(|| rust_run_tasks());
+    web_window().queue_microtask(run.unchecked_ref());
+}
+
+// Left to `run_woken_tasks`.
+#[cfg(feature = "native")]
+fn flush_later() {}
+
+/// Polls the woken tasks, if any. Returns whether there were some.
+#[cfg(feature = "native")]
+pub(crate) fn run_woken_tasks() -> bool {
+    if !FLUSH_PENDING.with(|pending| pending.get()) {
+        return false;
+    }
+    rust_run_tasks();
+    true
+}
+
+extern fn rust_run_tasks() {
+    FLUSH_PENDING.with(|pending| pending.set(false));
+    // Tasks woken from here on are left for the next microtask, so a task that keeps waking
+    // itself cannot starve the browser.
+    let count = QUEUE.with(|queue| queue.borrow().len());
+    for _ in 0..count {
+        let task = match QUEUE.with(|queue| queue.borrow_mut().pop_front()) {
+            Some(task) => task,
+            None => break,
+        };
+        task.queued.set(false);
+        let future = task.future.borrow_mut().take();
+        if let Some(mut future) = future {
+            let waker = waker(task.clone());
+            let mut cx = Context::from_waker(&waker);
+            if future.as_mut().poll(&mut cx).is_pending() {
+                *task.future.borrow_mut() = Some(future);
+            }
+        }
+    }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(waker_clone, waker_wake, waker_wake_by_ref, waker_drop);
+
+fn waker(task: Arc<Task>) -> Waker {
+    unsafe { Waker::from_raw(RawWaker::new(Arc::into_raw(task) as *const (), &VTABLE)) }
+}
+
+unsafe fn waker_clone(ptr: *const ()) -> RawWaker {
+    Arc::increment_strong_count(ptr as *const Task);
+    RawWaker::new(ptr, &VTABLE)
+}
+
+unsafe fn waker_wake(ptr: *const ()) {
+    waker_wake_by_ref(ptr);
+    waker_drop(ptr);
+}
+
+unsafe fn waker_wake_by_ref(ptr: *const ()) {
+    let task = &*(ptr as *const Task);
+    if !task.on_own_thread() {
+        panic!("a task started with `spawn_local` was woken from another thread");
+    }
+    Arc::increment_strong_count(ptr as *const Task);
+    schedule(Arc::from_raw(ptr as *const Task));
+}
+
+unsafe fn waker_drop(ptr: *const ()) {
+    // Off its thread, the reference is leaked rather than risk dropping the future there.
+    if (&*(ptr as *const Task)).on_own_thread() {
+        drop(Arc::from_raw(ptr as *const Task));
+    }
+}
+
+// =================================================================================================
+
+/* Carries values from listener callbacks to the future or stream awaiting them. */
+
+struct Channel<T> {
+    queue: VecDeque<T>,
+    waker: Option<Waker>,
+    closed: bool,
+}
+
+pub(crate) struct Sender<T> {
+    inner: Rc<RefCell<Channel<T>>>,
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        Sender { inner: self.inner.clone() }
+    }
+}
+
+impl<T> Sender<T> {
+    pub(crate) fn send(&self, value: T) {
+        let waker = {
+            let mut inner = self.inner.borrow_mut();
+            inner.queue.push_back(value);
+            inner.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    pub(crate) fn close(&self) {
+        let waker = {
+            let mut inner = self.inner.borrow_mut();
+            inner.closed = true;
+            inner.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+pub(crate) struct Receiver<T> {
+    inner: Rc<RefCell<Channel<T>>>,
+}
+
+impl<T> Receiver<T> {
+    fn poll_recv(&self, cx: &mut Context) -> Poll<Option<T>> {
+        let mut inner = self.inner.borrow_mut();
+        match inner.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None if inner.closed => Poll::Ready(None),
+            None => {
+                inner.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+pub(crate) fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let inner = Rc::new(RefCell::new(Channel { queue: VecDeque::new(), waker: None, closed: false }));
+    (Sender { inner: inner.clone() }, Receiver { inner })
+}
+
+// =================================================================================================
+
+/// A stream of values delivered by event listeners, which are removed when the stream is
+/// dropped.
+pub struct EventStream<'a, T> {
+    _listeners: Vec<ListenerHandle<'a>>,
+    rx: Receiver<T>,
+}
+
+impl<'a, T> EventStream<'a, T> {
+    pub(crate) fn new(listeners: Vec<ListenerHandle<'a>>, rx: Receiver<T>) -> Self {
+        EventStream { _listeners: listeners, rx }
+    }
+
+    /// Waits for the next value; `None` once the source is closed.
+    pub fn next(&mut self) -> Next<'_, 'a, T> {
+        Next { stream: self }
+    }
+
+    pub fn poll_next(&mut self, cx: &mut Context) -> Poll<Option<T>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl<'a, T> Unpin for EventStream<'a, T> {}
+
+#[cfg(feature = "futures")]
+impl<'a, T> Stream for EventStream<'a, T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.get_mut().rx.poll_recv(cx)
+    }
+}
+
+/// Future returned by `EventStream::next`.
+pub struct Next<'s, 'a: 's, T: 's> {
+    stream: &'s mut EventStream<'a, T>,
+}
+
+impl<'s, 'a, T> Future for Next<'s, 'a, T> {
+    type Output = Option<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        self.get_mut().stream.poll_next(cx)
+    }
+}
+
+/// Future returned by `HtmlNode::next_event`.
+///
+/// The event has already been dispatched when the future completes, so `prevent_default` and
+/// `stop_propagation` have no effect on it.
+pub struct NextEvent<'a> {
+    stream: EventStream<'a, Event<'a>>,
+}
+
+impl<'a> NextEvent<'a> {
+    pub(crate) fn new(stream: EventStream<'a, Event<'a>>) -> Self {
+        NextEvent { stream }
+    }
+}
+
+impl<'a> Future for NextEvent<'a> {
+    type Output = Event<'a>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Event<'a>> {
+        match self.get_mut().stream.poll_next(cx) {
+            Poll::Ready(Some(event)) => Poll::Ready(event),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+/// Future returned by `Window::sleep`. Dropping it cancels the timer.
+pub struct Sleep<'a> {
+    _timer: TimerHandle<'a>,
+    rx: Receiver<()>,
+}
+
+impl<'a> Sleep<'a> {
+    pub(crate) fn new(timer: TimerHandle<'a>, rx: Receiver<()>) -> Self {
+        Sleep { _timer: timer, rx }
+    }
+}
+
+impl<'a> Future for Sleep<'a> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        match self.rx.poll_recv(cx) {
+            Poll::Ready(_) => Poll::Ready(()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static NOOP_VTABLE: RawWakerVTable = RawWakerVTable::new(noop_clone, noop, noop, noop);
+
+    unsafe fn noop_clone(_: *const ()) -> RawWaker {
+        RawWaker::new(0 as *const (), &NOOP_VTABLE)
+    }
+
+    unsafe fn noop(_: *const ()) {}
+
+    #[test]
+    fn test_channel() {
+        let (tx, rx) = channel();
+        let waker = unsafe { Waker::from_raw(noop_clone(0 as *const ())) };
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Pending);
+        tx.send(1);
+        tx.send(2);
+        tx.close();
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(1)));
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(Some(2)));
+        assert_eq!(rx.poll_recv(&mut cx), Poll::Ready(None));
+    }
+
+    struct Recv {
+        stream: EventStream<'static, i32>,
+        value: Rc<Cell<Option<i32>>>,
+    }
+
+    impl Future for Recv {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            let this = self.get_mut();
+            match this.stream.poll_next(cx) {
+                Poll::Ready(value) => {
+                    this.value.set(value);
+                    Poll::Ready(())
+                }
+                Poll::Pending => Poll::Pending,
+            }
+        }
+    }
+
+    #[test]
+    fn test_spawn_local() {
+        let (tx, rx) = channel();
+        let value = Rc::new(Cell::new(None));
+        spawn_local(Recv { stream: EventStream::new(Vec::new(), rx), value: value.clone() });
+        rust_run_tasks();
+        assert_eq!(value.get(), None);
+        tx.send(1);
+        rust_run_tasks();
+        assert_eq!(value.get(), Some(1));
+    }
+
+    #[test]
+    fn test_waker_off_thread() {
+        let (tx, rx) = channel();
+        let value = Rc::new(Cell::new(None));
+        spawn_local(Recv { stream: EventStream::new(Vec::new(), rx), value: value.clone() });
+        rust_run_tasks();
+        let waker = tx.inner.borrow_mut().waker.take().unwrap();
+        let other = waker.clone();
+        assert!(thread::spawn(move || other.wake_by_ref()).join().is_err());
+        let other = waker.clone();
+        assert!(thread::spawn(move || drop(other)).join().is_ok());
+        tx.inner.borrow_mut().queue.push_back(1);
+        waker.wake();
+        rust_run_tasks();
+        assert_eq!(value.get(), Some(1));
+    }
+}
@@ -2,13 +2,14 @@ use std::collections::HashSet;
 
 use std::char;
 use std::clone::Clone;
-use std::ffi::CStr;
 use std::fmt;
 use std::ops::Deref;
 use std::str;
 use libc;
 use ::api::*;
+use ::error::*;
 use ::event::*;
+use ::executor::*;
 
 
 pub struct JSRef<'a> {
@@ -52,9 +53,23 @@ impl<'a> fmt::Debug for HtmlNode<'a> {
     }
 }
 
+impl<'a> Clone for HtmlNode<'a> {
+    fn clone(&self) -> HtmlNode<'a> {
+        let id = js_guarded! { (self.id) "\
+            return WEBPLATFORM.ref_push(WEBPLATFORM.rs_refs[$0]);\
+        " };
+        HtmlNode {
+            id: id,
+            doc: self.doc,
+        }
+    }
+}
+
 impl<'a> Drop for HtmlNode<'a> {
     fn drop(&mut self) {
-        println!("dropping HTML NODE {:?}", self.id);
+        js_guarded! { (self.id) "\
+            WEBPLATFORM.ref_free($0);\
+        " };
     }
 }
 
@@ -72,408 +87,509 @@ impl<'a> HtmlNode<'a> {
 
 impl<'a> HtmlNode<'a> {
     pub fn tagname(&self) -> String {
-        let a = js_guarded! { (self.id) "\
-            var str = WEBPLATFORM.rs_refs[$0].tagName.toLowerCase();\
-            return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
-        " };
-        unsafe {
-            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-        }
+        self.try_tagname().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_tagname(&self) -> Result<String, JsError> {
+        js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].tagName.toLowerCase();\
+        " }
     }
 
     pub fn focus(&self) {
-        js_guarded! { (self.id) "\
+        self.try_focus().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_focus(&self) -> Result<(), JsError> {
+        js_try! { (self.id) "\
             WEBPLATFORM.rs_refs[$0].focus();\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn html_set(&self, s: &str) {
-        js_guarded! { (self.id, s) "\
+        self.try_html_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_set(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s) "\
             WEBPLATFORM.rs_refs[$0].innerHTML = UTF8ToString($1);\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn html_get(&self) -> String {
-        let a = js_guarded! { (self.id) "\
-            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].innerHTML), 'i8', ALLOC_STACK);\
-        " };
-        unsafe {
-            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-        }
+        self.try_html_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_get(&self) -> Result<String, JsError> {
+        js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].innerHTML;\
+        " }
     }
 
     pub fn class_get(&self) -> HashSet<String> {
-        let a = js_guarded! { (self.id) "\
-            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].className), 'i8', ALLOC_STACK);\
-        " };
-        let class = unsafe {
-            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-        };
-        class.trim().split(char::is_whitespace).map(|x| x.to_string()).collect()
+        self.try_class_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_get(&self) -> Result<HashSet<String>, JsError> {
+        let class = js_try! { (self.id) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].className;\
+        " }?;
+        Ok(class.trim().split(char::is_whitespace).map(|x| x.to_string()).collect())
     }
 
     pub fn class_add(&self, s: &str) {
-        js_guarded! { (self.id, s) "\
+        self.try_class_add(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_add(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s) "\
             WEBPLATFORM.rs_refs[$0].classList.add(UTF8ToString($1));\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn class_toggle(&self, s: &str) {
-        js_guarded! { (self.id, s) "\
+        self.try_class_toggle(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_toggle(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s) "\
             WEBPLATFORM.rs_refs[$0].classList.toggle(UTF8ToString($1));\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn class_remove(&self, s: &str) {
-        js_guarded! { (self.id, s) "\
+        self.try_class_remove(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_remove(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s) "\
             WEBPLATFORM.rs_refs[$0].classList.remove(UTF8ToString($1));\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn parent(&self) -> Option<HtmlNode<'a>> {
-        let id = js_guarded! { (self.id) "\
+        self.try_parent().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_parent(&self) -> Result<Option<HtmlNode<'a>>, JsError> {
+        let id = js_try! { (self.id) "\
             var value = WEBPLATFORM.rs_refs[$0].parentNode;\
             if (!value) {\
                 return -1;\
             }\
-            return WEBPLATFORM.rs_refs.push(value) - 1;\
-        " };
+            return WEBPLATFORM.ref_push(value);\
+        " }?;
         if id < 0 {
-            None
+            Ok(None)
         } else {
-            Some(HtmlNode {
+            Ok(Some(HtmlNode {
                 id: id,
                 doc: self.doc,
-            })
+            }))
         }
     }
 
     pub fn data_set(&self, s: &str, v: &str) {
-        js_guarded! { (self.id, s, v) "\
+        self.try_data_set(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_data_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s, v) "\
             WEBPLATFORM.rs_refs[$0].dataset[UTF8ToString($1)] = UTF8ToString($2);\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn data_get(&self, s: &str) -> Option<String> {
-        let a = js_guarded! { (self.id, s) "\
-            var str = WEBPLATFORM.rs_refs[$0].dataset[UTF8ToString($1)];\
-            if (str == null) return -1;\
-            return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
-        " };
-        if a == -1 {
-            None
-        } else {
-            Some(unsafe {
-                str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-            })
-        }
+        self.try_data_get(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_data_get(&self, s: &str) -> Result<Option<String>, JsError> {
+        js_try! { (self.id, s) -> Option<String>, "\
+            return WEBPLATFORM.rs_refs[$0].dataset[UTF8ToString($1)];\
+        " }
     }
 
     pub fn style_set_str(&self, s: &str, v: &str) {
-        js_guarded! { (self.id, s, v) "\
+        self.try_style_set_str(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_style_set_str(&self, s: &str, v: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s, v) "\
             WEBPLATFORM.rs_refs[$0].style[UTF8ToString($1)] = UTF8ToString($2);\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn style_get_str(&self, s: &str) -> String {
-        let a = js_guarded! { (self.id, s) "\
-            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0].style[UTF8ToString($1)]), 'i8', ALLOC_STACK);\
-        " };
-        unsafe {
-            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-        }
+        self.try_style_get_str(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_style_get_str(&self, s: &str) -> Result<String, JsError> {
+        js_try! { (self.id, s) -> String, "\
+            return WEBPLATFORM.rs_refs[$0].style[UTF8ToString($1)];\
+        " }
     }
 
     pub fn prop_set_i32(&self, s: &str, v: i32) {
-        js_guarded! { (self.id, s, v) "\
+        self.try_prop_set_i32(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_i32(&self, s: &str, v: i32) -> Result<(), JsError> {
+        js_try! { (self.id, s, v) "\
             WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] = $2;\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn prop_set_str(&self, s: &str, v: &str) {
-        js_guarded! { (self.id, s, v) "\
+        self.try_prop_set_str(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_str(&self, s: &str, v: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s, v) "\
             WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] = UTF8ToString($2);\
-        " };
+        " }?;
+        Ok(())
+    }
+
+    /// Sets a numeric property without truncating it, as `scrollTop` or `opacity` need.
+    pub fn prop_set_f64(&self, s: &str, v: f64) {
+        self.try_prop_set_f64(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_f64(&self, s: &str, v: f64) -> Result<(), JsError> {
+        js_try! { (self.id, s, v) "\
+            WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] = $2;\
+        " }?;
+        Ok(())
+    }
+
+    pub fn prop_set_bool(&self, s: &str, v: bool) {
+        self.try_prop_set_bool(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_bool(&self, s: &str, v: bool) -> Result<(), JsError> {
+        js_try! { (self.id, s, v) "\
+            WEBPLATFORM.rs_refs[$0][UTF8ToString($1)] = !!$2;\
+        " }?;
+        Ok(())
     }
 
     pub fn prop_get_i32(&self, s: &str) -> i32 {
-        return js_guarded! { (self.id, s) "\
+        self.try_prop_get_i32(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_i32(&self, s: &str) -> Result<i32, JsError> {
+        js_try! { (self.id, s) "\
             return Number(WEBPLATFORM.rs_refs[$0][UTF8ToString($1)])\
-        " };
+        " }
+    }
+
+    /// Reads a property as a number, `NaN` if it is not numeric.
+    pub fn prop_get_f64(&self, s: &str) -> f64 {
+        self.try_prop_get_f64(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_f64(&self, s: &str) -> Result<f64, JsError> {
+        js_try! { (self.id, s) -> f64, "\
+            return WEBPLATFORM.rs_refs[$0][UTF8ToString($1)];\
+        " }
+    }
+
+    /// Reads a property with JS truthiness.
+    pub fn prop_get_bool(&self, s: &str) -> bool {
+        self.try_prop_get_bool(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_bool(&self, s: &str) -> Result<bool, JsError> {
+        js_try! { (self.id, s) -> bool, "\
+            return WEBPLATFORM.rs_refs[$0][UTF8ToString($1)];\
+        " }
     }
 
     pub fn prop_get_str(&self, s: &str) -> String {
-        let a = js_guarded! { (self.id, s) "\
-            return allocate(intArrayFromString(WEBPLATFORM.rs_refs[$0][UTF8ToString($1)]), 'i8', ALLOC_STACK);\
-        " };
-        unsafe {
-            str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned()
-        }
+        self.try_prop_get_str(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_str(&self, s: &str) -> Result<String, JsError> {
+        js_try! { (self.id, s) -> String, "\
+            return WEBPLATFORM.rs_refs[$0][UTF8ToString($1)];\
+        " }
     }
 
     pub fn append(&self, s: &HtmlNode) {
-        js_guarded! { (self.id, s.id) "\
+        self.try_append(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_append(&self, s: &HtmlNode) -> Result<(), JsError> {
+        js_try! { (self.id, s.id) "\
             WEBPLATFORM.rs_refs[$0].appendChild(WEBPLATFORM.rs_refs[$1]);\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn html_append(&self, s: &str) {
-        js_guarded! { (self.id, s) "\
+        self.try_html_append(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_append(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s) "\
             WEBPLATFORM.rs_refs[$0].insertAdjacentHTML('beforeEnd', UTF8ToString($1));\
-        " };
+        " }?;
+        Ok(())
     }
 
     pub fn html_prepend(&self, s: &str) {
-        js_guarded! { (self.id, s) "\
+        self.try_html_prepend(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_prepend(&self, s: &str) -> Result<(), JsError> {
+        js_try! { (self.id, s) "\
             WEBPLATFORM.rs_refs[$0].insertAdjacentHTML('afterBegin', UTF8ToString($1));\
-        " };
+        " }?;
+        Ok(())
+    }
+
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_on(s, f).unwrap_or_else(JsError::raise)
     }
 
-    pub fn on<F: FnMut(Event) + 'a>(&self, s: &str, f: F) {
+    pub fn try_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
         unsafe {
             let b = Box::new(f);
             let a = &*b as *const _;
-            js_guarded! { (self.id, s, a as *const libc::c_void,
+            let id = js_try! { (self.id, s, a as *const libc::c_void,
                 rust_caller::<F> as *const libc::c_void,
                 self.doc as *const libc::c_void)
                 "\
-                WEBPLATFORM.rs_refs[$0].addEventListener(UTF8ToString($1), function (e) {\
-                    Runtime.dynCall('viii', $3, [$2, $4, e.target ? WEBPLATFORM.rs_refs.push(e.target) - 1 : -1]);\
-                }, false);\
-            " };
-            (&*self.doc).push_ref(b);
-//            (&*self.doc).refs.borrow_mut().push(b);
+                return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], UTF8ToString($1), false, function (e) {\
+                    Runtime.dynCall('viiii', $3, [$2, $4, e.target ? WEBPLATFORM.ref_push(e.target) : -1, WEBPLATFORM.ref_push(e)]);\
+                });\
+            " }?;
+            Ok(ListenerHandle::new(id, self.doc, b))
         }
     }
 
-    pub fn captured_on<F: FnMut(Event) + 'a>(&self, s: &str, f: F) {
+    /// Resolves with the next `s` event on the node.
+    pub fn next_event(&self, s: &str) -> NextEvent<'a> {
+        self.try_next_event(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_next_event(&self, s: &str) -> Result<NextEvent<'a>, JsError> {
+        self.try_events(s).map(NextEvent::new)
+    }
+
+    /// Yields every `s` event on the node until the stream is dropped.
+    pub fn events(&self, s: &str) -> EventStream<'a, Event<'a>> {
+        self.try_events(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_events(&self, s: &str) -> Result<EventStream<'a, Event<'a>>, JsError> {
+        let (tx, rx) = channel();
+        let listener = self.try_on(s, move |e| tx.send(e))?;
+        Ok(EventStream::new(vec![listener], rx))
+    }
+
+    pub fn captured_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_captured_on(s, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_captured_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
         unsafe {
             let b = Box::new(f);
             let a = &*b as *const _;
-            js_guarded! { (self.id, s, a as *const libc::c_void,
+            let id = js_try! { (self.id, s, a as *const libc::c_void,
                 rust_caller::<F> as *const libc::c_void,
                 self.doc as *const libc::c_void)
                 "\
-                WEBPLATFORM.rs_refs[$0].addEventListener(UTF8ToString($1), function (e) {\
-                    Runtime.dynCall('viii', $3, [$2, $4, e.target ? WEBPLATFORM.rs_refs.push(e.target) - 1 : -1]);\
-                }, true);\
-            " };
-            (&*self.doc).push_ref(b);
-//            (&*self.doc).refs.borrow_mut().push(b);
+                return WEBPLATFORM.listen(WEBPLATFORM.rs_refs[$0], UTF8ToString($1), true, function (e) {\
+                    Runtime.dynCall('viiii', $3, [$2, $4, e.target ? WEBPLATFORM.ref_push(e.target) : -1, WEBPLATFORM.ref_push(e)]);\
+                });\
+            " }?;
+            Ok(ListenerHandle::new(id, self.doc, b))
         }
     }
 
-    pub fn remove_self(&self) {
-        js_guarded! { (self.id) "\
-            var s = WEBPLATFORM.rs_refs[$0];\
-            s.parentNode.removeChild(s);\
-        " };
+    pub fn on_keydown<F: FnMut(KeyboardEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_keydown(f).unwrap_or_else(JsError::raise)
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    fn with_html(html: &str, action: fn(&Document)) {
-        let document = init();
-        let body = document.element_query("body").unwrap();
-        let elem = document.element_create("div").unwrap();
-        elem.html_append(html);
-        body.append(&elem);
-        action(&document);
-        elem.remove_self();
+    pub fn try_on_keydown<F: FnMut(KeyboardEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_on("keydown", move |e| if let Some(k) = e.keyboard() { f(k) })
     }
 
-    #[test]
-    fn test_body_exists() {
-        let document = init();
-        assert!(document.element_query("body").is_some());
+    pub fn on_keyup<F: FnMut(KeyboardEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_keyup(f).unwrap_or_else(JsError::raise)
     }
 
-    #[test]
-    fn test_query() {
-        with_html(
-            r#"<div class="my_class"></div>"#,
-            |doc| {
-                assert!(doc.element_query(".my_class").is_some());
-            }
-        )
-    }
-
-    #[test]
-    fn test_tagname() {
-        with_html(
-            r#"<div class="my_class"></div>"#,
-            |doc| {
-                assert_eq!(doc.element_query(".my_class").unwrap().tagname(), "div");
-            }
-        )
+    pub fn try_on_keyup<F: FnMut(KeyboardEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_on("keyup", move |e| if let Some(k) = e.keyboard() { f(k) })
     }
 
-    #[test]
-    fn test_focus() {
-        // TODO needs is_focused
+    pub fn remove_self(&self) {
+        self.try_remove_self().unwrap_or_else(JsError::raise)
     }
 
-    #[test]
-    fn test_html_set_get() {
-        with_html(
-            r#"<div class="my_class"></div>"#,
-            |doc| {
-                let elem = doc.element_query(".my_class").unwrap();
-                elem.html_set("123");
-                assert_eq!(elem.html_get(), "123");
-            }
-        )
+    pub fn try_remove_self(&self) -> Result<(), JsError> {
+        js_try! { (self.id) "\
+            var s = WEBPLATFORM.rs_refs[$0];\
+            s.parentNode.removeChild(s);\
+        " }?;
+        Ok(())
     }
+}
 
-    #[test]
-    fn test_class_get() {
-        use std::iter::FromIterator;
+#[cfg(test)]
+#[path = "tests/html_node.rs"]
+mod shared_tests;
 
-        with_html(
-            r#"<div class="my_class my_class2"></div>"#,
-            |doc| {
-                let expect = ["my_class", "my_class2"];
-                let found = doc.element_query(".my_class").unwrap().class_get();
-                assert_eq!(found, HashSet::from_iter(expect.iter().map(|it| it.to_string())));
-            }
-        )
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::shared_tests::with_html;
 
     #[test]
-    fn test_class_add() {
-        with_html(
-            r#"<div class="my_class"></div>"#,
-            |doc| {
-                doc.element_query(".my_class").unwrap().class_add("added_class");
-                assert!(doc.element_query(".added_class").is_some());
-            }
-        )
+    fn test_focus() {
+        // TODO needs is_focused
     }
 
     #[test]
-    fn test_class_toggle() {
+    fn test_drop_releases_handle() {
         with_html(
             r#"<div class="my_class"></div>"#,
             |doc| {
+                let id = doc.element_query(".my_class").unwrap().id;
                 let elem = doc.element_query(".my_class").unwrap();
-                elem.class_toggle("my_class");
-                assert!(doc.element_query(".my_class").is_none());
-                elem.class_toggle("my_class");
-                assert!(doc.element_query(".my_class").is_some());
+                assert_eq!(elem.id, id);
             }
         )
     }
 
     #[test]
-    fn test_class_remove() {
+    fn test_clone() {
         with_html(
             r#"<div class="my_class"></div>"#,
             |doc| {
-                doc.element_query(".my_class").unwrap().class_remove("my_class");
-                assert!(doc.element_query(".my_class").is_none());
+                let elem = doc.element_query(".my_class").unwrap();
+                let copy = elem.clone();
+                assert!(copy.id != elem.id);
+                drop(elem);
+                assert_eq!(copy.tagname(), "div");
             }
         )
     }
 
     #[test]
-    fn test_parent() {
-        with_html(
-            r#"<div class="parent"><div class="child"></div></div>"#,
-            |doc| {
-                let child = doc.element_query(".child").unwrap();
-                let parent_class = child.parent().unwrap().class_get().into_iter().next().unwrap();
-                assert_eq!(parent_class, "parent");
-            }
-        )
-    }
+    fn test_on() {
+        use std::cell::Cell;
+        use std::rc::Rc;
 
-    #[test]
-    fn test_data_set_get() {
         with_html(
             r#"<div class="my_class"></div>"#,
             |doc| {
                 let elem = doc.element_query(".my_class").unwrap();
-                elem.data_set("key", "value");
-                assert_eq!(elem.data_get("key"), Some("value".to_string()));
+                let clicks = Rc::new(Cell::new(0));
+                let counter = clicks.clone();
+                let handle = elem.on("click", move |_| counter.set(counter.get() + 1));
+                js_guarded! { (elem.id) "WEBPLATFORM.rs_refs[$0].click();" };
+                assert_eq!(clicks.get(), 1);
+                handle.remove();
+                js_guarded! { (elem.id) "WEBPLATFORM.rs_refs[$0].click();" };
+                assert_eq!(clicks.get(), 1);
             }
         )
     }
 
     #[test]
-    fn test_style_set_get() {
-        with_html(
-            r#"<div class="my_class"></div>"#,
-            |doc| {
-                let elem = doc.element_query(".my_class").unwrap();
-                elem.style_set_str("color", "red");
-                assert_eq!(elem.style_get_str("color"), "red".to_string()   );
-            }
-        )
-    }
+    fn test_mouse_event() {
+        use std::cell::Cell;
+        use std::rc::Rc;
 
-    #[test]
-    fn test_prop_set_get() {
         with_html(
             r#"<div class="my_class"></div>"#,
             |doc| {
                 let elem = doc.element_query(".my_class").unwrap();
-                elem.prop_set_str("id", "value");
-                assert_eq!(elem.prop_get_str("id"), "value".to_string()   );
+                let seen = Rc::new(Cell::new(None));
+                let result = seen.clone();
+                let _handle = elem.on("mousedown", move |e| {
+                    let m = e.mouse().unwrap();
+                    result.set(Some((m.client_x(), m.client_y(), m.button(), m.ctrl_key(), m.shift_key())));
+                });
+                js_guarded! { (elem.id) "\
+                    var e = new MouseEvent('mousedown', { clientX: 10, clientY: 20, button: 2, ctrlKey: true });\
+                    WEBPLATFORM.rs_refs[$0].dispatchEvent(e);\
+                " };
+                assert_eq!(seen.get(), Some((10, 20, 2, true, false)));
             }
         )
     }
 
     #[test]
-    fn test_append() {
-        with_html(
-            r#"<div class="my_class"></div>"#,
-            |doc| {
-                let elem1 = doc.element_query(".my_class").unwrap();
-                let elem2 = doc.element_create("div").unwrap();
-                elem1.append(&elem2);
-                let parent_class = elem2.parent().unwrap().class_get().into_iter().next().unwrap();
-                assert_eq!(parent_class, "my_class");
-            }
-        )
-    }
+    fn test_keyboard_event() {
+        use std::cell::RefCell;
+        use std::rc::Rc;
 
-    #[test]
-    fn test_remove_self() {
         with_html(
-            r#"<div class="my_class"></div>"#,
+            r#"<input class="my_class">"#,
             |doc| {
-                doc.element_query(".my_class").unwrap().remove_self();
-                assert!(doc.element_query(".my_class").is_none());
+                let elem = doc.element_query(".my_class").unwrap();
+                let seen = Rc::new(RefCell::new(None));
+                let result = seen.clone();
+                let _handle = elem.on_keydown(move |k| {
+                    *result.borrow_mut() = Some((k.key(), k.code(), k.repeat(), k.shift_key()));
+                });
+                js_guarded! { (elem.id) "\
+                    var e = new KeyboardEvent('keydown', { key: 'A', code: 'KeyA', repeat: true, shiftKey: true });\
+                    WEBPLATFORM.rs_refs[$0].dispatchEvent(e);\
+                " };
+                assert_eq!(*seen.borrow(), Some(("A".to_string(), "KeyA".to_string(), true, true)));
             }
         )
     }
 
     #[test]
-    fn test_html_append() {
+    fn test_prevent_default() {
         with_html(
-            r#"<div class="my_class">aaa</div>"#,
+            r#"<div class="my_class"></div>"#,
             |doc| {
                 let elem = doc.element_query(".my_class").unwrap();
-                elem.html_append("bbb");
-                assert_eq!(elem.html_get(), "aaabbb");
+                let _handle = elem.on("submit", |e| {
+                    assert!(e.cancelable());
+                    e.prevent_default();
+                    assert!(e.default_prevented());
+                });
+                let not_prevented = js_guarded! { (elem.id) "\
+                    var e = new Event('submit', { cancelable: true });\
+                    return WEBPLATFORM.rs_refs[$0].dispatchEvent(e) ? 1 : 0;\
+                " };
+                assert_eq!(not_prevented, 0);
             }
         )
     }
 
     #[test]
-    fn test_html_prepend() {
+    fn test_stop_propagation() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
         with_html(
-            r#"<div class="my_class">aaa</div>"#,
+            r#"<div class="parent"><div class="child"></div></div>"#,
             |doc| {
-                let elem = doc.element_query(".my_class").unwrap();
-                elem.html_prepend("bbb");
-                assert_eq!(elem.html_get(), "bbbaaa");
+                let parent = doc.element_query(".parent").unwrap();
+                let child = doc.element_query(".child").unwrap();
+                let bubbled = Rc::new(Cell::new(false));
+                let flag = bubbled.clone();
+                let _parent_handle = parent.on("click", move |_| flag.set(true));
+                let _child_handle = child.on("click", |e| e.stop_propagation());
+                js_guarded! { (child.id) "WEBPLATFORM.rs_refs[$0].click();" };
+                assert!(!bubbled.get());
             }
         )
     }
-
-    // TODO test evens
 }
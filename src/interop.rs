@@ -1,40 +1,118 @@
 use std::ffi::CString;
-use std::str;
 use libc;
+use ::error::*;
 
 
+/// How a byte slice is passed to JS: `WEBPLATFORM.u8_slice` reads it back as a view of the heap.
 #[repr(C)]
 pub(crate) struct ArenaEntryArray {
     start: libc::c_int,
     length: libc::c_int,
 }
 
+/// How an `Option` is passed to JS: `WEBPLATFORM.option` reads it back as `null` or the
+/// number the value would have been passed as.
+#[repr(C)]
+pub(crate) struct ArenaEntryOption {
+    value: f64,
+    is_some: libc::c_int,
+}
+
+/// Keeps the arguments of one `js_raw!`, `js_guarded!` or `js_try!` call alive until the call
+/// returns.
+///
+/// Byte slices are borrowed for `'a`, and each gets a boxed `ArenaEntryArray` so the pointer
+/// handed to JS stays put while more arguments are pushed. Options are boxed the same way.
 pub(crate) struct Arena<'a> {
     cstring: Vec<CString>,
-    _u8array: Vec<&'a [u8]>,
-    u8array_parts: Vec<ArenaEntryArray>,
+    u8array: Vec<&'a [u8]>,
+    u8array_parts: Vec<Box<ArenaEntryArray>>,
+    options: Vec<Box<ArenaEntryOption>>,
 }
 
 impl<'a> Arena<'a> {
     pub(crate) fn new() -> Self { Arena {
         cstring: Vec::new(),
-        _u8array: Vec::new(),
-        u8array_parts: Vec::new()
+        u8array: Vec::new(),
+        u8array_parts: Vec::new(),
+        options: Vec::new()
     } }
 }
 
-pub(crate) trait Interop {
-    fn as_int(self, _: &mut Arena) -> libc::c_int;
+/// Converts a `js_raw!`, `js_guarded!` or `js_try!` argument into the value passed to the JS
+/// snippet.
+///
+/// Integers that fit in 32 bits and `bool` arrive as numbers (`bool` as 0 or 1); `u32`, `i64`
+/// and floats arrive as doubles, so `i64` loses precision beyond 2^53. Strings and byte slices
+/// arrive as pointers and a `char` as its code point. An `Option` arrives as a pointer that
+/// `WEBPLATFORM.option` turns into `null` or what the value would have arrived as, so
+/// `Some(0)` stays distinct from `None`.
+pub(crate) trait Interop<'a> {
+    type Repr;
+
+    fn as_js(self, arena: &mut Arena<'a>) -> Self::Repr;
+}
+
+impl<'a> Interop<'a> for i32 {
+    type Repr = libc::c_int;
+
+    fn as_js(self, _: &mut Arena<'a>) -> libc::c_int {
+        return self;
+    }
+}
+
+impl<'a> Interop<'a> for u32 {
+    type Repr = f64;
+
+    fn as_js(self, _: &mut Arena<'a>) -> f64 {
+        return self as f64;
+    }
+}
+
+impl<'a> Interop<'a> for i64 {
+    type Repr = f64;
+
+    fn as_js(self, _: &mut Arena<'a>) -> f64 {
+        return self as f64;
+    }
 }
 
-impl Interop for i32 {
-    fn as_int(self, _: &mut Arena) -> libc::c_int {
+impl<'a> Interop<'a> for f64 {
+    type Repr = f64;
+
+    fn as_js(self, _: &mut Arena<'a>) -> f64 {
         return self;
     }
 }
 
-impl<'a> Interop for &'a str {
-    fn as_int(self, arena: &mut Arena) -> libc::c_int {
+impl<'a> Interop<'a> for f32 {
+    type Repr = f64;
+
+    fn as_js(self, _: &mut Arena<'a>) -> f64 {
+        return self as f64;
+    }
+}
+
+impl<'a> Interop<'a> for bool {
+    type Repr = libc::c_int;
+
+    fn as_js(self, _: &mut Arena<'a>) -> libc::c_int {
+        return self as libc::c_int;
+    }
+}
+
+impl<'a> Interop<'a> for char {
+    type Repr = libc::c_int;
+
+    fn as_js(self, _: &mut Arena<'a>) -> libc::c_int {
+        return self as libc::c_int;
+    }
+}
+
+impl<'a, 'b> Interop<'a> for &'b str {
+    type Repr = libc::c_int;
+
+    fn as_js(self, arena: &mut Arena<'a>) -> libc::c_int {
         let c = CString::new(self).unwrap();
         let ret = c.as_ptr() as libc::c_int;
         arena.cstring.push(c);
@@ -42,24 +120,250 @@ impl<'a> Interop for &'a str {
     }
 }
 
-impl<'a> Interop for &'a [u8] {
-    fn as_int(self, arena: &mut Arena) -> libc::c_int {
-        let parts = ArenaEntryArray { start: self.as_ptr() as libc::c_int, length: self.len() as libc::c_int };
-        let partsptr: *const ArenaEntryArray = &parts;
 
-        arena.u8array_parts.push(parts);
-        // BIG FIXME: This is trusting that there is another reference to self
-        // around somewhere that outlives the arena. What else could we do --
-        // require arguments to be owned? To be boxed/RC'd? Use another arena
-        // mechanism that doesn't require storing in a vec?
-        //         arena.u8array.push(&self);
+impl<'a, 'b> Interop<'a> for &'b String {
+    type Repr = libc::c_int;
 
+    fn as_js(self, arena: &mut Arena<'a>) -> libc::c_int {
+        self.as_str().as_js(arena)
+    }
+}
+
+
+impl<'a> Interop<'a> for String {
+    type Repr = libc::c_int;
+
+    fn as_js(self, arena: &mut Arena<'a>) -> libc::c_int {
+        self.as_str().as_js(arena)
+    }
+}
+
+
+impl<'a> Interop<'a> for &'a [u8] {
+    type Repr = libc::c_int;
+
+    fn as_js(self, arena: &mut Arena<'a>) -> libc::c_int {
+        let parts = Box::new(ArenaEntryArray { start: self.as_ptr() as libc::c_int, length: self.len() as libc::c_int });
+        let partsptr: *const ArenaEntryArray = &*parts;
+        arena.u8array.push(self);
+        arena.u8array_parts.push(parts);
         partsptr as libc::c_int
     }
 }
 
-impl<'a> Interop for *const libc::c_void {
-    fn as_int(self, _:&mut Arena) -> libc::c_int {
+
+impl<'a> Interop<'a> for *const libc::c_void {
+    type Repr = libc::c_int;
+
+    fn as_js(self, _: &mut Arena<'a>) -> libc::c_int {
         return self as libc::c_int;
     }
 }
+
+
+impl<'a, T: Interop<'a>> Interop<'a> for Option<T> where T::Repr: Into<f64> {
+    type Repr = libc::c_int;
+
+    fn as_js(self, arena: &mut Arena<'a>) -> libc::c_int {
+        let entry = Box::new(match self {
+            Some(value) => ArenaEntryOption { value: value.as_js(arena).into(), is_some: 1 },
+            None => ArenaEntryOption { value: 0.0, is_some: 0 },
+        });
+        let entryptr: *const ArenaEntryOption = &*entry;
+        arena.options.push(entry);
+        entryptr as libc::c_int
+    }
+}
+
+// =================================================================================================
+
+/// Converts the value returned by the JS snippet of `js_raw!`, `js_guarded!` or `js_try!` in
+/// their `-> T,` form.
+///
+/// The snippet's return value is parked in a `rs_refs` slot, and `from_ref` takes it out. The
+/// slot is freed whether or not the conversion succeeds; a conversion that throws, such as
+/// `String` of a `Symbol`, returns the exception as `Err`.
+pub(crate) trait FromJs: Sized {
+    fn from_ref(id: libc::c_int) -> Result<Self, JsError>;
+}
+
+/// `String(value)`, with lone surrogates replaced by U+FFFD.
+impl FromJs for String {
+    fn from_ref(id: libc::c_int) -> Result<Self, JsError> {
+        try_ref_string(id)
+    }
+}
+
+/// `null` and `undefined` are `None`.
+impl<T: FromJs> FromJs for Option<T> {
+    fn from_ref(id: libc::c_int) -> Result<Self, JsError> {
+        let null = js_raw! { (id) "\
+            if (WEBPLATFORM.rs_refs[$0] != null) {\
+                return 0;\
+            }\
+            WEBPLATFORM.ref_free($0);\
+            return 1;\
+        " };
+        if null == 0 { T::from_ref(id).map(Some) } else { Ok(None) }
+    }
+}
+
+/// `Number(value)`.
+impl FromJs for f64 {
+    fn from_ref(id: libc::c_int) -> Result<Self, JsError> {
+        let mut value = 0f64;
+        js_try! { (id, &mut value as *mut f64 as *const libc::c_void) "\
+            var value = WEBPLATFORM.rs_refs[$0];\
+            WEBPLATFORM.ref_free($0);\
+            HEAPF64[$1 >> 3] = Number(value);\
+        " }?;
+        Ok(value)
+    }
+}
+
+/// `Number(value)`, truncated to 32 bits like `value | 0`.
+impl FromJs for i32 {
+    fn from_ref(id: libc::c_int) -> Result<Self, JsError> {
+        js_try! { (id) "\
+            var value = WEBPLATFORM.rs_refs[$0];\
+            WEBPLATFORM.ref_free($0);\
+            return Number(value);\
+        " }
+    }
+}
+
+/// The truthiness of the value.
+impl FromJs for bool {
+    fn from_ref(id: libc::c_int) -> Result<Self, JsError> {
+        let value = js_raw! { (id) "\
+            var value = WEBPLATFORM.rs_refs[$0];\
+            WEBPLATFORM.ref_free($0);\
+            return value ? 1 : 0;\
+        " };
+        Ok(value != 0)
+    }
+}
+
+/// The bytes of an `ArrayBuffer` or of a typed array or `DataView`.
+impl FromJs for Vec<u8> {
+    fn from_ref(id: libc::c_int) -> Result<Self, JsError> {
+        try_ref_bytes(id)
+    }
+}
+
+/// Takes a string out of a `rs_refs` slot.
+pub(crate) fn ref_string(id: libc::c_int) -> String {
+    try_ref_string(id).unwrap_or_else(JsError::raise)
+}
+
+pub(crate) fn try_ref_string(id: libc::c_int) -> Result<String, JsError> {
+    let length = js_try! { (id) "\
+        try {\
+            var str = WEBPLATFORM.rs_refs[$0] = String(WEBPLATFORM.rs_refs[$0]);\
+        } catch (e) {\
+            WEBPLATFORM.ref_free($0);\
+            throw e;\
+        }\
+        return lengthBytesUTF8(str);\
+    " }?;
+    // `stringToUTF8` always writes a terminating NUL.
+    let mut buf = vec![0u8; length as usize + 1];
+    js_guarded! { (id, buf.as_mut_ptr() as *const libc::c_void, length + 1) "\
+        stringToUTF8(WEBPLATFORM.rs_refs[$0], $1, $2);\
+        WEBPLATFORM.ref_free($0);\
+    " };
+    buf.truncate(length as usize);
+    Ok(match String::from_utf8(buf) {
+        Ok(s) => s,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    })
+}
+
+/// Takes the bytes of an `ArrayBuffer` out of a `rs_refs` slot.
+pub(crate) fn ref_bytes(id: libc::c_int) -> Vec<u8> {
+    try_ref_bytes(id).unwrap_or_else(JsError::raise)
+}
+
+pub(crate) fn try_ref_bytes(id: libc::c_int) -> Result<Vec<u8>, JsError> {
+    let mut buf = Vec::new();
+    try_ref_bytes_into(id, &mut buf)?;
+    Ok(buf)
+}
+
+/// Takes the bytes of an `ArrayBuffer` out of a `rs_refs` slot, reusing the allocation of `buf`.
+pub(crate) fn ref_bytes_into(id: libc::c_int, buf: &mut Vec<u8>) {
+    try_ref_bytes_into(id, buf).unwrap_or_else(JsError::raise)
+}
+
+pub(crate) fn try_ref_bytes_into(id: libc::c_int, buf: &mut Vec<u8>) -> Result<(), JsError> {
+    let length = js_try! { (id) "\
+        var data = WEBPLATFORM.rs_refs[$0];\
+        try {\
+            data = WEBPLATFORM.rs_refs[$0] = ArrayBuffer.isView(data)\
+                ? new Uint8Array(data.buffer, data.byteOffset, data.byteLength)\
+                : new Uint8Array(data);\
+        } catch (e) {\
+            WEBPLATFORM.ref_free($0);\
+            throw e;\
+        }\
+        return data.length;\
+    " }?;
+    buf.clear();
+    buf.resize(length as usize, 0);
+    js_guarded! { (id, buf.as_mut_ptr() as *const libc::c_void) "\
+        HEAPU8.set(WEBPLATFORM.rs_refs[$0], $1);\
+        WEBPLATFORM.ref_free($0);\
+    " };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use ::api::init;
+
+    #[test]
+    fn test_u8_slices() {
+        let _document = init();
+        let first = vec![1u8, 2, 3];
+        let second: Vec<u8> = (0..100000).map(|i| i as u8).collect();
+        let joined = js_guarded! { (&first[..], &[][..], &second[..]) -> Vec<u8>, "\
+            var a = WEBPLATFORM.u8_slice($0), b = WEBPLATFORM.u8_slice($1), c = WEBPLATFORM.u8_slice($2);\
+            var out = new Uint8Array(a.length + b.length + c.length);\
+            out.set(a);\
+            out.set(c, a.length);\
+            return out;\
+        " };
+        assert_eq!(joined, [&first[..], &second[..]].concat());
+    }
+
+    #[test]
+    fn test_options() {
+        let _document = init();
+        fn describe(a: Option<i32>, b: Option<f64>, c: Option<bool>, d: Option<&str>) -> String {
+            js_guarded! { (a, b, c, d) -> String, "\
+                var d = WEBPLATFORM.option($3);\
+                return [WEBPLATFORM.option($0), WEBPLATFORM.option($1), WEBPLATFORM.option($2),\
+                    d == null ? null : UTF8ToString(d)].map(String).join(',');\
+            " }
+        }
+        assert_eq!(describe(Some(0), Some(0.0), Some(false), Some("")), "0,0,0,");
+        assert_eq!(describe(Some(-7), Some(0.25), Some(true), Some("a")), "-7,0.25,1,a");
+        assert_eq!(describe(None, None, None, None), "null,null,null,null");
+    }
+
+    #[test]
+    fn test_conversion_errors() {
+        let _document = init();
+        let error = js_try! { -> String, "return Symbol('s');" }.unwrap_err();
+        assert_eq!(error.name, "TypeError");
+        let error = js_try! { -> f64, "return { valueOf: function () { throw new RangeError('no'); } };" }.unwrap_err();
+        assert_eq!(error.name, "RangeError");
+        assert!(js_try! { -> f64, "return 'x';" }.unwrap().is_nan());
+        assert_eq!(js_try! { -> Option<i32>, "return Symbol('s');" }.unwrap_err().name, "TypeError");
+        // The slot is freed on error.
+        let live = || js_guarded! { "return WEBPLATFORM.rs_refs.filter(function (r) { return r != null; }).length;" };
+        let before = live();
+        assert!(js_try! { -> Vec<u8>, "return -1;" }.is_err());
+        assert_eq!(live(), before);
+    }
+}
@@ -1,19 +1,59 @@
 #![allow(unused_unsafe)]
 
 extern crate libc;
+#[cfg(feature = "futures")]
+extern crate futures_core;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "wasm-bindgen")]
+extern crate js_sys;
+#[cfg(feature = "wasm-bindgen")]
+extern crate wasm_bindgen;
+#[cfg(feature = "wasm-bindgen")]
+extern crate web_sys;
+#[cfg(all(test, feature = "wasm-bindgen", target_arch = "wasm32"))]
+extern crate wasm_bindgen_test;
 
 
+#[cfg(all(feature = "native", feature = "wasm-bindgen"))]
+compile_error!("the `native` and `wasm-bindgen` features select different backends and cannot be combined");
+
 #[macro_use] pub mod macros;
 
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 pub mod api;
+pub mod error;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 pub mod event;
+pub mod executor;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 pub mod html_node;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 pub mod interop;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub mod main_loop;
+#[cfg(feature = "native")]
+pub mod native;
+#[cfg(feature = "wasm-bindgen")]
+pub mod bindgen;
 
 
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 pub use api::*;
+pub use error::*;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 pub use event::*;
+pub use executor::*;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 pub use html_node::*;
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+pub use main_loop::*;
+#[cfg(feature = "native")]
+pub use native::*;
+#[cfg(feature = "wasm-bindgen")]
+pub use bindgen::*;
 
 
 extern "C" {
@@ -21,22 +61,20 @@ extern "C" {
     pub fn emscripten_asm_const(s: *const libc::c_char);
     pub fn emscripten_asm_const_int(s: *const libc::c_char, ...) -> libc::c_int;
     pub fn emscripten_pause_main_loop();
+    pub fn emscripten_resume_main_loop();
+    pub fn emscripten_cancel_main_loop();
     pub fn emscripten_set_main_loop(m: extern fn(), fps: libc::c_int, infinite: libc::c_int);
+    pub fn emscripten_set_main_loop_arg(m: extern fn(*mut libc::c_void), arg: *mut libc::c_void, fps: libc::c_int, infinite: libc::c_int);
+    pub fn emscripten_set_main_loop_timing(mode: libc::c_int, value: libc::c_int) -> libc::c_int;
 }
 
 
-extern fn leavemebe() {
-    unsafe {
-        emscripten_pause_main_loop();
-    }
-}
-
+#[deprecated(note = "use `run` or `keep_alive`")]
 pub fn spin() {
-    unsafe {
-        emscripten_set_main_loop(leavemebe, 0, 1);
-    }
+    keep_alive()
 }
 
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 #[no_mangle]
 pub extern "C" fn syscall(a: i32) -> i32 {
     if a == 355 {
@@ -45,20 +83,46 @@ pub extern "C" fn syscall(a: i32) -> i32 {
     return -1
 }
 
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
 pub fn check_last_js_exception() {
-    use std::ffi::CStr;
-    use std::str;
+    if let Some(error) = take_last_js_exception() {
+        panic!("{}", error)
+    }
+}
 
-    let a = js_raw! { "\
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+/// Takes the exception recorded by the last failed `js_guarded!` or `js_try!` call, if any.
+pub fn take_last_js_exception() -> Option<JsError> {
+    let found = js_raw! { "\
                     var exc = WEBPLATFORM.last_exc;\
+                    if (exc == null) {\
+                        return 0;\
+                    }\
                     WEBPLATFORM.last_exc = null;\
-                    var str = exc == null ? \"\" : exc.toString();\
-                    return allocate(intArrayFromString(str), 'i8', ALLOC_STACK);\
+                    if (typeof exc == 'object') {\
+                        WEBPLATFORM.last_error = {\
+                            name: String(exc.name || 'Error'),\
+                            message: String(exc.message),\
+                            stack: exc.stack == null ? null : String(exc.stack),\
+                        };\
+                    } else {\
+                        WEBPLATFORM.last_error = { name: '', message: String(exc), stack: null };\
+                    }\
+                    return 1;\
                 " };
-    unsafe {
-        let error = str::from_utf8(CStr::from_ptr(a as *const libc::c_char).to_bytes()).unwrap().to_owned();
-        if !error.is_empty() {
-            panic!(error)
-        }
+    if found == 0 {
+        return None;
     }
+    let name = last_error_field("name").unwrap_or_default();
+    let message = last_error_field("message").unwrap_or_default();
+    let stack = last_error_field("stack");
+    js_raw! { "WEBPLATFORM.last_error = null;" };
+    Some(JsError { name, message, stack })
+}
+
+#[cfg(not(any(feature = "native", feature = "wasm-bindgen")))]
+fn last_error_field(field: &str) -> Option<String> {
+    js_raw! { (field) -> Option<String>, "\
+                    return WEBPLATFORM.last_error[UTF8ToString($0)];\
+                " }
 }
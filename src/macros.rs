@@ -1,5 +1,15 @@
 #[macro_export]
 macro_rules! js_raw {
+    ( ($( $x:expr ),*) -> $t:ty, $y:expr ) => {
+        <$t as $crate::interop::FromJs>::from_ref(js_raw! { ($( $x ),*) concat!(
+                "return WEBPLATFORM.ref_push((function () { ", $y, " })());"
+        ) }).unwrap_or_else($crate::error::JsError::raise)
+    };
+    ( -> $t:ty, $y:expr ) => {
+        <$t as $crate::interop::FromJs>::from_ref(js_raw! { concat!(
+                "return WEBPLATFORM.ref_push((function () { ", $y, " })());"
+        ) }).unwrap_or_else($crate::error::JsError::raise)
+    };
     ( ($( $x:expr ),*) $y:expr ) => {
         {
             let mut arena = $crate::interop::Arena::new();
@@ -7,7 +17,7 @@ macro_rules! js_raw {
             unsafe {
                 $crate::emscripten_asm_const_int(
                         LOCAL as *const _ as *const ::libc::c_char,
-                        $($crate::interop::Interop::as_int($x, &mut arena)),
+                        $($crate::interop::Interop::as_js($x, &mut arena)),
                         *
                 )
             }
@@ -23,8 +33,23 @@ macro_rules! js_raw {
     };
 }
 
+/// Like `js_guarded!`, but returns a JS exception as `Err(JsError)` instead of panicking.
+///
+/// All three macros take an optional `-> T,` before the snippet, which converts whatever the
+/// snippet returns with `interop::FromJs`, e.g. `js_try! { (id) -> Option<String>, "..." }`.
+/// A conversion that throws is handled like an exception in the snippet.
 #[macro_export]
-macro_rules! js_guarded {
+macro_rules! js_try {
+    ( ($( $x:expr ),*) -> $t:ty, $y:expr ) => {
+        js_try! { ($( $x ),*) concat!(
+                "return WEBPLATFORM.ref_push((function () { ", $y, " })());"
+        ) }.and_then(<$t as $crate::interop::FromJs>::from_ref)
+    };
+    ( -> $t:ty, $y:expr ) => {
+        js_try! { concat!(
+                "return WEBPLATFORM.ref_push((function () { ", $y, " })());"
+        ) }.and_then(<$t as $crate::interop::FromJs>::from_ref)
+    };
     ( ($( $x:expr ),*) $y:expr ) => {
         {
             let mut arena = $crate::interop::Arena::new();
@@ -38,15 +63,14 @@ macro_rules! js_guarded {
             let ret = unsafe {
                 $crate::emscripten_asm_const_int(
                         LOCAL as *const _ as *const ::libc::c_char,
-                        $($crate::interop::Interop::as_int($x, &mut arena)),
+                        $($crate::interop::Interop::as_js($x, &mut arena)),
                         *
                 )
             };
-            if ret == -173642426 {
-                $crate::check_last_js_exception();
+            match if ret == -173642426 { $crate::take_last_js_exception() } else { None } {
+                Some(error) => Err(error),
+                None => Ok(ret),
             }
-
-            ret
         }
     };
     ( $y:expr ) => {
@@ -61,16 +85,43 @@ macro_rules! js_guarded {
             let ret = unsafe {
                 $crate::emscripten_asm_const_int(LOCAL as *const _ as *const ::libc::c_char)
             };
-            if ret == -173642426 {
-                $crate::check_last_js_exception();
+            match if ret == -173642426 { $crate::take_last_js_exception() } else { None } {
+                Some(error) => Err(error),
+                None => Ok(ret),
             }
+        }
+    };
+}
 
-            ret
+#[macro_export]
+macro_rules! js_guarded {
+    ( ($( $x:expr ),*) -> $t:ty, $y:expr ) => {
+        match js_try! { ($( $x ),*) -> $t, $y } {
+            Ok(ret) => ret,
+            Err(error) => panic!("{}", error),
+        }
+    };
+    ( -> $t:ty, $y:expr ) => {
+        match js_try! { -> $t, $y } {
+            Ok(ret) => ret,
+            Err(error) => panic!("{}", error),
+        }
+    };
+    ( ($( $x:expr ),*) $y:expr ) => {
+        match js_try! { ($( $x ),*) $y } {
+            Ok(ret) => ret,
+            Err(error) => panic!("{}", error),
+        }
+    };
+    ( $y:expr ) => {
+        match js_try! { $y } {
+            Ok(ret) => ret,
+            Err(error) => panic!("{}", error),
         }
     };
 }
 
-#[cfg(test)]
+#[cfg(all(test, not(any(feature = "native", feature = "wasm-bindgen"))))]
 mod tests {
 
     #[test]
@@ -81,7 +132,7 @@ mod tests {
 
     #[test]
     fn test_js_raw_value() {
-        let a = js_raw! { (42) "return $0;" };
+        let a = js_raw! { (42i32) "return $0;" };
         assert_eq!(a, 42);
     }
 
@@ -93,7 +144,7 @@ mod tests {
 
     #[test]
     fn test_js_guarded_value() {
-        let a = js_guarded! { (42) "return $0;" };
+        let a = js_guarded! { (42i32) "return $0;" };
         assert_eq!(a, 42);
     }
 
@@ -106,5 +157,59 @@ mod tests {
         });
         assert!(result.is_err());
     }
-}
 
+    #[test]
+    fn test_js_try_value() {
+        let a = js_try! { (42i32) "return $0;" };
+        assert_eq!(a, Ok(42));
+    }
+
+    #[test]
+    fn test_js_try_exception() {
+        let error = js_try! { "throw new SyntaxError('bad selector');" }.unwrap_err();
+        assert_eq!(error.name, "SyntaxError");
+        assert_eq!(error.message, "bad selector");
+        assert_eq!(error.to_string(), "SyntaxError: bad selector");
+    }
+
+    #[test]
+    fn test_js_guarded_typed() {
+        assert_eq!(js_guarded! { -> String, "return 'h\\u00e9llo';" }, "h\u{e9}llo");
+        assert_eq!(js_guarded! { -> Option<String>, "return null;" }, None);
+        assert_eq!(js_guarded! { (0.5f64) -> f64, "return $0 * 3;" }, 1.5);
+        assert_eq!(js_guarded! { (true) -> bool, "return !$0;" }, false);
+        assert_eq!(js_guarded! { -> Vec<u8>, "return new Uint8Array([1, 2, 3]);" }, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_js_guarded_typed_options() {
+        let _document = ::api::init();
+        assert_eq!(js_guarded! { -> Option<i32>, "return 0;" }, Some(0));
+        assert_eq!(js_guarded! { -> Option<i32>, "return undefined;" }, None);
+        assert_eq!(js_guarded! { -> Option<f64>, "return 0.5;" }, Some(0.5));
+        assert_eq!(js_guarded! { -> Option<f64>, "return null;" }, None);
+        assert_eq!(js_guarded! { -> Option<bool>, "return false;" }, Some(false));
+        assert_eq!(js_guarded! { -> Option<bool>, "return null;" }, None);
+        assert_eq!(js_guarded! { (Some(0i32)) -> Option<i32>, "return WEBPLATFORM.option($0);" }, Some(0));
+        assert_eq!(js_guarded! { (None::<i32>) -> Option<i32>, "return WEBPLATFORM.option($0);" }, None);
+    }
+
+    #[test]
+    fn test_js_guarded_large_string() {
+        let a = js_guarded! { -> String, "return new Array(1000001).join('x');" };
+        assert_eq!(a.len(), 1000000);
+    }
+
+    #[test]
+    fn test_js_try_typed_exception() {
+        let error = js_try! { -> String, "throw new TypeError('nope');" }.unwrap_err();
+        assert_eq!(error.name, "TypeError");
+    }
+
+    #[test]
+    fn test_js_try_thrown_string() {
+        let error = js_try! { "throw 'exception';" }.unwrap_err();
+        assert_eq!(error.name, "");
+        assert_eq!(error.to_string(), "exception");
+    }
+}
@@ -0,0 +1,160 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use libc;
+use ::api::*;
+use ::{emscripten_cancel_main_loop, emscripten_pause_main_loop, emscripten_resume_main_loop,
+       emscripten_set_main_loop, emscripten_set_main_loop_arg, emscripten_set_main_loop_timing};
+
+
+const EM_TIMING_SETTIMEOUT: libc::c_int = 0;
+const EM_TIMING_RAF: libc::c_int = 1;
+
+extern fn leavemebe() {
+    unsafe {
+        emscripten_pause_main_loop();
+    }
+}
+
+/// Returns from `main` without shutting down the runtime, so that listeners, timers and
+/// spawned tasks keep running.
+pub fn keep_alive() -> ! {
+    unsafe {
+        // Emscripten unwinds out of `main` here and never returns.
+        emscripten_set_main_loop(leavemebe, 0, 1);
+    }
+    unreachable!()
+}
+
+struct LoopState {
+    doc: *const Document<'static>,
+    frame: RefCell<Option<Box<FnMut()>>>,
+    frame_generation: Cell<u32>,
+    fps: Cell<i32>,
+    started: Cell<bool>,
+    quitting: Cell<bool>,
+}
+
+/// Controls the loop started by `run`.
+#[derive(Clone)]
+pub struct MainLoop {
+    state: Rc<LoopState>,
+}
+
+impl MainLoop {
+    /// Calls `f` on every frame, replacing the previous frame callback.
+    pub fn set_frame<F: FnMut() + 'static>(&self, f: F) {
+        *self.state.frame.borrow_mut() = Some(Box::new(f));
+        self.state.frame_generation.set(self.state.frame_generation.get().wrapping_add(1));
+        if self.state.started.get() {
+            unsafe {
+                emscripten_resume_main_loop();
+            }
+        }
+    }
+
+    /// Stops calling the frame callback. The runtime stays alive.
+    pub fn clear_frame(&self) {
+        *self.state.frame.borrow_mut() = None;
+        self.state.frame_generation.set(self.state.frame_generation.get().wrapping_add(1));
+    }
+
+    /// Runs the frame callback `fps` times per second, or on every animation frame if `fps` is
+    /// zero, which is the default.
+    pub fn set_fps(&self, fps: i32) {
+        self.state.fps.set(fps);
+        if self.state.started.get() {
+            set_timing(fps);
+        }
+    }
+
+    pub fn fps(&self) -> i32 {
+        self.state.fps.get()
+    }
+
+    /// Stops the loop once the current callback has returned, then drops the frame callback and
+    /// every forgotten listener and timer, and the closures passed to `Document::push_ref` and
+    /// friends.
+    ///
+    /// The `Document` itself stays alive, as spawned tasks and `Window`s may still refer to it.
+    pub fn quit(&self) {
+        if self.state.quitting.replace(true) {
+            return;
+        }
+        let ptr = Rc::into_raw(self.state.clone());
+        js_guarded! { (rust_main_loop_quit as *const libc::c_void, ptr as *const libc::c_void) "\
+            Promise.resolve().then(function () {\
+                Runtime.dynCall('vi', $0, [$1]);\
+            });\
+        " };
+    }
+}
+
+fn set_timing(fps: i32) {
+    unsafe {
+        if fps > 0 {
+            emscripten_set_main_loop_timing(EM_TIMING_SETTIMEOUT, 1000 / fps);
+        } else {
+            emscripten_set_main_loop_timing(EM_TIMING_RAF, 1);
+        }
+    }
+}
+
+/// Creates the `Document`, calls `app` to set up the page and runs the main loop until
+/// `MainLoop::quit`.
+///
+/// Like `keep_alive`, this returns from `main` without shutting down the runtime.
+pub fn run<F: FnOnce(&Document<'static>, &MainLoop)>(app: F) -> ! {
+    // Never freed: anything scheduled from `app` may refer to the document after `quit`.
+    let document: &'static Document<'static> = Box::leak(Box::new(init()));
+    let state = Rc::new(LoopState {
+        doc: document,
+        frame: RefCell::new(None),
+        frame_generation: Cell::new(0),
+        fps: Cell::new(0),
+        started: Cell::new(false),
+        quitting: Cell::new(false),
+    });
+    let main_loop = MainLoop { state: state.clone() };
+    app(document, &main_loop);
+    drop(main_loop);
+
+    let fps = state.fps.get();
+    state.started.set(true);
+    unsafe {
+        // The loop owns a reference until `rust_main_loop_quit` cancels it.
+        emscripten_set_main_loop_arg(rust_main_loop_frame, Rc::into_raw(state) as *mut libc::c_void, fps, 1);
+    }
+    unreachable!()
+}
+
+extern fn rust_main_loop_frame(arg: *mut libc::c_void) {
+    let state = unsafe { &*(arg as *const LoopState) };
+    let frame = state.frame.borrow_mut().take();
+    match frame {
+        Some(mut frame) => {
+            let generation = state.frame_generation.get();
+            frame();
+            if state.frame_generation.get() == generation {
+                *state.frame.borrow_mut() = Some(frame);
+            }
+        }
+        None => unsafe {
+            // Nothing to do on each frame; `set_frame` resumes the loop.
+            emscripten_pause_main_loop();
+        },
+    }
+}
+
+extern fn rust_main_loop_quit(arg: *const libc::c_void) {
+    let state = unsafe { Rc::from_raw(arg as *const LoopState) };
+    if state.started.get() {
+        unsafe {
+            emscripten_cancel_main_loop();
+            // Releases the reference held by the loop.
+            drop(Rc::from_raw(&*state as *const LoopState));
+        }
+    }
+    let frame = state.frame.borrow_mut().take();
+    drop(frame);
+    unsafe { &*state.doc }.release_all();
+}
@@ -0,0 +1,98 @@
+use std::cell::RefCell;
+use std::mem;
+use ::error::*;
+use ::native::*;
+use ::native::dom::*;
+use ::native::timer::Clock;
+use ::native::window::WindowState;
+
+
+pub fn init<'a>() -> Document<'a> {
+    Document {
+        listeners: RefCell::new(Vec::new()),
+        timers: RefCell::new(Vec::new()),
+        dom: RefCell::new(Dom::new()),
+        clock: RefCell::new(Clock::new()),
+        window: RefCell::new(WindowState::new()),
+    }
+}
+
+/// An in-memory document holding `<html>`, `<head>` and `<body>`.
+pub struct Document<'a> {
+    // Declared first, so that the forgotten handles are dropped while `dom` and `clock` are
+    // still there to remove them from.
+    listeners: RefCell<Vec<ListenerHandle<'a>>>,
+    timers: RefCell<Vec<TimerHandle<'a>>>,
+    pub(crate) dom: RefCell<Dom<'a>>,
+    pub(crate) clock: RefCell<Clock<'a>>,
+    pub(crate) window: RefCell<WindowState>,
+}
+
+impl<'a> Document<'a> {
+    pub fn window(&self) -> Window<'a> {
+        Window::new(&*self)
+    }
+
+    pub(crate) fn push_listener(&self, value: ListenerHandle<'a>) {
+        self.listeners.borrow_mut().push(value);
+    }
+
+    pub(crate) fn push_timer(&self, value: TimerHandle<'a>) {
+        self.timers.borrow_mut().push(value);
+    }
+
+    /// Removes a forgotten timer by its `TimerHandle::key`.
+    pub(crate) fn take_timer(&self, key: u32) -> Option<TimerHandle<'a>> {
+        let mut timers = self.timers.borrow_mut();
+        let index = timers.iter().position(|timer| timer.key() == key);
+        index.map(|index| timers.swap_remove(index))
+    }
+
+    /// Drops every forgotten listener and timer, which removes them. Called when the main loop
+    /// quits.
+    pub(crate) fn release_all(&self) {
+        let listeners = mem::take(&mut *self.listeners.borrow_mut());
+        let timers = mem::take(&mut *self.timers.borrow_mut());
+        drop(listeners);
+        drop(timers);
+    }
+
+    #[deprecated(note = "use `Window::websocket_create`")]
+    pub fn websocket_create<'b>(&'b self, url: &str) -> Option<WebSocket<'a>> {
+        self.window().websocket_create(url)
+    }
+
+    pub fn element_create<'b>(&'b self, s: &str) -> Option<HtmlNode<'a>> {
+        self.try_element_create(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_element_create<'b>(&'b self, s: &str) -> Result<Option<HtmlNode<'a>>, JsError> {
+        let id = self.dom.borrow_mut().create_element(s);
+        Ok(Some(HtmlNode::new(id, &*self)))
+    }
+
+    #[deprecated(note = "use `Window::location_hash_get`")]
+    pub fn location_hash_get(&self) -> String {
+        self.window().location_hash_get()
+    }
+
+    #[deprecated(note = "use `Window::on`")]
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.window().on(s, f)
+    }
+
+    pub fn element_query<'b>(&'b self, s: &str) -> Option<HtmlNode<'a>> {
+        self.try_element_query(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_element_query<'b>(&'b self, s: &str) -> Result<Option<HtmlNode<'a>>, JsError> {
+        let dom = self.dom.borrow();
+        let id = dom.query(dom.root, s)?;
+        Ok(id.map(|id| HtmlNode::new(id, &*self)))
+    }
+
+    /// The element `focus` was last called on.
+    pub fn active_element(&self) -> Option<HtmlNode<'a>> {
+        self.dom.borrow().focused.map(|id| HtmlNode::new(id, &*self))
+    }
+}
@@ -0,0 +1,708 @@
+use std::cell::RefCell;
+use std::char;
+use std::rc::Rc;
+use ::error::*;
+use ::native::*;
+
+
+pub(crate) type NodeId = usize;
+
+/// Listener target standing for `window`, above the root element.
+pub(crate) const WINDOW: NodeId = !0;
+
+const VOID_ELEMENTS: &'static [&'static str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr",
+];
+
+enum NodeKind {
+    Element { tag: String, attrs: Vec<(String, String)> },
+    Text(String),
+}
+
+struct Node {
+    kind: NodeKind,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    props: Vec<(String, String)>,
+}
+
+pub(crate) type Callback<'a> = Rc<RefCell<Box<FnMut(Event<'a>) + 'a>>>;
+
+struct Listener<'a> {
+    id: usize,
+    target: NodeId,
+    event: String,
+    capture: bool,
+    callback: Callback<'a>,
+}
+
+/// What sockets and the window deliver to listeners that do not take an `Event`.
+pub(crate) enum Signal {
+    Open,
+    Text(String),
+    Binary(Vec<u8>),
+    Close(CloseEvent),
+    Error,
+    Storage(StorageEvent),
+}
+
+pub(crate) type SignalCallback<'a> = Rc<RefCell<Box<FnMut(&Signal) + 'a>>>;
+
+/// A listener on a socket, by index into `Dom::sockets`, or on `WINDOW`.
+struct SignalListener<'a> {
+    id: usize,
+    source: usize,
+    callback: SignalCallback<'a>,
+}
+
+/// The element tree and the listeners of a native `Document`. Nodes are never freed; removed
+/// nodes are only detached.
+pub(crate) struct Dom<'a> {
+    nodes: Vec<Node>,
+    pub(crate) root: NodeId,
+    pub(crate) focused: Option<NodeId>,
+    pub(crate) sockets: Vec<Socket>,
+    listeners: Vec<Listener<'a>>,
+    signal_listeners: Vec<SignalListener<'a>>,
+    next_listener: usize,
+}
+
+impl<'a> Dom<'a> {
+    pub(crate) fn new() -> Self {
+        let mut dom = Dom {
+            nodes: Vec::new(),
+            root: 0,
+            focused: None,
+            sockets: Vec::new(),
+            listeners: Vec::new(),
+            signal_listeners: Vec::new(),
+            next_listener: 0,
+        };
+        let root = dom.create_element("html");
+        let head = dom.create_element("head");
+        let body = dom.create_element("body");
+        dom.append(root, head);
+        dom.append(root, body);
+        dom.root = root;
+        dom
+    }
+
+    fn push(&mut self, kind: NodeKind) -> NodeId {
+        self.nodes.push(Node { kind, parent: None, children: Vec::new(), props: Vec::new() });
+        self.nodes.len() - 1
+    }
+
+    pub(crate) fn create_element(&mut self, tag: &str) -> NodeId {
+        self.push(NodeKind::Element { tag: tag.to_lowercase(), attrs: Vec::new() })
+    }
+
+    fn create_text(&mut self, text: &str) -> NodeId {
+        self.push(NodeKind::Text(text.to_string()))
+    }
+
+    pub(crate) fn tag(&self, node: NodeId) -> &str {
+        match self.nodes[node].kind {
+            NodeKind::Element { ref tag, .. } => tag,
+            NodeKind::Text(_) => "#text",
+        }
+    }
+
+    pub(crate) fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.nodes[node].parent
+    }
+
+    /// The ancestors of `node`, nearest first.
+    pub(crate) fn ancestors(&self, node: NodeId) -> Vec<NodeId> {
+        let mut ancestors = Vec::new();
+        let mut current = self.nodes[node].parent;
+        while let Some(parent) = current {
+            ancestors.push(parent);
+            current = self.nodes[parent].parent;
+        }
+        ancestors
+    }
+
+    pub(crate) fn detach(&mut self, node: NodeId) {
+        if let Some(parent) = self.nodes[node].parent.take() {
+            self.nodes[parent].children.retain(|&child| child != node);
+        }
+    }
+
+    pub(crate) fn append(&mut self, parent: NodeId, child: NodeId) {
+        self.detach(child);
+        self.nodes[child].parent = Some(parent);
+        self.nodes[parent].children.push(child);
+    }
+
+    fn insert(&mut self, parent: NodeId, index: usize, child: NodeId) {
+        self.detach(child);
+        self.nodes[child].parent = Some(parent);
+        self.nodes[parent].children.insert(index, child);
+    }
+
+    // Attributes and properties
+
+    pub(crate) fn attr(&self, node: NodeId, name: &str) -> Option<&str> {
+        match self.nodes[node].kind {
+            NodeKind::Element { ref attrs, .. } => {
+                attrs.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| &v[..])
+            }
+            NodeKind::Text(_) => None,
+        }
+    }
+
+    pub(crate) fn set_attr(&mut self, node: NodeId, name: &str, value: &str) {
+        if let NodeKind::Element { ref mut attrs, .. } = self.nodes[node].kind {
+            let name = name.to_lowercase();
+            match attrs.iter_mut().find(|&&mut (ref n, _)| *n == name) {
+                Some(attr) => attr.1 = value.to_string(),
+                None => attrs.push((name, value.to_string())),
+            }
+        }
+    }
+
+    pub(crate) fn remove_attr(&mut self, node: NodeId, name: &str) {
+        if let NodeKind::Element { ref mut attrs, .. } = self.nodes[node].kind {
+            attrs.retain(|&(ref n, _)| n != name);
+        }
+    }
+
+    pub(crate) fn prop(&self, node: NodeId, name: &str) -> Option<&str> {
+        self.nodes[node].props.iter().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| &v[..])
+    }
+
+    pub(crate) fn set_prop(&mut self, node: NodeId, name: &str, value: &str) {
+        let props = &mut self.nodes[node].props;
+        match props.iter_mut().find(|&&mut (ref n, _)| n == name) {
+            Some(prop) => prop.1 = value.to_string(),
+            None => props.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    // Markup
+
+    pub(crate) fn inner_html(&self, node: NodeId) -> String {
+        let mut out = String::new();
+        for &child in &self.nodes[node].children {
+            self.serialize(child, &mut out);
+        }
+        out
+    }
+
+    pub(crate) fn text_content(&self, node: NodeId) -> String {
+        match self.nodes[node].kind {
+            NodeKind::Text(ref text) => text.clone(),
+            NodeKind::Element { .. } => {
+                self.nodes[node].children.iter().map(|&child| self.text_content(child)).collect()
+            }
+        }
+    }
+
+    fn serialize(&self, node: NodeId, out: &mut String) {
+        match self.nodes[node].kind {
+            NodeKind::Text(ref text) => out.push_str(&escape(text, false)),
+            NodeKind::Element { ref tag, ref attrs } => {
+                out.push('<');
+                out.push_str(tag);
+                for &(ref name, ref value) in attrs {
+                    out.push_str(&format!(" {}=\"{}\"", name, escape(value, true)));
+                }
+                out.push('>');
+                if VOID_ELEMENTS.contains(&&tag[..]) {
+                    return;
+                }
+                for &child in &self.nodes[node].children {
+                    self.serialize(child, out);
+                }
+                out.push_str(&format!("</{}>", tag));
+            }
+        }
+    }
+
+    pub(crate) fn set_inner_html(&mut self, node: NodeId, html: &str) {
+        for child in self.nodes[node].children.clone() {
+            self.detach(child);
+        }
+        self.insert_html(node, html, false);
+    }
+
+    /// Parses `html` and inserts it at the start or the end of the children of `node`.
+    pub(crate) fn insert_html(&mut self, node: NodeId, html: &str, at_start: bool) {
+        let fragment = self.create_element("#fragment");
+        self.parse_into(fragment, html);
+        let children = self.nodes[fragment].children.clone();
+        for (i, child) in children.into_iter().enumerate() {
+            if at_start {
+                self.insert(node, i, child);
+            } else {
+                self.append(node, child);
+            }
+        }
+    }
+
+    /// A forgiving HTML parser: tags, attributes, text, comments and void elements. Unknown end
+    /// tags are ignored and unclosed elements are closed at the end.
+    fn parse_into(&mut self, container: NodeId, html: &str) {
+        let mut stack = vec![container];
+        let mut rest = html;
+        while !rest.is_empty() {
+            let parent = *stack.last().unwrap();
+            if rest.starts_with("<!--") {
+                rest = match rest.find("-->") {
+                    Some(end) => &rest[end + 3..],
+                    None => "",
+                };
+            } else if rest.starts_with("</") {
+                let end = match rest.find('>') {
+                    Some(end) => end,
+                    None => break,
+                };
+                let name = rest[2..end].trim().to_lowercase();
+                if let Some(pos) = stack.iter().rposition(|&n| n != container && self.tag(n) == name) {
+                    stack.truncate(pos);
+                }
+                rest = &rest[end + 1..];
+            } else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+                let (tag, attrs, self_closing, remaining) = parse_start_tag(&rest[1..]);
+                let element = self.create_element(&tag);
+                for (name, value) in attrs {
+                    self.set_attr(element, &name, &value);
+                }
+                self.append(parent, element);
+                if !self_closing && !VOID_ELEMENTS.contains(&&tag[..]) {
+                    stack.push(element);
+                }
+                rest = remaining;
+            } else {
+                let first = rest.chars().next().unwrap().len_utf8();
+                let end = rest[first..].find('<').map(|i| i + first).unwrap_or(rest.len());
+                let text = self.create_text(&unescape(&rest[..end]));
+                self.append(parent, text);
+                rest = &rest[end..];
+            }
+        }
+    }
+
+    // Selectors
+
+    /// The first element under `scope` in document order matching `selector`.
+    pub(crate) fn query(&self, scope: NodeId, selector: &str) -> Result<Option<NodeId>, JsError> {
+        let selector = parse_selector(selector)?;
+        let mut pending = vec![scope];
+        while let Some(node) = pending.pop() {
+            if self.is_element(node) && selector.iter().any(|complex| self.matches(node, complex)) {
+                return Ok(Some(node));
+            }
+            pending.extend(self.nodes[node].children.iter().rev());
+        }
+        Ok(None)
+    }
+
+    fn is_element(&self, node: NodeId) -> bool {
+        match self.nodes[node].kind {
+            NodeKind::Element { ref tag, .. } => tag != "#fragment",
+            NodeKind::Text(_) => false,
+        }
+    }
+
+    fn element_siblings(&self, node: NodeId) -> Vec<NodeId> {
+        match self.nodes[node].parent {
+            Some(parent) => self.nodes[parent].children.iter().cloned().filter(|&n| self.is_element(n)).collect(),
+            None => vec![node],
+        }
+    }
+
+    fn matches(&self, node: NodeId, complex: &[(Combinator, Compound)]) -> bool {
+        let (combinator, compound) = match complex.last() {
+            Some(&(combinator, ref compound)) => (combinator, compound),
+            None => return true,
+        };
+        if !self.is_element(node) || !self.matches_compound(node, compound) {
+            return false;
+        }
+        let rest = &complex[..complex.len() - 1];
+        if rest.is_empty() {
+            return true;
+        }
+        match combinator {
+            Combinator::Descendant => self.ancestors(node).into_iter().any(|a| self.matches(a, rest)),
+            Combinator::Child => self.parent(node).map_or(false, |p| self.matches(p, rest)),
+            Combinator::Adjacent | Combinator::Sibling => {
+                let siblings = self.element_siblings(node);
+                let index = siblings.iter().position(|&n| n == node).unwrap_or(0);
+                let before = &siblings[..index];
+                if combinator == Combinator::Adjacent {
+                    before.last().map_or(false, |&n| self.matches(n, rest))
+                } else {
+                    before.iter().any(|&n| self.matches(n, rest))
+                }
+            }
+        }
+    }
+
+    fn matches_compound(&self, node: NodeId, compound: &Compound) -> bool {
+        if let Some(ref tag) = compound.tag {
+            if self.tag(node) != tag {
+                return false;
+            }
+        }
+        if let Some(ref id) = compound.id {
+            if self.attr(node, "id") != Some(&id[..]) {
+                return false;
+            }
+        }
+        let classes = self.attr(node, "class").unwrap_or("");
+        if !compound.classes.iter().all(|c| classes.split_whitespace().any(|k| k == c)) {
+            return false;
+        }
+        for &(ref name, ref op, ref expected) in &compound.attrs {
+            let value = match self.attr(node, name) {
+                Some(value) => value,
+                None => return false,
+            };
+            let ok = match &op[..] {
+                "" => true,
+                "=" => value == expected,
+                "~=" => value.split_whitespace().any(|v| v == expected),
+                "^=" => !expected.is_empty() && value.starts_with(&expected[..]),
+                "$=" => !expected.is_empty() && value.ends_with(&expected[..]),
+                "*=" => !expected.is_empty() && value.contains(&expected[..]),
+                _ => false,
+            };
+            if !ok {
+                return false;
+            }
+        }
+        for pseudo in &compound.pseudo {
+            let siblings = self.element_siblings(node);
+            let ok = match &pseudo[..] {
+                "first-child" => siblings.first() == Some(&node),
+                "last-child" => siblings.last() == Some(&node),
+                _ => false,
+            };
+            if !ok {
+                return false;
+            }
+        }
+        true
+    }
+
+    // Listeners
+
+    pub(crate) fn add_listener(&mut self, target: NodeId, event: &str, capture: bool, callback: Box<FnMut(Event<'a>) + 'a>) -> usize {
+        let id = self.next_listener();
+        self.listeners.push(Listener {
+            id,
+            target,
+            event: event.to_string(),
+            capture,
+            callback: Rc::new(RefCell::new(callback)),
+        });
+        id
+    }
+
+    pub(crate) fn add_signal_listener(&mut self, source: usize, callback: Box<FnMut(&Signal) + 'a>) -> usize {
+        let id = self.next_listener();
+        self.signal_listeners.push(SignalListener {
+            id,
+            source,
+            callback: Rc::new(RefCell::new(callback)),
+        });
+        id
+    }
+
+    fn next_listener(&mut self) -> usize {
+        let id = self.next_listener;
+        self.next_listener += 1;
+        id
+    }
+
+    pub(crate) fn remove_listener(&mut self, id: usize) {
+        self.listeners.retain(|l| l.id != id);
+        self.signal_listeners.retain(|l| l.id != id);
+    }
+
+    pub(crate) fn has_listener(&self, id: usize) -> bool {
+        self.listeners.iter().any(|l| l.id == id) || self.signal_listeners.iter().any(|l| l.id == id)
+    }
+
+    /// The listeners for `event` on `target`: capturing ones, non-capturing ones, or both.
+    pub(crate) fn listeners_for(&self, target: NodeId, event: &str, capture: Option<bool>) -> Vec<(usize, Callback<'a>)> {
+        self.listeners.iter()
+            .filter(|l| l.target == target && l.event == event && capture.map_or(true, |c| c == l.capture))
+            .map(|l| (l.id, l.callback.clone()))
+            .collect()
+    }
+
+    pub(crate) fn signal_listeners_for(&self, source: usize) -> Vec<(usize, SignalCallback<'a>)> {
+        self.signal_listeners.iter()
+            .filter(|l| l.source == source)
+            .map(|l| (l.id, l.callback.clone()))
+            .collect()
+    }
+}
+
+/// Parses a start tag after the `<`. Returns the tag, the attributes, whether it was
+/// self-closing and the rest of the input.
+fn parse_start_tag(s: &str) -> (String, Vec<(String, String)>, bool, &str) {
+    let name_end = s.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(s.len());
+    let tag = s[..name_end].to_lowercase();
+    let mut attrs = Vec::new();
+    let mut rest = &s[name_end..];
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return (tag, attrs, false, rest);
+        }
+        if rest.starts_with("/>") {
+            return (tag, attrs, true, &rest[2..]);
+        }
+        if rest.starts_with('>') {
+            return (tag, attrs, false, &rest[1..]);
+        }
+        if rest.starts_with('/') {
+            rest = &rest[1..];
+            continue;
+        }
+        let end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/').unwrap_or(rest.len());
+        let name = rest[..end].to_lowercase();
+        rest = rest[end..].trim_start();
+        let mut value = String::new();
+        if rest.starts_with('=') {
+            rest = rest[1..].trim_start();
+            if rest.starts_with('"') || rest.starts_with('\'') {
+                let quote = &rest[..1];
+                let end = rest[1..].find(quote).map(|i| i + 1).unwrap_or(rest.len());
+                value = unescape(&rest[1..end]);
+                rest = if end < rest.len() { &rest[end + 1..] } else { "" };
+            } else {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(rest.len());
+                value = unescape(&rest[..end]);
+                rest = &rest[end..];
+            }
+        }
+        if !name.is_empty() {
+            attrs.push((name, value));
+        }
+    }
+}
+
+fn escape(s: &str, attr: bool) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '"' if attr => out.push_str("&quot;"),
+            '<' if !attr => out.push_str("&lt;"),
+            '>' if !attr => out.push_str("&gt;"),
+            '\u{a0}' => out.push_str("&nbsp;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32)
+                }
+                _ if entity.starts_with('#') => entity[1..].parse().ok().and_then(char::from_u32),
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// =================================================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Combinator {
+    Descendant,
+    Child,
+    Adjacent,
+    Sibling,
+}
+
+#[derive(Debug, Default)]
+struct Compound {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+    attrs: Vec<(String, String, String)>,
+    pseudo: Vec<String>,
+}
+
+fn selector_error(selector: &str) -> JsError {
+    JsError {
+        name: "SyntaxError".to_string(),
+        message: format!("'{}' is not a valid selector", selector),
+        stack: None,
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii()
+}
+
+fn take_ident(s: &str) -> (&str, &str) {
+    let end = s.find(|c: char| !is_ident_char(c)).unwrap_or(s.len());
+    (&s[..end], &s[end..])
+}
+
+/// Parses a selector list of compound selectors joined by descendant, `>`, `+` or `~`
+/// combinators. Supports type, universal, id, class and attribute selectors, and the
+/// `:first-child` and `:last-child` pseudo-classes.
+fn parse_selector(selector: &str) -> Result<Vec<Vec<(Combinator, Compound)>>, JsError> {
+    let mut list = Vec::new();
+    for part in selector.split(',') {
+        let mut complex = Vec::new();
+        let mut combinator = Combinator::Descendant;
+        let mut rest = part.trim();
+        if rest.is_empty() {
+            return Err(selector_error(selector));
+        }
+        while !rest.is_empty() {
+            let mut compound = Compound::default();
+            let mut empty = true;
+            if rest.starts_with('*') {
+                rest = &rest[1..];
+                empty = false;
+            } else {
+                let (tag, r) = take_ident(rest);
+                if !tag.is_empty() {
+                    compound.tag = Some(tag.to_lowercase());
+                    rest = r;
+                    empty = false;
+                }
+            }
+            loop {
+                if rest.starts_with('#') || rest.starts_with('.') || rest.starts_with(':') {
+                    let (ident, r) = take_ident(&rest[1..]);
+                    if ident.is_empty() {
+                        return Err(selector_error(selector));
+                    }
+                    match &rest[..1] {
+                        "#" => compound.id = Some(ident.to_string()),
+                        "." => compound.classes.push(ident.to_string()),
+                        _ => {
+                            if ident != "first-child" && ident != "last-child" {
+                                return Err(JsError {
+                                    name: "NotSupportedError".to_string(),
+                                    message: format!("':{}' is not supported by the native backend", ident),
+                                    stack: None,
+                                });
+                            }
+                            compound.pseudo.push(ident.to_string());
+                        }
+                    }
+                    rest = r;
+                } else if rest.starts_with('[') {
+                    let end = rest.find(']').ok_or_else(|| selector_error(selector))?;
+                    let inner = &rest[1..end];
+                    let (name, after) = take_ident(inner.trim());
+                    if name.is_empty() {
+                        return Err(selector_error(selector));
+                    }
+                    let after = after.trim();
+                    let (op, value) = match after.find('=') {
+                        Some(i) => (&after[..i + 1], after[i + 1..].trim()),
+                        None if after.is_empty() => ("", ""),
+                        None => return Err(selector_error(selector)),
+                    };
+                    let value = value.trim_matches(|c| c == '"' || c == '\'');
+                    compound.attrs.push((name.to_lowercase(), op.to_string(), value.to_string()));
+                    rest = &rest[end + 1..];
+                } else {
+                    break;
+                }
+                empty = false;
+            }
+            if empty {
+                return Err(selector_error(selector));
+            }
+            complex.push((combinator, compound));
+
+            let trimmed = rest.trim_start();
+            combinator = if trimmed.starts_with('>') {
+                Combinator::Child
+            } else if trimmed.starts_with('+') {
+                Combinator::Adjacent
+            } else if trimmed.starts_with('~') {
+                Combinator::Sibling
+            } else if trimmed.len() < rest.len() {
+                Combinator::Descendant
+            } else if trimmed.is_empty() {
+                break;
+            } else {
+                return Err(selector_error(selector));
+            };
+            rest = if combinator == Combinator::Descendant { trimmed } else { trimmed[1..].trim_start() };
+            if rest.is_empty() {
+                return Err(selector_error(selector));
+            }
+        }
+        list.push(complex);
+    }
+    Ok(list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dom_with(html: &str) -> Dom<'static> {
+        let mut dom = Dom::new();
+        let body = dom.query(dom.root, "body").unwrap().unwrap();
+        dom.set_inner_html(body, html);
+        dom
+    }
+
+    #[test]
+    fn test_parse_serialize() {
+        let html = r#"<div class="a" data-x="1 &amp; 2"><input type="text"><p>a &lt; b</p></div>text"#;
+        let dom = dom_with(html);
+        let body = dom.query(dom.root, "body").unwrap().unwrap();
+        assert_eq!(dom.inner_html(body), html);
+        assert_eq!(dom.text_content(body), "a < btext");
+    }
+
+    #[test]
+    fn test_selectors() {
+        let dom = dom_with(r#"<ul id="list"><li class="a b">1</li><li title="x y">2</li></ul>"#);
+        let text = |s: &str| dom.query(dom.root, s).unwrap().map(|n| dom.text_content(n));
+        assert_eq!(text("#list > li.b"), Some("1".to_string()));
+        assert_eq!(text("ul li:last-child"), Some("2".to_string()));
+        assert_eq!(text("li + li"), Some("2".to_string()));
+        assert_eq!(text("[title~=y]"), Some("2".to_string()));
+        assert_eq!(text("body > li"), None);
+        assert_eq!(dom.query(dom.root, "#").unwrap_err().name, "SyntaxError");
+        assert_eq!(dom.query(dom.root, "li >").unwrap_err().name, "SyntaxError");
+    }
+}
@@ -0,0 +1,334 @@
+use std::cell::Cell;
+use std::ops::Deref;
+use std::rc::Rc;
+use ::native::*;
+use ::native::dom::*;
+
+
+/// Mouse fields of a synthetic event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MouseInit {
+    pub client_x: i32,
+    pub client_y: i32,
+    pub page_x: i32,
+    pub page_y: i32,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub button: i32,
+    pub buttons: i32,
+    pub alt_key: bool,
+    pub ctrl_key: bool,
+    pub shift_key: bool,
+    pub meta_key: bool,
+}
+
+/// Keyboard fields of a synthetic event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyboardInit {
+    pub key: String,
+    pub code: String,
+    pub location: i32,
+    pub repeat: bool,
+    pub is_composing: bool,
+    pub alt_key: bool,
+    pub ctrl_key: bool,
+    pub shift_key: bool,
+    pub meta_key: bool,
+}
+
+/// Describes a synthetic event for `HtmlNode::dispatch` and `Window::dispatch`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventInit {
+    pub bubbles: bool,
+    pub cancelable: bool,
+    pub mouse: Option<MouseInit>,
+    pub keyboard: Option<KeyboardInit>,
+}
+
+impl EventInit {
+    /// A bubbling, cancelable mouse event, as dispatched by `HtmlNode::click`.
+    pub fn mouse(init: MouseInit) -> Self {
+        EventInit { bubbles: true, cancelable: true, mouse: Some(init), keyboard: None }
+    }
+
+    /// A bubbling, cancelable keyboard event.
+    pub fn keyboard(init: KeyboardInit) -> Self {
+        EventInit { bubbles: true, cancelable: true, mouse: None, keyboard: Some(init) }
+    }
+}
+
+struct EventState {
+    init: EventInit,
+    default_prevented: Cell<bool>,
+    propagation_stopped: Cell<bool>,
+    immediate_propagation_stopped: Cell<bool>,
+}
+
+pub struct Event<'a> {
+    pub target: Option<HtmlNode<'a>>,
+    state: Rc<EventState>,
+}
+
+impl<'a> Event<'a> {
+    /// Mouse-specific fields, if this is a mouse event.
+    pub fn mouse<'e>(&'e self) -> Option<MouseEvent<'e, 'a>> {
+        if self.state.init.mouse.is_some() {
+            Some(MouseEvent { event: self })
+        } else {
+            None
+        }
+    }
+
+    /// Keyboard-specific fields, if this is a keyboard event.
+    pub fn keyboard<'e>(&'e self) -> Option<KeyboardEvent<'e, 'a>> {
+        if self.state.init.keyboard.is_some() {
+            Some(KeyboardEvent { event: self })
+        } else {
+            None
+        }
+    }
+
+    pub fn prevent_default(&self) {
+        if self.state.init.cancelable {
+            self.state.default_prevented.set(true);
+        }
+    }
+
+    pub fn stop_propagation(&self) {
+        self.state.propagation_stopped.set(true);
+    }
+
+    pub fn stop_immediate_propagation(&self) {
+        self.state.propagation_stopped.set(true);
+        self.state.immediate_propagation_stopped.set(true);
+    }
+
+    pub fn default_prevented(&self) -> bool {
+        self.state.default_prevented.get()
+    }
+
+    pub fn cancelable(&self) -> bool {
+        self.state.init.cancelable
+    }
+}
+
+/// Runs the capture, target and bubble phases of `event` on `target` and returns `false` if
+/// a listener called `prevent_default`, like `dispatchEvent`.
+pub(crate) fn dispatch<'a>(doc: *const Document<'a>, target: NodeId, event: &str, init: EventInit) -> bool {
+    let document = unsafe { &*doc };
+    let state = Rc::new(EventState {
+        init,
+        default_prevented: Cell::new(false),
+        propagation_stopped: Cell::new(false),
+        immediate_propagation_stopped: Cell::new(false),
+    });
+    let mut path = if target == WINDOW { Vec::new() } else { document.dom.borrow().ancestors(target) };
+    if target != WINDOW {
+        path.push(WINDOW);
+    }
+
+    let invoke = |node: NodeId, capture: Option<bool>| {
+        if state.propagation_stopped.get() {
+            return;
+        }
+        let listeners = document.dom.borrow().listeners_for(node, event, capture);
+        for (id, callback) in listeners {
+            if state.immediate_propagation_stopped.get() {
+                return;
+            }
+            // Skips listeners removed by an earlier one, and re-entrant dispatch to a running one.
+            if !document.dom.borrow().has_listener(id) {
+                continue;
+            }
+            if let Ok(mut callback) = callback.try_borrow_mut() {
+                (&mut *callback)(Event {
+                    target: if target == WINDOW { None } else { Some(HtmlNode::new(target, doc)) },
+                    state: state.clone(),
+                });
+            }
+        }
+    };
+
+    for &node in path.iter().rev() {
+        invoke(node, Some(true));
+    }
+    invoke(target, None);
+    if state.init.bubbles {
+        for &node in &path {
+            invoke(node, Some(false));
+        }
+    }
+    !state.default_prevented.get()
+}
+
+/// Calls the listeners of a socket, or of `WINDOW`, with `signal`.
+pub(crate) fn emit<'a>(doc: *const Document<'a>, source: usize, signal: &Signal) {
+    let document = unsafe { &*doc };
+    let listeners = document.dom.borrow().signal_listeners_for(source);
+    for (id, callback) in listeners {
+        if !document.dom.borrow().has_listener(id) {
+            continue;
+        }
+        if let Ok(mut callback) = callback.try_borrow_mut() {
+            (&mut *callback)(signal);
+        }
+    }
+}
+
+/// Mouse-specific view of an `Event`.
+pub struct MouseEvent<'e, 'a: 'e> {
+    event: &'e Event<'a>,
+}
+
+impl<'e, 'a> Deref for MouseEvent<'e, 'a> {
+    type Target = Event<'a>;
+
+    fn deref(&self) -> &Event<'a> {
+        self.event
+    }
+}
+
+impl<'e, 'a> MouseEvent<'e, 'a> {
+    fn init(&self) -> &MouseInit {
+        self.event.state.init.mouse.as_ref().unwrap()
+    }
+
+    pub fn client_x(&self) -> i32 {
+        self.init().client_x
+    }
+
+    pub fn client_y(&self) -> i32 {
+        self.init().client_y
+    }
+
+    pub fn page_x(&self) -> i32 {
+        self.init().page_x
+    }
+
+    pub fn page_y(&self) -> i32 {
+        self.init().page_y
+    }
+
+    pub fn offset_x(&self) -> i32 {
+        self.init().offset_x
+    }
+
+    pub fn offset_y(&self) -> i32 {
+        self.init().offset_y
+    }
+
+    pub fn button(&self) -> i32 {
+        self.init().button
+    }
+
+    pub fn buttons(&self) -> i32 {
+        self.init().buttons
+    }
+
+    pub fn alt_key(&self) -> bool {
+        self.init().alt_key
+    }
+
+    pub fn ctrl_key(&self) -> bool {
+        self.init().ctrl_key
+    }
+
+    pub fn shift_key(&self) -> bool {
+        self.init().shift_key
+    }
+
+    pub fn meta_key(&self) -> bool {
+        self.init().meta_key
+    }
+
+    /// Always `None`: synthetic events have no related target.
+    pub fn related_target(&self) -> Option<HtmlNode<'a>> {
+        None
+    }
+}
+
+/// Keyboard-specific view of an `Event`.
+pub struct KeyboardEvent<'e, 'a: 'e> {
+    event: &'e Event<'a>,
+}
+
+impl<'e, 'a> Deref for KeyboardEvent<'e, 'a> {
+    type Target = Event<'a>;
+
+    fn deref(&self) -> &Event<'a> {
+        self.event
+    }
+}
+
+impl<'e, 'a> KeyboardEvent<'e, 'a> {
+    fn init(&self) -> &KeyboardInit {
+        self.event.state.init.keyboard.as_ref().unwrap()
+    }
+
+    pub fn key(&self) -> String {
+        self.init().key.clone()
+    }
+
+    pub fn code(&self) -> String {
+        self.init().code.clone()
+    }
+
+    pub fn location(&self) -> i32 {
+        self.init().location
+    }
+
+    pub fn repeat(&self) -> bool {
+        self.init().repeat
+    }
+
+    pub fn is_composing(&self) -> bool {
+        self.init().is_composing
+    }
+
+    pub fn alt_key(&self) -> bool {
+        self.init().alt_key
+    }
+
+    pub fn ctrl_key(&self) -> bool {
+        self.init().ctrl_key
+    }
+
+    pub fn shift_key(&self) -> bool {
+        self.init().shift_key
+    }
+
+    pub fn meta_key(&self) -> bool {
+        self.init().meta_key
+    }
+}
+
+/// Keeps an event listener registered. Dropping the handle removes the listener.
+#[must_use = "the listener is removed as soon as the handle is dropped; use `forget` to keep it"]
+pub struct ListenerHandle<'a> {
+    id: usize,
+    doc: *const Document<'a>,
+}
+
+impl<'a> ListenerHandle<'a> {
+    pub(crate) fn new(id: usize, doc: *const Document<'a>) -> Self {
+        ListenerHandle { id, doc }
+    }
+
+    /// Removes the listener and frees its closure.
+    pub fn remove(self) {}
+
+    /// Keeps the listener for as long as the `Document` lives.
+    pub fn forget(self) {
+        unsafe {
+            (&*self.doc).push_listener(self);
+        }
+    }
+}
+
+impl<'a> Drop for ListenerHandle<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            (&*self.doc).dom.borrow_mut().remove_listener(self.id);
+        }
+    }
+}
@@ -0,0 +1,596 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Deref;
+use ::error::*;
+use ::executor::*;
+use ::native::*;
+use ::native::dom::*;
+
+
+pub struct JSRef<'a> {
+    ptr: *const HtmlNode<'a>,
+}
+
+impl<'a> fmt::Debug for JSRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "JSRef")
+    }
+}
+
+impl<'a> Clone for JSRef<'a> {
+    fn clone(&self) -> JSRef<'a> {
+        JSRef {
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<'a> Deref for JSRef<'a> {
+    type Target = HtmlNode<'a>;
+
+    fn deref(&self) -> &HtmlNode<'a> {
+        unsafe {
+            &*self.ptr
+        }
+    }
+}
+
+/// An element of a native `Document`. Clones refer to the same element.
+#[derive(Clone)]
+pub struct HtmlNode<'a> {
+    pub(crate) id: NodeId,
+    doc: *const Document<'a>,
+}
+
+impl<'a> fmt::Debug for HtmlNode<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "HtmlNode({})", self.id)
+    }
+}
+
+/// Element properties backed by an attribute.
+fn reflected(prop: &str) -> Option<&'static str> {
+    match prop {
+        "id" => Some("id"),
+        "className" => Some("class"),
+        "title" => Some("title"),
+        "name" => Some("name"),
+        "type" => Some("type"),
+        "href" => Some("href"),
+        "src" => Some("src"),
+        "alt" => Some("alt"),
+        "placeholder" => Some("placeholder"),
+        "bgColor" => Some("bgcolor"),
+        _ => None,
+    }
+}
+
+/// Boolean element properties backed by the presence of an attribute.
+fn reflected_bool(prop: &str) -> Option<&'static str> {
+    match prop {
+        "hidden" => Some("hidden"),
+        "disabled" => Some("disabled"),
+        "checked" => Some("checked"),
+        "selected" => Some("selected"),
+        "readOnly" => Some("readonly"),
+        "required" => Some("required"),
+        "multiple" => Some("multiple"),
+        _ => None,
+    }
+}
+
+/// `backgroundColor` to `background-color`, as `dataset` and `style` map names.
+fn kebab(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_uppercase() {
+            out.push('-');
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+fn parse_style(style: &str) -> Vec<(String, String)> {
+    style.split(';')
+        .filter_map(|decl| {
+            let mut parts = decl.splitn(2, ':');
+            match (parts.next(), parts.next()) {
+                (Some(name), Some(value)) => Some((name.trim().to_string(), value.trim().to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+impl<'a> HtmlNode<'a> {
+    pub(crate) fn new(id: NodeId, doc: *const Document<'a>) -> Self {
+        HtmlNode { id, doc }
+    }
+
+    pub fn root_ref(&self) -> JSRef<'a> {
+        JSRef {
+            ptr: &*self,
+        }
+    }
+
+    fn document(&self) -> &Document<'a> {
+        unsafe { &*self.doc }
+    }
+}
+
+impl<'a> HtmlNode<'a> {
+    pub fn tagname(&self) -> String {
+        self.try_tagname().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_tagname(&self) -> Result<String, JsError> {
+        Ok(self.document().dom.borrow().tag(self.id).to_string())
+    }
+
+    /// Makes the element the `Document::active_element` and dispatches `focus` to it.
+    pub fn focus(&self) {
+        self.try_focus().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_focus(&self) -> Result<(), JsError> {
+        self.document().dom.borrow_mut().focused = Some(self.id);
+        self.dispatch("focus", EventInit::default());
+        Ok(())
+    }
+
+    pub fn html_set(&self, s: &str) {
+        self.try_html_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_set(&self, s: &str) -> Result<(), JsError> {
+        self.document().dom.borrow_mut().set_inner_html(self.id, s);
+        Ok(())
+    }
+
+    pub fn html_get(&self) -> String {
+        self.try_html_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_get(&self) -> Result<String, JsError> {
+        Ok(self.document().dom.borrow().inner_html(self.id))
+    }
+
+    pub fn class_get(&self) -> HashSet<String> {
+        self.try_class_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_get(&self) -> Result<HashSet<String>, JsError> {
+        let dom = self.document().dom.borrow();
+        let class = dom.attr(self.id, "class").unwrap_or("");
+        Ok(class.split_whitespace().map(|x| x.to_string()).collect())
+    }
+
+    fn set_classes(&self, classes: Vec<String>) {
+        self.document().dom.borrow_mut().set_attr(self.id, "class", &classes.join(" "));
+    }
+
+    fn classes(&self) -> Vec<String> {
+        let dom = self.document().dom.borrow();
+        dom.attr(self.id, "class").unwrap_or("").split_whitespace().map(|x| x.to_string()).collect()
+    }
+
+    pub fn class_add(&self, s: &str) {
+        self.try_class_add(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_add(&self, s: &str) -> Result<(), JsError> {
+        let mut classes = self.classes();
+        if !classes.iter().any(|c| c == s) {
+            classes.push(s.to_string());
+        }
+        self.set_classes(classes);
+        Ok(())
+    }
+
+    pub fn class_toggle(&self, s: &str) {
+        self.try_class_toggle(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_toggle(&self, s: &str) -> Result<(), JsError> {
+        if self.classes().iter().any(|c| c == s) {
+            self.try_class_remove(s)
+        } else {
+            self.try_class_add(s)
+        }
+    }
+
+    pub fn class_remove(&self, s: &str) {
+        self.try_class_remove(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_class_remove(&self, s: &str) -> Result<(), JsError> {
+        let classes = self.classes().into_iter().filter(|c| c != s).collect();
+        self.set_classes(classes);
+        Ok(())
+    }
+
+    pub fn parent(&self) -> Option<HtmlNode<'a>> {
+        self.try_parent().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_parent(&self) -> Result<Option<HtmlNode<'a>>, JsError> {
+        let parent = self.document().dom.borrow().parent(self.id);
+        Ok(parent.map(|id| HtmlNode::new(id, self.doc)))
+    }
+
+    pub fn data_set(&self, s: &str, v: &str) {
+        self.try_data_set(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_data_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        self.document().dom.borrow_mut().set_attr(self.id, &format!("data-{}", kebab(s)), v);
+        Ok(())
+    }
+
+    pub fn data_get(&self, s: &str) -> Option<String> {
+        self.try_data_get(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_data_get(&self, s: &str) -> Result<Option<String>, JsError> {
+        let dom = self.document().dom.borrow();
+        Ok(dom.attr(self.id, &format!("data-{}", kebab(s))).map(|v| v.to_string()))
+    }
+
+    pub fn style_set_str(&self, s: &str, v: &str) {
+        self.try_style_set_str(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    /// Updates the `style` attribute; an empty value removes the declaration.
+    pub fn try_style_set_str(&self, s: &str, v: &str) -> Result<(), JsError> {
+        let mut dom = self.document().dom.borrow_mut();
+        let name = kebab(s);
+        let mut style = parse_style(dom.attr(self.id, "style").unwrap_or(""));
+        style.retain(|&(ref n, _)| *n != name);
+        if !v.is_empty() {
+            style.push((name, v.to_string()));
+        }
+        if style.is_empty() {
+            dom.remove_attr(self.id, "style");
+        } else {
+            let style: Vec<String> = style.iter().map(|&(ref n, ref v)| format!("{}: {};", n, v)).collect();
+            dom.set_attr(self.id, "style", &style.join(" "));
+        }
+        Ok(())
+    }
+
+    pub fn style_get_str(&self, s: &str) -> String {
+        self.try_style_get_str(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_style_get_str(&self, s: &str) -> Result<String, JsError> {
+        let dom = self.document().dom.borrow();
+        let name = kebab(s);
+        let style = parse_style(dom.attr(self.id, "style").unwrap_or(""));
+        Ok(style.into_iter().find(|&(ref n, _)| *n == name).map(|(_, v)| v).unwrap_or_default())
+    }
+
+    pub fn prop_set_i32(&self, s: &str, v: i32) {
+        self.try_prop_set_i32(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_i32(&self, s: &str, v: i32) -> Result<(), JsError> {
+        self.try_prop_set_str(s, &v.to_string())
+    }
+
+    pub fn prop_set_str(&self, s: &str, v: &str) {
+        self.try_prop_set_str(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_str(&self, s: &str, v: &str) -> Result<(), JsError> {
+        let mut dom = self.document().dom.borrow_mut();
+        match s {
+            "innerHTML" => dom.set_inner_html(self.id, v),
+            _ => match reflected(s) {
+                Some(attr) => dom.set_attr(self.id, attr, v),
+                None => dom.set_prop(self.id, s, v),
+            },
+        }
+        Ok(())
+    }
+
+    /// Sets a numeric property without truncating it, as `scrollTop` or `opacity` need.
+    pub fn prop_set_f64(&self, s: &str, v: f64) {
+        self.try_prop_set_f64(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_f64(&self, s: &str, v: f64) -> Result<(), JsError> {
+        self.try_prop_set_str(s, &v.to_string())
+    }
+
+    pub fn prop_set_bool(&self, s: &str, v: bool) {
+        self.try_prop_set_bool(s, v).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_set_bool(&self, s: &str, v: bool) -> Result<(), JsError> {
+        match reflected_bool(s) {
+            Some(attr) => {
+                let mut dom = self.document().dom.borrow_mut();
+                if v {
+                    dom.set_attr(self.id, attr, "");
+                } else {
+                    dom.remove_attr(self.id, attr);
+                }
+                Ok(())
+            }
+            None => self.try_prop_set_str(s, if v { "true" } else { "false" }),
+        }
+    }
+
+    /// Unset and non-numeric properties read as 0; layout properties such as `clientWidth` are
+    /// always 0.
+    pub fn prop_get_i32(&self, s: &str) -> i32 {
+        self.try_prop_get_i32(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_i32(&self, s: &str) -> Result<i32, JsError> {
+        let value = self.try_prop_get_str(s)?;
+        Ok(value.trim().parse::<f64>().map(|n| n as i32).unwrap_or(0))
+    }
+
+    /// Unset properties read as 0 and non-numeric ones as `NaN`; layout properties such as
+    /// `scrollTop` are always 0.
+    pub fn prop_get_f64(&self, s: &str) -> f64 {
+        self.try_prop_get_f64(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_f64(&self, s: &str) -> Result<f64, JsError> {
+        let value = self.try_prop_get_str(s)?;
+        let value = value.trim();
+        if value.is_empty() {
+            return Ok(0.0);
+        }
+        Ok(value.parse::<f64>().unwrap_or(::std::f64::NAN))
+    }
+
+    /// Properties stored as strings read as false when empty, `"false"` or `"0"`.
+    pub fn prop_get_bool(&self, s: &str) -> bool {
+        self.try_prop_get_bool(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_bool(&self, s: &str) -> Result<bool, JsError> {
+        if let Some(attr) = reflected_bool(s) {
+            return Ok(self.document().dom.borrow().attr(self.id, attr).is_some());
+        }
+        let value = self.try_prop_get_str(s)?;
+        Ok(match &value[..] {
+            "" | "false" | "0" => false,
+            _ => true,
+        })
+    }
+
+    /// Unset properties read as an empty string.
+    pub fn prop_get_str(&self, s: &str) -> String {
+        self.try_prop_get_str(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prop_get_str(&self, s: &str) -> Result<String, JsError> {
+        let dom = self.document().dom.borrow();
+        Ok(match s {
+            "innerHTML" => dom.inner_html(self.id),
+            "textContent" => dom.text_content(self.id),
+            "tagName" => dom.tag(self.id).to_uppercase(),
+            _ => match reflected(s) {
+                Some(attr) => dom.attr(self.id, attr).unwrap_or("").to_string(),
+                None => dom.prop(self.id, s).unwrap_or("").to_string(),
+            },
+        })
+    }
+
+    pub fn append(&self, s: &HtmlNode) {
+        self.try_append(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_append(&self, s: &HtmlNode) -> Result<(), JsError> {
+        self.document().dom.borrow_mut().append(self.id, s.id);
+        Ok(())
+    }
+
+    pub fn html_append(&self, s: &str) {
+        self.try_html_append(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_append(&self, s: &str) -> Result<(), JsError> {
+        self.document().dom.borrow_mut().insert_html(self.id, s, false);
+        Ok(())
+    }
+
+    pub fn html_prepend(&self, s: &str) {
+        self.try_html_prepend(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_html_prepend(&self, s: &str) -> Result<(), JsError> {
+        self.document().dom.borrow_mut().insert_html(self.id, s, true);
+        Ok(())
+    }
+
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_on(s, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let id = self.document().dom.borrow_mut().add_listener(self.id, s, false, Box::new(f));
+        Ok(ListenerHandle::new(id, self.doc))
+    }
+
+    /// Resolves with the next `s` event on the node.
+    pub fn next_event(&self, s: &str) -> NextEvent<'a> {
+        self.try_next_event(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_next_event(&self, s: &str) -> Result<NextEvent<'a>, JsError> {
+        self.try_events(s).map(NextEvent::new)
+    }
+
+    /// Yields every `s` event on the node until the stream is dropped.
+    pub fn events(&self, s: &str) -> EventStream<'a, Event<'a>> {
+        self.try_events(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_events(&self, s: &str) -> Result<EventStream<'a, Event<'a>>, JsError> {
+        let (tx, rx) = channel();
+        let listener = self.try_on(s, move |e| tx.send(e))?;
+        Ok(EventStream::new(vec![listener], rx))
+    }
+
+    pub fn captured_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_captured_on(s, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_captured_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let id = self.document().dom.borrow_mut().add_listener(self.id, s, true, Box::new(f));
+        Ok(ListenerHandle::new(id, self.doc))
+    }
+
+    pub fn on_keydown<F: FnMut(KeyboardEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_keydown(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_keydown<F: FnMut(KeyboardEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_on("keydown", move |e| if let Some(k) = e.keyboard() { f(k) })
+    }
+
+    pub fn on_keyup<F: FnMut(KeyboardEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_keyup(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_keyup<F: FnMut(KeyboardEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        self.try_on("keyup", move |e| if let Some(k) = e.keyboard() { f(k) })
+    }
+
+    /// Dispatches a synthetic `s` event to the element. Returns `false` if a listener called
+    /// `prevent_default`.
+    pub fn dispatch(&self, s: &str, init: EventInit) -> bool {
+        dispatch(self.doc, self.id, s, init)
+    }
+
+    /// Dispatches a `click` with the primary button.
+    pub fn click(&self) -> bool {
+        self.dispatch("click", EventInit::mouse(MouseInit::default()))
+    }
+
+    pub fn remove_self(&self) {
+        self.try_remove_self().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_remove_self(&self) -> Result<(), JsError> {
+        self.document().dom.borrow_mut().detach(self.id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "../tests/html_node.rs"]
+mod shared_tests;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll};
+    use super::*;
+
+    #[test]
+    fn test_on() {
+        let document = init();
+        let body = document.element_query("body").unwrap();
+        body.html_set("<button>ok</button>");
+        let button = document.element_query("button").unwrap();
+        let clicks = Rc::new(Cell::new(0));
+        let counter = clicks.clone();
+        let handle = button.on("click", move |e| {
+            assert_eq!(e.target.as_ref().unwrap().tagname(), "button");
+            counter.set(counter.get() + 1)
+        });
+        button.click();
+        assert_eq!(clicks.get(), 1);
+        handle.remove();
+        button.click();
+        assert_eq!(clicks.get(), 1);
+    }
+
+    #[test]
+    fn test_keyboard_event() {
+        let document = init();
+        let input = document.element_create("input").unwrap();
+        let seen = Rc::new(RefCell::new(None));
+        let result = seen.clone();
+        let _handle = input.on_keydown(move |k| {
+            *result.borrow_mut() = Some((k.key(), k.shift_key()));
+        });
+        input.dispatch("keydown", EventInit::keyboard(KeyboardInit {
+            key: "A".to_string(),
+            shift_key: true,
+            ..KeyboardInit::default()
+        }));
+        assert_eq!(*seen.borrow(), Some(("A".to_string(), true)));
+    }
+
+    #[test]
+    fn test_propagation() {
+        let document = init();
+        let body = document.element_query("body").unwrap();
+        body.html_set(r#"<div class="parent"><a class="child"></a></div>"#);
+        let parent = document.element_query(".parent").unwrap();
+        let child = document.element_query(".child").unwrap();
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let log = |name: &'static str| {
+            let order = order.clone();
+            move |_: Event| order.borrow_mut().push(name)
+        };
+        let _capture = parent.captured_on("click", log("capture"));
+        let _bubble = parent.on("click", log("bubble"));
+        let _window = document.window().on("click", log("window"));
+        let _target = child.on("click", |e| e.prevent_default());
+        assert!(!child.click());
+        assert_eq!(*order.borrow(), vec!["capture", "bubble", "window"]);
+
+        let _stop = child.on("click", |e| e.stop_propagation());
+        child.click();
+        assert_eq!(order.borrow().len(), 4);
+    }
+
+    /// Calls the closure with the output of the future once it resolves.
+    struct Then<F, G>(F, Option<G>);
+
+    impl<F: Future + Unpin, G: FnOnce(F::Output) + Unpin> Future for Then<F, G> {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            let this = self.get_mut();
+            match Pin::new(&mut this.0).poll(cx) {
+                Poll::Ready(output) => {
+                    (this.1.take().unwrap())(output);
+                    Poll::Ready(())
+                }
+                Poll::Pending => Poll::Pending,
+            }
+        }
+    }
+
+    #[test]
+    fn test_next_event() {
+        let document = Box::leak(Box::new(init()));
+        let button = document.element_create("button").unwrap();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let result = seen.clone();
+        spawn_local(Then(button.next_event("click"), Some(move |e: Event| {
+            result.borrow_mut().push(e.target.unwrap().tagname());
+        })));
+        button.click();
+        assert!(seen.borrow().is_empty());
+        document.window().advance(0);
+        button.click();
+        document.window().advance(0);
+        assert_eq!(*seen.borrow(), vec!["button"]);
+    }
+}
@@ -0,0 +1,305 @@
+use std::iter::IntoIterator;
+use ::error::*;
+use ::native::*;
+
+
+thread_local! {
+    static LOCAL: MemoryStorage = MemoryStorage::new();
+    static SESSION: MemoryStorage = MemoryStorage::new();
+}
+
+/// `localStorage`, kept in memory for the current thread.
+pub struct LocalStorageInterface;
+
+/// What iterating over `LocalStorage` yields: the values, as before the `Storage` trait.
+pub type LocalStorageIterator = LocalStorageValues;
+
+pub type LocalStorageKeys = Keys<'static, LocalStorageInterface>;
+
+pub type LocalStorageValues = Values<'static, LocalStorageInterface>;
+
+pub type LocalStorageEntries = Entries<'static, LocalStorageInterface>;
+
+impl LocalStorageInterface {
+    /// Calls `f` on the `localStorage` changes reported by `Window::dispatch_storage`.
+    pub fn on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, f: F) -> ListenerHandle<'a> {
+        self.try_on_change(window, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        window.try_on_storage_change(move |e| if e.area == StorageArea::Local { f(e) })
+    }
+
+    // The `Storage` methods, also available without the trait in scope.
+
+    pub fn len(&self) -> i32 {
+        Storage::len(self)
+    }
+
+    pub fn try_len(&self) -> Result<i32, JsError> {
+        Storage::try_len(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Storage::is_empty(self)
+    }
+
+    pub fn clear(&self) {
+        Storage::clear(self)
+    }
+
+    pub fn try_clear(&self) -> Result<(), JsError> {
+        Storage::try_clear(self)
+    }
+
+    pub fn remove(&self, s: &str) {
+        Storage::remove(self, s)
+    }
+
+    pub fn try_remove(&self, s: &str) -> Result<(), JsError> {
+        Storage::try_remove(self, s)
+    }
+
+    pub fn set(&self, s: &str, v: &str) {
+        Storage::set(self, s, v)
+    }
+
+    pub fn try_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        Storage::try_set(self, s, v)
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        Storage::get(self, name)
+    }
+
+    pub fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        Storage::try_get(self, name)
+    }
+
+    /// Returns the name of the key at `index`, or `None` past the end.
+    pub fn key(&self, index: i32) -> Option<String> {
+        Storage::key(self, index)
+    }
+
+    pub fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        Storage::try_key(self, index)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        Storage::contains_key(self, name)
+    }
+
+    pub fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        Storage::try_contains_key(self, name)
+    }
+
+    pub fn keys(&self) -> LocalStorageKeys {
+        StorageExt::keys(&LocalStorage)
+    }
+
+    pub fn values(&self) -> LocalStorageValues {
+        StorageExt::values(&LocalStorage)
+    }
+
+    pub fn entries(&self) -> LocalStorageEntries {
+        StorageExt::entries(&LocalStorage)
+    }
+}
+
+impl Storage for LocalStorageInterface {
+    fn try_len(&self) -> Result<i32, JsError> {
+        LOCAL.with(|s| s.try_len())
+    }
+
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        LOCAL.with(|s| s.try_key(index))
+    }
+
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        LOCAL.with(|s| s.try_get(name))
+    }
+
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> {
+        LOCAL.with(|s| s.try_set(name, value))
+    }
+
+    fn try_remove(&self, name: &str) -> Result<(), JsError> {
+        LOCAL.with(|s| s.try_remove(name))
+    }
+
+    fn try_clear(&self) -> Result<(), JsError> {
+        LOCAL.with(|s| s.try_clear())
+    }
+}
+
+/// Yields the values; use `entries` for `(key, value)` pairs.
+impl IntoIterator for LocalStorageInterface {
+    type Item = String;
+    type IntoIter = LocalStorageIterator;
+
+    fn into_iter(self) -> LocalStorageIterator {
+        LocalStorage.values()
+    }
+}
+
+#[allow(non_upper_case_globals)]
+pub const LocalStorage: LocalStorageInterface = LocalStorageInterface;
+
+/// `sessionStorage`, kept in memory for the current thread.
+pub struct SessionStorageInterface;
+
+pub type SessionStorageKeys = Keys<'static, SessionStorageInterface>;
+
+pub type SessionStorageValues = Values<'static, SessionStorageInterface>;
+
+pub type SessionStorageEntries = Entries<'static, SessionStorageInterface>;
+
+impl SessionStorageInterface {
+    /// Calls `f` on the `sessionStorage` changes reported by `Window::dispatch_storage`.
+    pub fn on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, f: F) -> ListenerHandle<'a> {
+        self.try_on_change(window, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_change<'a, F: FnMut(StorageEvent) + 'a>(&self, window: &Window<'a>, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        window.try_on_storage_change(move |e| if e.area == StorageArea::Session { f(e) })
+    }
+
+    // The `Storage` methods, also available without the trait in scope.
+
+    pub fn len(&self) -> i32 {
+        Storage::len(self)
+    }
+
+    pub fn try_len(&self) -> Result<i32, JsError> {
+        Storage::try_len(self)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        Storage::is_empty(self)
+    }
+
+    pub fn clear(&self) {
+        Storage::clear(self)
+    }
+
+    pub fn try_clear(&self) -> Result<(), JsError> {
+        Storage::try_clear(self)
+    }
+
+    pub fn remove(&self, s: &str) {
+        Storage::remove(self, s)
+    }
+
+    pub fn try_remove(&self, s: &str) -> Result<(), JsError> {
+        Storage::try_remove(self, s)
+    }
+
+    pub fn set(&self, s: &str, v: &str) {
+        Storage::set(self, s, v)
+    }
+
+    pub fn try_set(&self, s: &str, v: &str) -> Result<(), JsError> {
+        Storage::try_set(self, s, v)
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        Storage::get(self, name)
+    }
+
+    pub fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        Storage::try_get(self, name)
+    }
+
+    /// Returns the name of the key at `index`, or `None` past the end.
+    pub fn key(&self, index: i32) -> Option<String> {
+        Storage::key(self, index)
+    }
+
+    pub fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        Storage::try_key(self, index)
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        Storage::contains_key(self, name)
+    }
+
+    pub fn try_contains_key(&self, name: &str) -> Result<bool, JsError> {
+        Storage::try_contains_key(self, name)
+    }
+
+    pub fn keys(&self) -> SessionStorageKeys {
+        StorageExt::keys(&SessionStorage)
+    }
+
+    pub fn values(&self) -> SessionStorageValues {
+        StorageExt::values(&SessionStorage)
+    }
+
+    pub fn entries(&self) -> SessionStorageEntries {
+        StorageExt::entries(&SessionStorage)
+    }
+}
+
+impl Storage for SessionStorageInterface {
+    fn try_len(&self) -> Result<i32, JsError> {
+        SESSION.with(|s| s.try_len())
+    }
+
+    fn try_key(&self, index: i32) -> Result<Option<String>, JsError> {
+        SESSION.with(|s| s.try_key(index))
+    }
+
+    fn try_get(&self, name: &str) -> Result<Option<String>, JsError> {
+        SESSION.with(|s| s.try_get(name))
+    }
+
+    fn try_set(&self, name: &str, value: &str) -> Result<(), JsError> {
+        SESSION.with(|s| s.try_set(name, value))
+    }
+
+    fn try_remove(&self, name: &str) -> Result<(), JsError> {
+        SESSION.with(|s| s.try_remove(name))
+    }
+
+    fn try_clear(&self) -> Result<(), JsError> {
+        SESSION.with(|s| s.try_clear())
+    }
+}
+
+#[allow(non_upper_case_globals)]
+pub const SessionStorage: SessionStorageInterface = SessionStorageInterface;
+
+#[cfg(test)]
+mod tests {
+    // Only the constant: the inherent methods must work without `Storage` in scope.
+    use super::{LocalStorage, SessionStorage};
+
+    #[test]
+    fn test_inherent_methods() {
+        LocalStorage.clear();
+        LocalStorage.set("a", "1");
+        assert_eq!(LocalStorage.len(), 1);
+        assert_eq!(LocalStorage.get("a"), Some("1".to_string()));
+        assert_eq!(LocalStorage.key(0), Some("a".to_string()));
+        assert!(LocalStorage.contains_key("a"));
+        assert_eq!(LocalStorage.keys().collect::<Vec<_>>(), vec!["a"]);
+        assert_eq!(LocalStorage.into_iter().collect::<Vec<_>>(), vec!["1"]);
+        assert_eq!(LocalStorage.entries().collect::<Vec<_>>(), vec![("a".to_string(), "1".to_string())]);
+        LocalStorage.remove("a");
+        assert!(LocalStorage.is_empty());
+    }
+
+    #[test]
+    fn test_session_inherent_methods() {
+        SessionStorage.clear();
+        SessionStorage.set("a", "1");
+        assert_eq!(SessionStorage.len(), 1);
+        assert_eq!(SessionStorage.get("a"), Some("1".to_string()));
+        assert_eq!(SessionStorage.key(0), Some("a".to_string()));
+        assert!(SessionStorage.contains_key("a"));
+        assert_eq!(SessionStorage.values().collect::<Vec<_>>(), vec!["1"]);
+        assert_eq!(SessionStorage.entries().collect::<Vec<_>>(), vec![("a".to_string(), "1".to_string())]);
+        SessionStorage.remove("a");
+        assert!(SessionStorage.is_empty());
+    }
+}
@@ -0,0 +1,198 @@
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use ::native::*;
+
+
+/// Does nothing: listeners, timers and spawned tasks of a native document keep running from
+/// `Window::advance` for as long as the document lives.
+pub fn keep_alive() {}
+
+struct LoopState {
+    doc: *const Document<'static>,
+    frame: RefCell<Option<Box<FnMut()>>>,
+    frame_generation: Cell<u32>,
+    fps: Cell<i32>,
+    started: Cell<bool>,
+    quitting: Cell<bool>,
+    // Holds the state alive until `quit`, which breaks the cycle.
+    pending: RefCell<Option<TimerHandle<'static>>>,
+}
+
+impl LoopState {
+    /// Schedules the next frame, if there is a frame callback and none is scheduled yet.
+    fn request(state: &Rc<LoopState>) {
+        if !state.started.get() || state.quitting.get() || state.pending.borrow().is_some() || state.frame.borrow().is_none() {
+            return;
+        }
+        let window = Window::new(state.doc);
+        let tick = state.clone();
+        let fps = state.fps.get();
+        let pending = if fps > 0 {
+            window.set_timeout(1000 / fps, move || LoopState::frame(&tick))
+        } else {
+            window.request_animation_frame(move |_| LoopState::frame(&tick))
+        };
+        *state.pending.borrow_mut() = Some(pending);
+    }
+
+    fn frame(state: &Rc<LoopState>) {
+        let pending = state.pending.borrow_mut().take();
+        drop(pending);
+        let frame = state.frame.borrow_mut().take();
+        if let Some(mut frame) = frame {
+            let generation = state.frame_generation.get();
+            frame();
+            if state.frame_generation.get() == generation {
+                *state.frame.borrow_mut() = Some(frame);
+            }
+        }
+        LoopState::request(state);
+    }
+}
+
+/// Controls the loop started by `run`.
+#[derive(Clone)]
+pub struct MainLoop {
+    state: Rc<LoopState>,
+}
+
+impl MainLoop {
+    /// Calls `f` on every frame, replacing the previous frame callback.
+    pub fn set_frame<F: FnMut() + 'static>(&self, f: F) {
+        *self.state.frame.borrow_mut() = Some(Box::new(f));
+        self.state.frame_generation.set(self.state.frame_generation.get().wrapping_add(1));
+        LoopState::request(&self.state);
+    }
+
+    /// Stops calling the frame callback. The document stays alive.
+    pub fn clear_frame(&self) {
+        *self.state.frame.borrow_mut() = None;
+        self.state.frame_generation.set(self.state.frame_generation.get().wrapping_add(1));
+    }
+
+    /// Runs the frame callback `fps` times per second, or on every animation frame if `fps` is
+    /// zero, which is the default.
+    pub fn set_fps(&self, fps: i32) {
+        self.state.fps.set(fps);
+        let pending = self.state.pending.borrow_mut().take();
+        if pending.is_some() {
+            drop(pending);
+            LoopState::request(&self.state);
+        }
+    }
+
+    pub fn fps(&self) -> i32 {
+        self.state.fps.get()
+    }
+
+    /// Stops the loop once the current callback has returned, then drops the frame callback and
+    /// every forgotten listener and timer.
+    ///
+    /// The `Document` itself stays alive, as spawned tasks and `Window`s may still refer to it.
+    pub fn quit(&self) {
+        if self.state.quitting.replace(true) {
+            return;
+        }
+        let state = self.state.clone();
+        Window::new(self.state.doc).queue_microtask(move || {
+            let pending = state.pending.borrow_mut().take();
+            drop(pending);
+            let frame = state.frame.borrow_mut().take();
+            drop(frame);
+            unsafe { &*state.doc }.release_all();
+        }).forget();
+    }
+}
+
+/// Creates the `Document`, calls `app` to set up the page and runs the frame callback from
+/// `Window::advance` until `MainLoop::quit`.
+///
+/// Returns once `app` has returned, so that the test can go on to drive the window.
+pub fn run<F: FnOnce(&Document<'static>, &MainLoop)>(app: F) {
+    // Never freed: anything scheduled from `app` may refer to the document after `quit`.
+    let document: &'static Document<'static> = Box::leak(Box::new(init()));
+    let state = Rc::new(LoopState {
+        doc: document,
+        frame: RefCell::new(None),
+        frame_generation: Cell::new(0),
+        fps: Cell::new(0),
+        started: Cell::new(false),
+        quitting: Cell::new(false),
+        pending: RefCell::new(None),
+    });
+    let main_loop = MainLoop { state: state.clone() };
+    app(document, &main_loop);
+    state.started.set(true);
+    LoopState::request(&state);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use super::*;
+
+    #[test]
+    fn test_run_quit() {
+        let frames = Rc::new(Cell::new(0));
+        let mut window = None;
+        {
+            let frames = frames.clone();
+            run(|document, main_loop| {
+                window = Some(document.window());
+                let main_loop = main_loop.clone();
+                main_loop.clone().set_frame(move || {
+                    frames.set(frames.get() + 1);
+                    if frames.get() == 3 {
+                        main_loop.quit();
+                    }
+                });
+            });
+        }
+        let window = window.unwrap();
+        assert_eq!(frames.get(), 0);
+        window.advance(16);
+        assert_eq!(frames.get(), 1);
+        window.advance(1000);
+        assert_eq!(frames.get(), 3);
+    }
+
+    #[test]
+    fn test_set_fps() {
+        let frames = Rc::new(Cell::new(0));
+        let mut window = None;
+        {
+            let frames = frames.clone();
+            run(|document, main_loop| {
+                window = Some(document.window());
+                main_loop.set_fps(10);
+                main_loop.set_frame(move || frames.set(frames.get() + 1));
+            });
+        }
+        window.unwrap().advance(1000);
+        assert_eq!(frames.get(), 10);
+    }
+
+    #[test]
+    fn test_quit_releases_forgotten() {
+        let clicks = Rc::new(Cell::new(0));
+        let mut window = None;
+        let mut button = None;
+        {
+            let clicks = clicks.clone();
+            run(|document, main_loop| {
+                window = Some(document.window());
+                let elem = document.element_create("button").unwrap();
+                elem.on("click", move |_| clicks.set(clicks.get() + 1)).forget();
+                button = Some(elem);
+                main_loop.quit();
+            });
+        }
+        let (window, button) = (window.unwrap(), button.unwrap());
+        button.click();
+        assert_eq!(clicks.get(), 1);
+        window.advance(16);
+        button.click();
+        assert_eq!(clicks.get(), 1);
+    }
+}
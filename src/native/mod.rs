@@ -0,0 +1,44 @@
+//! A pure-Rust stand-in for the browser, compiled instead of the emscripten bindings when the
+//! `native` feature is enabled.
+//!
+//! `Document`, `HtmlNode`, `Event`, `Window`, `WebSocket`, the storages, `run` and the executor
+//! keep the API of their browser counterparts, backed by an in-memory element tree, so UI code
+//! can be tested with a plain `cargo test --features native`. Tests stand in for the browser,
+//! the user and the network through a few extra methods:
+//!
+//! - Time only passes in `Window::advance`, which runs the timers, animation frames (every 16
+//!   milliseconds), microtasks and `spawn_local` tasks that come due, in order.
+//! - Events are only dispatched synthetically, through `HtmlNode::dispatch`, `HtmlNode::click`,
+//!   `Window::dispatch`, `Window::resize` and `Window::dispatch_storage`.
+//! - `alert`, `confirm` and `prompt` are recorded for `Window::take_dialogs` and answered from
+//!   `Window::push_confirm` and `Window::push_prompt`.
+//! - The location starts at `http://localhost/` and navigating never unloads the document.
+//! - Sockets never connect on their own: the `server_` methods of `WebSocket` accept, feed and
+//!   close them, and `WebSocket::take_sent` returns what the page sent.
+//!
+//! Not supported: `fetch`, `XmlHttpRequest`, IndexedDB, `ReconnectingWebSocket` and the
+//! `Document::push_ref` family. There is no layout, styles are not computed and properties
+//! other than the reflected attributes are stored as strings.
+
+mod dom;
+mod timer;
+
+pub mod document;
+pub mod event;
+pub mod html_node;
+pub mod local_storage;
+pub mod main_loop;
+#[path = "../api/storage.rs"]
+pub mod storage;
+pub mod websocket;
+pub mod window;
+
+pub use self::document::*;
+pub use self::event::*;
+pub use self::html_node::*;
+pub use self::local_storage::*;
+pub use self::main_loop::*;
+pub use self::storage::*;
+pub use self::timer::TimerHandle;
+pub use self::websocket::*;
+pub use self::window::*;
@@ -0,0 +1,195 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use ::executor;
+use ::native::*;
+
+
+/// Animation frames are due on every multiple of this many milliseconds.
+const FRAME_MS: f64 = 16.0;
+
+type TimerCallback<'a> = Rc<RefCell<Box<FnMut(f64) + 'a>>>;
+
+struct Timer<'a> {
+    key: u32,
+    due: f64,
+    // The interval of repeating timers.
+    period: Option<f64>,
+    // Orders timers due at the same time by when they were scheduled.
+    seq: u64,
+    callback: TimerCallback<'a>,
+}
+
+/// The time of a native `Document` and the timers, animation frames and microtasks waiting on
+/// it. Nothing runs until `Window::advance`.
+pub(crate) struct Clock<'a> {
+    now: f64,
+    timers: Vec<Timer<'a>>,
+    microtasks: VecDeque<(u32, TimerCallback<'a>)>,
+    next_key: u32,
+    next_seq: u64,
+}
+
+impl<'a> Clock<'a> {
+    pub(crate) fn new() -> Self {
+        Clock { now: 0.0, timers: Vec::new(), microtasks: VecDeque::new(), next_key: 0, next_seq: 0 }
+    }
+
+    pub(crate) fn now(&self) -> f64 {
+        self.now
+    }
+
+    fn next_key(&mut self) -> u32 {
+        self.next_key = self.next_key.wrapping_add(1);
+        self.next_key
+    }
+
+    fn push(&mut self, due: f64, period: Option<f64>, callback: Box<FnMut(f64) + 'a>) -> u32 {
+        let key = self.next_key();
+        self.next_seq += 1;
+        self.timers.push(Timer { key, due, period, seq: self.next_seq, callback: Rc::new(RefCell::new(callback)) });
+        key
+    }
+
+    fn cancel(&mut self, key: u32) {
+        self.timers.retain(|t| t.key != key);
+        self.microtasks.retain(|&(k, _)| k != key);
+    }
+
+    /// Takes the earliest timer due by `until`, with its key if it was a one-shot timer.
+    /// Repeating timers stay scheduled for their next run.
+    fn take_due(&mut self, until: f64) -> Option<(Option<u32>, f64, TimerCallback<'a>)> {
+        let index = self.timers.iter().enumerate()
+            .filter(|&(_, t)| t.due <= until)
+            .min_by(|&(_, a), &(_, b)| a.due.partial_cmp(&b.due).unwrap().then(a.seq.cmp(&b.seq)))
+            .map(|(index, _)| index)?;
+        let due = self.timers[index].due;
+        match self.timers[index].period {
+            Some(period) => {
+                self.next_seq += 1;
+                let timer = &mut self.timers[index];
+                timer.due += period;
+                timer.seq = self.next_seq;
+                Some((None, due, timer.callback.clone()))
+            }
+            None => {
+                let timer = self.timers.swap_remove(index);
+                Some((Some(timer.key), due, timer.callback))
+            }
+        }
+    }
+}
+
+/// Runs `f` at most once, so that one-shot callbacks can be stored as `FnMut`.
+fn once<'a, A, F: FnOnce(A) + 'a>(f: F) -> Box<FnMut(A) + 'a> {
+    let mut f = Some(f);
+    Box::new(move |a| if let Some(f) = f.take() { f(a) })
+}
+
+pub(crate) fn set_timeout<'a, F: FnOnce() + 'a>(doc: *const Document<'a>, ms: i32, f: F) -> TimerHandle<'a> {
+    let mut clock = unsafe { &*doc }.clock.borrow_mut();
+    let due = clock.now + ms.max(0) as f64;
+    let key = clock.push(due, None, once(move |_| f()));
+    TimerHandle { key, doc }
+}
+
+pub(crate) fn set_interval<'a, F: FnMut() + 'a>(doc: *const Document<'a>, ms: i32, mut f: F) -> TimerHandle<'a> {
+    let mut clock = unsafe { &*doc }.clock.borrow_mut();
+    // Like browsers, never fire an interval more than once per millisecond.
+    let period = ms.max(1) as f64;
+    let due = clock.now + period;
+    let key = clock.push(due, Some(period), Box::new(move |_| f()));
+    TimerHandle { key, doc }
+}
+
+pub(crate) fn request_animation_frame<'a, F: FnOnce(f64) + 'a>(doc: *const Document<'a>, f: F) -> TimerHandle<'a> {
+    let mut clock = unsafe { &*doc }.clock.borrow_mut();
+    let due = ((clock.now / FRAME_MS).floor() + 1.0) * FRAME_MS;
+    let key = clock.push(due, None, once(f));
+    TimerHandle { key, doc }
+}
+
+pub(crate) fn queue_microtask<'a, F: FnOnce() + 'a>(doc: *const Document<'a>, f: F) -> TimerHandle<'a> {
+    let mut clock = unsafe { &*doc }.clock.borrow_mut();
+    let key = clock.next_key();
+    clock.microtasks.push_back((key, Rc::new(RefCell::new(once(move |_| f())))));
+    TimerHandle { key, doc }
+}
+
+/// Runs queued microtasks and woken executor tasks until there are none left.
+fn run_microtasks(doc: &Document) {
+    loop {
+        let microtask = doc.clock.borrow_mut().microtasks.pop_front();
+        match microtask {
+            Some((key, callback)) => {
+                (&mut *callback.borrow_mut())(0.0);
+                let _handle = doc.take_timer(key);
+            }
+            None => if !executor::run_woken_tasks() {
+                return;
+            },
+        }
+    }
+}
+
+/// Moves the clock of `doc` forward by `ms`, running every timer and animation frame that
+/// comes due in order, each followed by the microtasks it queued.
+pub(crate) fn advance(doc: &Document, ms: i32) {
+    let until = doc.clock.borrow().now + ms.max(0) as f64;
+    run_microtasks(doc);
+    loop {
+        let timer = doc.clock.borrow_mut().take_due(until);
+        let (key, due, callback) = match timer {
+            Some(timer) => timer,
+            None => break,
+        };
+        {
+            let mut clock = doc.clock.borrow_mut();
+            clock.now = clock.now.max(due);
+        }
+        // Skips an interval that is still running, when it calls `advance` itself.
+        if let Ok(mut callback) = callback.try_borrow_mut() {
+            (&mut *callback)(due);
+        }
+        if let Some(key) = key {
+            // Frees the handle of a forgotten one-shot timer.
+            let _handle = doc.take_timer(key);
+        }
+        run_microtasks(doc);
+    }
+    let mut clock = doc.clock.borrow_mut();
+    clock.now = clock.now.max(until);
+}
+
+/// Owns a scheduled timer, animation frame or microtask. Dropping the handle cancels it.
+#[must_use = "the timer is cancelled as soon as the handle is dropped; use `forget` to keep it"]
+pub struct TimerHandle<'a> {
+    key: u32,
+    doc: *const Document<'a>,
+}
+
+impl<'a> TimerHandle<'a> {
+    /// Identifies the timer in `Document::take_timer`.
+    pub(crate) fn key(&self) -> u32 {
+        self.key
+    }
+
+    /// Cancels the timer and frees its closure.
+    pub fn cancel(self) {}
+
+    /// Keeps the timer scheduled for as long as the `Document` lives. One-shot timers are
+    /// freed once they have fired.
+    pub fn forget(self) {
+        unsafe {
+            (&*self.doc).push_timer(self);
+        }
+    }
+}
+
+impl<'a> Drop for TimerHandle<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            (&*self.doc).clock.borrow_mut().cancel(self.key);
+        }
+    }
+}
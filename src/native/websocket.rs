@@ -0,0 +1,376 @@
+use ::error::*;
+use ::executor::*;
+use ::native::*;
+use ::native::dom::*;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadyState {
+    Connecting,
+    Open,
+    Closing,
+    Closed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseEvent {
+    pub code: u16,
+    pub reason: String,
+    pub was_clean: bool,
+}
+
+/// A message sent by the page, as returned by `WebSocket::take_sent`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+/// The state of a socket of a native `Document`. There is no server: tests play its part
+/// through the `server_` methods of `WebSocket`.
+pub(crate) struct Socket {
+    url: String,
+    protocols: Vec<String>,
+    protocol: String,
+    state: ReadyState,
+    sent: Vec<Message>,
+}
+
+fn error(name: &str, message: &str) -> JsError {
+    JsError {
+        name: name.to_string(),
+        message: message.to_string(),
+        stack: None,
+    }
+}
+
+pub(crate) fn create<'a>(doc: *const Document<'a>, url: &str, protocols: &[&str]) -> Result<Option<WebSocket<'a>>, JsError> {
+    if !url.starts_with("ws://") && !url.starts_with("wss://") {
+        return Err(error("SyntaxError", &format!("The URL '{}' is invalid.", url)));
+    }
+    let mut dom = unsafe { &*doc }.dom.borrow_mut();
+    dom.sockets.push(Socket {
+        url: url.to_string(),
+        protocols: protocols.iter().map(|p| p.to_string()).collect(),
+        protocol: String::new(),
+        state: ReadyState::Connecting,
+        sent: Vec::new(),
+    });
+    Ok(Some(WebSocket { id: dom.sockets.len() - 1, doc }))
+}
+
+/// A socket of a native `Document`. Clones refer to the same socket.
+#[derive(Clone)]
+pub struct WebSocket<'a> {
+    id: usize,
+    doc: *const Document<'a>,
+}
+
+impl<'a> WebSocket<'a> {
+    pub(crate) fn new(id: usize, doc: *const Document<'a>) -> Self {
+        WebSocket { id, doc }
+    }
+
+    fn document(&self) -> &Document<'a> {
+        unsafe { &*self.doc }
+    }
+
+    fn with_socket<T, F: FnOnce(&mut Socket) -> T>(&self, f: F) -> T {
+        f(&mut self.document().dom.borrow_mut().sockets[self.id])
+    }
+
+    fn listen<F: FnMut(&Signal) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        let id = self.document().dom.borrow_mut().add_signal_listener(self.id, Box::new(f));
+        ListenerHandle::new(id, self.doc)
+    }
+
+    pub fn on_open<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_open(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_open<F: FnMut() + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        Ok(self.listen(move |signal| if let Signal::Open = *signal { f() }))
+    }
+
+    pub fn on_message_string<F: FnMut(String) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_message_string(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_message_string<F: FnMut(String) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        Ok(self.listen(move |signal| if let Signal::Text(ref text) = *signal { f(text.clone()) }))
+    }
+
+    pub fn on_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_message_binary(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_message_binary<F: FnMut(&[u8]) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        Ok(self.listen(move |signal| if let Signal::Binary(ref data) = *signal { f(data) }))
+    }
+
+    /// Yields the text messages until the socket closes.
+    pub fn messages(&self) -> EventStream<'a, String> {
+        self.try_messages().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_messages(&self) -> Result<EventStream<'a, String>, JsError> {
+        let (tx, rx) = channel();
+        let closed = tx.clone();
+        let listeners = vec![
+            self.try_on_message_string(move |message| tx.send(message))?,
+            self.try_on_close(move |_| closed.close())?,
+        ];
+        Ok(EventStream::new(listeners, rx))
+    }
+
+    /// Yields the binary messages until the socket closes.
+    pub fn binary_messages(&self) -> EventStream<'a, Vec<u8>> {
+        self.try_binary_messages().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_binary_messages(&self) -> Result<EventStream<'a, Vec<u8>>, JsError> {
+        let (tx, rx) = channel();
+        let closed = tx.clone();
+        let listeners = vec![
+            self.try_on_message_binary(move |message| tx.send(message.to_vec()))?,
+            self.try_on_close(move |_| closed.close())?,
+        ];
+        Ok(EventStream::new(listeners, rx))
+    }
+
+    pub fn on_close<F: FnMut(CloseEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_close(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_close<F: FnMut(CloseEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        Ok(self.listen(move |signal| if let Signal::Close(ref event) = *signal { f(event.clone()) }))
+    }
+
+    pub fn on_error<F: FnMut() + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_error(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_error<F: FnMut() + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        Ok(self.listen(move |signal| if let Signal::Error = *signal { f() }))
+    }
+
+    pub fn add_event_listener_open<F: FnMut() + 'a>(&self, f: F) {
+        self.on_open(f).forget()
+    }
+
+    pub fn try_add_event_listener_open<F: FnMut() + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_open(f).map(ListenerHandle::forget)
+    }
+
+    pub fn add_event_listener_message_string<F: FnMut(String) + 'a>(&self, f: F) {
+        self.on_message_string(f).forget()
+    }
+
+    pub fn try_add_event_listener_message_string<F: FnMut(String) + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_message_string(f).map(ListenerHandle::forget)
+    }
+
+    pub fn add_event_listener_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) {
+        self.on_message_binary(f).forget()
+    }
+
+    pub fn try_add_event_listener_message_binary<F: FnMut(&[u8]) + 'a>(&self, f: F) -> Result<(), JsError> {
+        self.try_on_message_binary(f).map(ListenerHandle::forget)
+    }
+
+    fn try_send_message(&self, message: Message) -> Result<(), JsError> {
+        self.with_socket(|socket| match socket.state {
+            ReadyState::Connecting => Err(error("InvalidStateError", "Still in CONNECTING state.")),
+            ReadyState::Open => {
+                socket.sent.push(message);
+                Ok(())
+            }
+            // Like browsers, drop messages sent after closing.
+            ReadyState::Closing | ReadyState::Closed => Ok(()),
+        })
+    }
+
+    pub fn send(&self, data: &str) {
+        self.try_send(data).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_send(&self, data: &str) -> Result<(), JsError> {
+        self.try_send_message(Message::Text(data.to_string()))
+    }
+
+    pub fn send_binary(&self, data: &[u8]) {
+        self.try_send_binary(data).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_send_binary(&self, data: &[u8]) -> Result<(), JsError> {
+        self.try_send_message(Message::Binary(data.to_vec()))
+    }
+
+    /// Closes the connection normally (code 1000) with `data` as the reason.
+    pub fn close(&self, data: &str) {
+        self.close_with(1000, data)
+    }
+
+    pub fn try_close(&self, data: &str) -> Result<(), JsError> {
+        self.try_close_with(1000, data)
+    }
+
+    /// Moves the socket to `Closing`; the close event follows `server_close`.
+    pub fn close_with(&self, code: u16, reason: &str) {
+        self.try_close_with(code, reason).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_close_with(&self, code: u16, reason: &str) -> Result<(), JsError> {
+        if code != 1000 && !(3000..=4999).contains(&code) {
+            return Err(error("InvalidAccessError", &format!("The code must be either 1000, or between 3000 and 4999. {} is neither.", code)));
+        }
+        if reason.len() > 123 {
+            return Err(error("SyntaxError", "The message must not be greater than 123 bytes."));
+        }
+        self.with_socket(|socket| if socket.state != ReadyState::Closed {
+            socket.state = ReadyState::Closing;
+        });
+        Ok(())
+    }
+
+    pub fn ready_state(&self) -> ReadyState {
+        self.try_ready_state().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_ready_state(&self) -> Result<ReadyState, JsError> {
+        Ok(self.with_socket(|socket| socket.state))
+    }
+
+    /// Always 0: messages are delivered to `take_sent` at once.
+    pub fn buffered_amount(&self) -> u32 {
+        self.try_buffered_amount().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_buffered_amount(&self) -> Result<u32, JsError> {
+        Ok(0)
+    }
+
+    /// The subprotocol selected by `server_accept`.
+    pub fn protocol(&self) -> String {
+        self.try_protocol().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_protocol(&self) -> Result<String, JsError> {
+        Ok(self.with_socket(|socket| socket.protocol.clone()))
+    }
+
+    /// Always empty: the fake server negotiates no extensions.
+    pub fn extensions(&self) -> String {
+        self.try_extensions().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_extensions(&self) -> Result<String, JsError> {
+        Ok(String::new())
+    }
+
+    // The server side
+
+    pub fn url(&self) -> String {
+        self.with_socket(|socket| socket.url.clone())
+    }
+
+    /// The subprotocols the socket was created with.
+    pub fn requested_protocols(&self) -> Vec<String> {
+        self.with_socket(|socket| socket.protocols.clone())
+    }
+
+    /// Takes the messages sent since the last call.
+    pub fn take_sent(&self) -> Vec<Message> {
+        self.with_socket(|socket| socket.sent.drain(..).collect())
+    }
+
+    /// Opens the connection with `protocol` as the selected subprotocol.
+    pub fn server_accept(&self, protocol: &str) {
+        let opened = self.with_socket(|socket| {
+            if socket.state != ReadyState::Connecting {
+                return false;
+            }
+            socket.state = ReadyState::Open;
+            socket.protocol = protocol.to_string();
+            true
+        });
+        if opened {
+            emit(self.doc, self.id, &Signal::Open);
+        }
+    }
+
+    fn server_message(&self, signal: Signal) {
+        if self.with_socket(|socket| socket.state) == ReadyState::Open {
+            emit(self.doc, self.id, &signal);
+        }
+    }
+
+    /// Delivers a text message, if the socket is open.
+    pub fn server_send(&self, data: &str) {
+        self.server_message(Signal::Text(data.to_string()))
+    }
+
+    /// Delivers a binary message, if the socket is open.
+    pub fn server_send_binary(&self, data: &[u8]) {
+        self.server_message(Signal::Binary(data.to_vec()))
+    }
+
+    fn server_closed(&self, error: bool, event: CloseEvent) {
+        let closed = self.with_socket(|socket| {
+            let closed = socket.state != ReadyState::Closed;
+            socket.state = ReadyState::Closed;
+            closed
+        });
+        if closed {
+            if error {
+                emit(self.doc, self.id, &Signal::Error);
+            }
+            emit(self.doc, self.id, &Signal::Close(event));
+        }
+    }
+
+    /// Closes the connection cleanly, whether or not the page asked to close it.
+    pub fn server_close(&self, code: u16, reason: &str) {
+        self.server_closed(false, CloseEvent { code, reason: reason.to_string(), was_clean: true })
+    }
+
+    /// Drops the connection, as when the server cannot be reached: an error event is followed by
+    /// an unclean close with code 1006.
+    pub fn server_fail(&self) {
+        self.server_closed(true, CloseEvent { code: 1006, reason: String::new(), was_clean: false })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+
+    #[test]
+    fn test_fake_server() {
+        let document = init();
+        let window = document.window();
+        assert_eq!(window.try_websocket_create("http://a/").err().unwrap().name, "SyntaxError");
+        let ws = window.websocket_create_with_protocols("ws://a/", &["v1", "v2"]).unwrap();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let _open = { let log = log.clone(); ws.on_open(move || log.borrow_mut().push("open".to_string())) };
+        let _message = { let log = log.clone(); ws.on_message_string(move |m| log.borrow_mut().push(m)) };
+        let _close = { let log = log.clone(); ws.on_close(move |e| log.borrow_mut().push(format!("close {}", e.code))) };
+
+        assert_eq!(ws.try_send("early").err().unwrap().name, "InvalidStateError");
+        assert_eq!(window.websockets()[0].requested_protocols(), vec!["v1", "v2"]);
+        ws.server_accept("v2");
+        assert_eq!((ws.ready_state(), ws.protocol()), (ReadyState::Open, "v2".to_string()));
+        ws.send("hi");
+        ws.send_binary(&[1, 2]);
+        assert_eq!(ws.take_sent(), vec![Message::Text("hi".to_string()), Message::Binary(vec![1, 2])]);
+        ws.server_send("hello");
+        ws.close("bye");
+        assert_eq!(ws.ready_state(), ReadyState::Closing);
+        ws.server_send("late");
+        ws.server_close(1000, "bye");
+        assert_eq!(ws.ready_state(), ReadyState::Closed);
+        assert_eq!(*log.borrow(), vec!["open", "hello", "close 1000"]);
+    }
+}
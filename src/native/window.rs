@@ -0,0 +1,656 @@
+use std::collections::VecDeque;
+use ::error::*;
+use ::executor::*;
+use ::native::*;
+use ::native::dom::*;
+use ::native::{timer, websocket};
+
+
+/// A dialog opened by the page, as returned by `Window::take_dialogs`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dialog {
+    Alert(String),
+    Confirm(String),
+    /// The message and the default answer.
+    Prompt(String, String),
+}
+
+/// The location, history, dialogs and viewport of a native `Document`.
+pub(crate) struct WindowState {
+    history: Vec<String>,
+    index: usize,
+    reloads: u32,
+    dialogs: Vec<Dialog>,
+    confirms: VecDeque<bool>,
+    prompts: VecDeque<Option<String>>,
+    inner_width: i32,
+    inner_height: i32,
+    device_pixel_ratio: f64,
+}
+
+impl WindowState {
+    pub(crate) fn new() -> Self {
+        WindowState {
+            history: vec!["http://localhost/".to_string()],
+            index: 0,
+            reloads: 0,
+            dialogs: Vec::new(),
+            confirms: VecDeque::new(),
+            prompts: VecDeque::new(),
+            inner_width: 1024,
+            inner_height: 768,
+            device_pixel_ratio: 1.0,
+        }
+    }
+
+    fn href(&self) -> &str {
+        &self.history[self.index]
+    }
+
+    /// Adds `url` after the current entry, dropping the entries that were after it.
+    fn push(&mut self, url: String) {
+        self.history.truncate(self.index + 1);
+        self.history.push(url);
+        self.index += 1;
+    }
+}
+
+/// Splits `url` before its fragment.
+fn split_fragment(url: &str) -> (&str, &str) {
+    match url.find('#') {
+        Some(index) => (&url[..index], &url[index..]),
+        None => (url, ""),
+    }
+}
+
+/// Resolves `url` against the absolute `base` like a browser, without normalizing `.` and `..`.
+fn resolve(base: &str, url: &str) -> String {
+    let (base, _) = split_fragment(base);
+    let scheme_end = match base.find("://") {
+        Some(index) => index + 3,
+        None => return url.to_string(),
+    };
+    let path_start = base[scheme_end..].find('/').map(|index| scheme_end + index).unwrap_or(base.len());
+    let without_query = split_query(base);
+    if url.contains("://") {
+        url.to_string()
+    } else if url.starts_with("//") {
+        format!("{}{}", &base[..scheme_end - 2], url)
+    } else if url.starts_with('/') {
+        format!("{}{}", &base[..path_start], url)
+    } else if url.starts_with('?') {
+        format!("{}{}", without_query, url)
+    } else if url.is_empty() || url.starts_with('#') {
+        format!("{}{}", base, url)
+    } else if path_start == base.len() {
+        format!("{}/{}", base, url)
+    } else {
+        let dir = &without_query[..without_query.rfind('/').unwrap() + 1];
+        format!("{}{}", dir, url)
+    }
+}
+
+fn split_query(url: &str) -> &str {
+    &url[..url.find('?').unwrap_or(url.len())]
+}
+
+/// The window of a native `Document`.
+///
+/// Besides the browser API it has the controls tests need to stand in for the browser and the
+/// user: `advance` runs the timers, `push_confirm` and `push_prompt` script the answers to
+/// dialogs, `resize` and `dispatch_storage` report outside changes and `websockets` reaches
+/// the server side of the sockets.
+#[derive(Clone, Copy)]
+pub struct Window<'a> {
+    doc: *const Document<'a>,
+}
+
+impl<'a> Window<'a> {
+    pub(crate) fn new(doc: *const Document<'a>) -> Self {
+        Window { doc }
+    }
+
+    fn document(&self) -> &Document<'a> {
+        unsafe { &*self.doc }
+    }
+
+    pub fn on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> ListenerHandle<'a> {
+        self.try_on(s, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on<F: FnMut(Event<'a>) + 'a>(&self, s: &str, f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let id = self.document().dom.borrow_mut().add_listener(WINDOW, s, false, Box::new(f));
+        Ok(ListenerHandle::new(id, self.doc))
+    }
+
+    /// Dispatches a synthetic `s` event to the window listeners.
+    pub fn dispatch(&self, s: &str, init: EventInit) -> bool {
+        dispatch(self.doc, WINDOW, s, init)
+    }
+
+    // WebSockets
+
+    /// Creates a socket for `url`, which must be a `ws:` or `wss:` URL. It stays connecting
+    /// until `WebSocket::server_accept`.
+    pub fn websocket_create(&self, url: &str) -> Option<WebSocket<'a>> {
+        self.try_websocket_create(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_websocket_create(&self, url: &str) -> Result<Option<WebSocket<'a>>, JsError> {
+        websocket::create(self.doc, url, &[])
+    }
+
+    pub fn websocket_create_with_protocols(&self, url: &str, protocols: &[&str]) -> Option<WebSocket<'a>> {
+        self.try_websocket_create_with_protocols(url, protocols).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_websocket_create_with_protocols(&self, url: &str, protocols: &[&str]) -> Result<Option<WebSocket<'a>>, JsError> {
+        websocket::create(self.doc, url, protocols)
+    }
+
+    /// Every socket created so far, in order.
+    pub fn websockets(&self) -> Vec<WebSocket<'a>> {
+        let count = self.document().dom.borrow().sockets.len();
+        (0..count).map(|id| WebSocket::new(id, self.doc)).collect()
+    }
+
+    // Storage
+
+    pub fn local_storage(&self) -> LocalStorageInterface {
+        LocalStorage
+    }
+
+    pub fn session_storage(&self) -> SessionStorageInterface {
+        SessionStorage
+    }
+
+    /// Calls `f` on the changes reported by `dispatch_storage`. Like in browsers, changes made
+    /// through this document's storages are not reported.
+    pub fn on_storage_change<F: FnMut(StorageEvent) + 'a>(&self, f: F) -> ListenerHandle<'a> {
+        self.try_on_storage_change(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_on_storage_change<F: FnMut(StorageEvent) + 'a>(&self, mut f: F) -> Result<ListenerHandle<'a>, JsError> {
+        let id = self.document().dom.borrow_mut().add_signal_listener(WINDOW, Box::new(move |signal: &Signal| {
+            if let Signal::Storage(ref event) = *signal {
+                f(event.clone())
+            }
+        }));
+        Ok(ListenerHandle::new(id, self.doc))
+    }
+
+    /// Reports a change made by another document. The storages themselves are left as they are.
+    pub fn dispatch_storage(&self, event: StorageEvent) {
+        emit(self.doc, WINDOW, &Signal::Storage(event))
+    }
+
+    // Location
+
+    pub fn location_hash_get(&self) -> String {
+        self.try_location_hash_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_hash_get(&self) -> Result<String, JsError> {
+        let state = self.document().window.borrow();
+        let (_, hash) = split_fragment(state.href());
+        Ok(if hash == "#" { String::new() } else { hash.to_string() })
+    }
+
+    /// Navigates to `hash` and dispatches `hashchange`, unless it is the current one.
+    pub fn location_hash_set(&self, s: &str) {
+        self.try_location_hash_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_hash_set(&self, s: &str) -> Result<(), JsError> {
+        let hash = s.strip_prefix('#').unwrap_or(s);
+        let href = format!("{}#{}", split_fragment(self.document().window.borrow().href()).0, hash);
+        self.try_location_href_set(&href)
+    }
+
+    /// The current history entry, `http://localhost/` at first.
+    pub fn location_href_get(&self) -> String {
+        self.try_location_href_get().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_href_get(&self) -> Result<String, JsError> {
+        Ok(self.document().window.borrow().href().to_string())
+    }
+
+    /// Adds a history entry for `s`, resolved against the current URL. When only the fragment
+    /// changes, dispatches `hashchange`; other navigations do not unload the document.
+    pub fn location_href_set(&self, s: &str) {
+        self.try_location_href_set(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_href_set(&self, s: &str) -> Result<(), JsError> {
+        let hash_changed = {
+            let mut state = self.document().window.borrow_mut();
+            let href = resolve(state.href(), s);
+            if href == state.href() && href.contains('#') {
+                return Ok(());
+            }
+            let hash_changed = href.contains('#') && split_fragment(&href).0 == split_fragment(state.href()).0;
+            state.push(href);
+            hash_changed
+        };
+        if hash_changed {
+            self.dispatch("hashchange", EventInit::default());
+        }
+        Ok(())
+    }
+
+    /// Counts the reload, see `reloads`; the document is left as it is.
+    pub fn location_reload(&self) {
+        self.try_location_reload().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_location_reload(&self) -> Result<(), JsError> {
+        self.document().window.borrow_mut().reloads += 1;
+        Ok(())
+    }
+
+    /// How many times `location_reload` or `history_go(0)` was called.
+    pub fn reloads(&self) -> u32 {
+        self.document().window.borrow().reloads
+    }
+
+    // History
+
+    pub fn history_length(&self) -> i32 {
+        self.try_history_length().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_length(&self) -> Result<i32, JsError> {
+        Ok(self.document().window.borrow().history.len() as i32)
+    }
+
+    pub fn history_push(&self, url: &str) {
+        self.try_history_push(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_push(&self, url: &str) -> Result<(), JsError> {
+        let mut state = self.document().window.borrow_mut();
+        let href = resolve(state.href(), url);
+        state.push(href);
+        Ok(())
+    }
+
+    pub fn history_replace(&self, url: &str) {
+        self.try_history_replace(url).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_replace(&self, url: &str) -> Result<(), JsError> {
+        let mut state = self.document().window.borrow_mut();
+        let index = state.index;
+        state.history[index] = resolve(state.href(), url);
+        Ok(())
+    }
+
+    /// Moves `delta` entries through the history and dispatches `popstate`, then `hashchange`
+    /// if only the fragment changed. Unlike in browsers this happens synchronously.
+    pub fn history_go(&self, delta: i32) {
+        self.try_history_go(delta).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_history_go(&self, delta: i32) -> Result<(), JsError> {
+        if delta == 0 {
+            return self.try_location_reload();
+        }
+        let hash_changed = {
+            let mut state = self.document().window.borrow_mut();
+            let index = state.index as i64 + delta as i64;
+            if index < 0 || index >= state.history.len() as i64 {
+                return Ok(());
+            }
+            let old = state.href().to_string();
+            state.index = index as usize;
+            old != state.href() && split_fragment(&old).0 == split_fragment(state.href()).0
+        };
+        self.dispatch("popstate", EventInit::default());
+        if hash_changed {
+            self.dispatch("hashchange", EventInit::default());
+        }
+        Ok(())
+    }
+
+    pub fn history_back(&self) {
+        self.history_go(-1)
+    }
+
+    pub fn history_forward(&self) {
+        self.history_go(1)
+    }
+
+    // Timers
+
+    /// Calls `f` once after `ms` milliseconds of `advance`.
+    pub fn set_timeout<F: FnOnce() + 'a>(&self, ms: i32, f: F) -> TimerHandle<'a> {
+        self.try_set_timeout(ms, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_set_timeout<F: FnOnce() + 'a>(&self, ms: i32, f: F) -> Result<TimerHandle<'a>, JsError> {
+        Ok(timer::set_timeout(self.doc, ms, f))
+    }
+
+    /// Calls `f` every `ms` milliseconds of `advance`.
+    pub fn set_interval<F: FnMut() + 'a>(&self, ms: i32, f: F) -> TimerHandle<'a> {
+        self.try_set_interval(ms, f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_set_interval<F: FnMut() + 'a>(&self, ms: i32, f: F) -> Result<TimerHandle<'a>, JsError> {
+        Ok(timer::set_interval(self.doc, ms, f))
+    }
+
+    /// Resolves after `ms` milliseconds of `advance`.
+    pub fn sleep(&self, ms: i32) -> Sleep<'a> {
+        self.try_sleep(ms).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_sleep(&self, ms: i32) -> Result<Sleep<'a>, JsError> {
+        let (tx, rx) = channel();
+        let timer = self.try_set_timeout(ms, move || tx.send(()))?;
+        Ok(Sleep::new(timer, rx))
+    }
+
+    /// Calls `f` on the next animation frame with its time. Frames come every 16 milliseconds
+    /// of `advance`.
+    pub fn request_animation_frame<F: FnOnce(f64) + 'a>(&self, f: F) -> TimerHandle<'a> {
+        self.try_request_animation_frame(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_request_animation_frame<F: FnOnce(f64) + 'a>(&self, f: F) -> Result<TimerHandle<'a>, JsError> {
+        Ok(timer::request_animation_frame(self.doc, f))
+    }
+
+    /// Calls `f` on the next `advance`, before any timer.
+    pub fn queue_microtask<F: FnOnce() + 'a>(&self, f: F) -> TimerHandle<'a> {
+        self.try_queue_microtask(f).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_queue_microtask<F: FnOnce() + 'a>(&self, f: F) -> Result<TimerHandle<'a>, JsError> {
+        Ok(timer::queue_microtask(self.doc, f))
+    }
+
+    /// Moves the clock forward by `ms` milliseconds and runs what comes due in order: timers,
+    /// animation frames, microtasks and the tasks of `spawn_local`. `advance(0)` only runs the
+    /// microtasks, tasks and timers already due.
+    pub fn advance(&self, ms: i32) {
+        timer::advance(self.document(), ms)
+    }
+
+    /// The time on the clock, in milliseconds since `init`.
+    pub fn now(&self) -> f64 {
+        self.document().clock.borrow().now()
+    }
+
+    // Dialogs
+
+    /// Records the alert, see `take_dialogs`.
+    pub fn alert(&self, s: &str) {
+        self.try_alert(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_alert(&self, s: &str) -> Result<(), JsError> {
+        self.document().window.borrow_mut().dialogs.push(Dialog::Alert(s.to_string()));
+        Ok(())
+    }
+
+    /// Records the dialog and returns the next answer from `push_confirm`, or `false` if there
+    /// is none left.
+    pub fn confirm(&self, s: &str) -> bool {
+        self.try_confirm(s).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_confirm(&self, s: &str) -> Result<bool, JsError> {
+        let mut state = self.document().window.borrow_mut();
+        state.dialogs.push(Dialog::Confirm(s.to_string()));
+        Ok(state.confirms.pop_front().unwrap_or(false))
+    }
+
+    /// Records the dialog and returns the next answer from `push_prompt`, or `None`, as if
+    /// cancelled, if there is none left.
+    pub fn prompt(&self, s: &str, default: &str) -> Option<String> {
+        self.try_prompt(s, default).unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_prompt(&self, s: &str, default: &str) -> Result<Option<String>, JsError> {
+        let mut state = self.document().window.borrow_mut();
+        state.dialogs.push(Dialog::Prompt(s.to_string(), default.to_string()));
+        Ok(state.prompts.pop_front().unwrap_or(None))
+    }
+
+    /// Queues the answer to a later `confirm`.
+    pub fn push_confirm(&self, answer: bool) {
+        self.document().window.borrow_mut().confirms.push_back(answer);
+    }
+
+    /// Queues the answer to a later `prompt`; `None` cancels it.
+    pub fn push_prompt(&self, answer: Option<&str>) {
+        self.document().window.borrow_mut().prompts.push_back(answer.map(str::to_string));
+    }
+
+    /// Takes the dialogs opened since the last call, in order.
+    pub fn take_dialogs(&self) -> Vec<Dialog> {
+        self.document().window.borrow_mut().dialogs.drain(..).collect()
+    }
+
+    // Viewport
+
+    /// 1024 until `resize`.
+    pub fn inner_width(&self) -> i32 {
+        self.try_inner_width().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_inner_width(&self) -> Result<i32, JsError> {
+        Ok(self.document().window.borrow().inner_width)
+    }
+
+    /// 768 until `resize`.
+    pub fn inner_height(&self) -> i32 {
+        self.try_inner_height().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_inner_height(&self) -> Result<i32, JsError> {
+        Ok(self.document().window.borrow().inner_height)
+    }
+
+    /// 1.0 until `set_device_pixel_ratio`.
+    pub fn device_pixel_ratio(&self) -> f64 {
+        self.try_device_pixel_ratio().unwrap_or_else(JsError::raise)
+    }
+
+    pub fn try_device_pixel_ratio(&self) -> Result<f64, JsError> {
+        Ok(self.document().window.borrow().device_pixel_ratio)
+    }
+
+    /// Sets the viewport size and dispatches `resize`.
+    pub fn resize(&self, width: i32, height: i32) {
+        {
+            let mut state = self.document().window.borrow_mut();
+            state.inner_width = width;
+            state.inner_height = height;
+        }
+        self.dispatch("resize", EventInit::default());
+    }
+
+    pub fn set_device_pixel_ratio(&self, ratio: f64) {
+        self.document().window.borrow_mut().device_pixel_ratio = ratio;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll};
+    use super::*;
+
+    #[test]
+    fn test_location_hash_set_get() {
+        let document = init();
+        let window = document.window();
+        let changes = Rc::new(Cell::new(0));
+        let counter = changes.clone();
+        let _handle = window.on("hashchange", move |_| counter.set(counter.get() + 1));
+        window.location_hash_set("section");
+        assert_eq!(window.location_hash_get(), "#section");
+        window.location_hash_set("#section");
+        assert_eq!(changes.get(), 1);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let base = "http://localhost/a/b?q#h";
+        assert_eq!(resolve(base, "c"), "http://localhost/a/c");
+        assert_eq!(resolve(base, "/c"), "http://localhost/c");
+        assert_eq!(resolve(base, "?r"), "http://localhost/a/b?r");
+        assert_eq!(resolve(base, "#i"), "http://localhost/a/b?q#i");
+        assert_eq!(resolve(base, "//example.com/"), "http://example.com/");
+        assert_eq!(resolve(base, "https://example.com/"), "https://example.com/");
+        assert_eq!(resolve("http://localhost", "c"), "http://localhost/c");
+    }
+
+    #[test]
+    fn test_history() {
+        let document = init();
+        let window = document.window();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let _popstate = { let events = events.clone(); window.on("popstate", move |_| events.borrow_mut().push("popstate")) };
+        let _hashchange = { let events = events.clone(); window.on("hashchange", move |_| events.borrow_mut().push("hashchange")) };
+
+        window.history_push("/page");
+        window.location_hash_set("top");
+        window.history_replace("#bottom");
+        assert_eq!(window.location_href_get(), "http://localhost/page#bottom");
+        assert_eq!(window.history_length(), 3);
+        window.history_back();
+        assert_eq!(window.location_href_get(), "http://localhost/page");
+        window.history_go(5);
+        window.history_back();
+        assert_eq!(window.location_href_get(), "http://localhost/");
+        window.history_push("/other");
+        assert_eq!(window.history_length(), 2);
+        window.history_go(0);
+        assert_eq!(window.reloads(), 1);
+        assert_eq!(*events.borrow(), vec!["hashchange", "popstate", "hashchange", "popstate"]);
+    }
+
+    #[test]
+    fn test_dialogs() {
+        let document = init();
+        let window = document.window();
+        window.push_confirm(true);
+        window.push_prompt(Some("Ada"));
+        window.alert("hi");
+        assert!(window.confirm("sure?"));
+        assert!(!window.confirm("really?"));
+        assert_eq!(window.prompt("name?", "anon"), Some("Ada".to_string()));
+        assert_eq!(window.prompt("again?", ""), None);
+        assert_eq!(window.take_dialogs(), vec![
+            Dialog::Alert("hi".to_string()),
+            Dialog::Confirm("sure?".to_string()),
+            Dialog::Confirm("really?".to_string()),
+            Dialog::Prompt("name?".to_string(), "anon".to_string()),
+            Dialog::Prompt("again?".to_string(), String::new()),
+        ]);
+        assert!(window.take_dialogs().is_empty());
+    }
+
+    #[test]
+    fn test_timers() {
+        let document = init();
+        let window = document.window();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let push = |s: &'static str| { let log = log.clone(); move || log.borrow_mut().push(s) };
+        window.set_timeout(20, push("timeout 20")).forget();
+        window.set_timeout(10, push("timeout 10")).forget();
+        let interval = window.set_interval(8, push("interval"));
+        let cancelled = window.set_timeout(5, push("cancelled"));
+        window.request_animation_frame({ let log = log.clone(); move |t| log.borrow_mut().push(if t == 16.0 { "frame 16" } else { "frame" }) }).forget();
+        window.queue_microtask(push("microtask")).forget();
+        cancelled.cancel();
+        assert!(log.borrow().is_empty());
+
+        window.advance(20);
+        assert_eq!(window.now(), 20.0);
+        assert_eq!(*log.borrow(), vec!["microtask", "interval", "timeout 10", "frame 16", "interval", "timeout 20"]);
+        drop(interval);
+        window.advance(100);
+        assert_eq!(log.borrow().len(), 6);
+    }
+
+    #[test]
+    fn test_microtasks_run_after_each_timer() {
+        let document = init();
+        let window = document.window();
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let first = { let log = log.clone(); move || {
+            log.borrow_mut().push("first");
+            let log = log.clone();
+            window.queue_microtask(move || log.borrow_mut().push("microtask")).forget();
+        } };
+        window.set_timeout(0, first).forget();
+        window.set_timeout(0, { let log = log.clone(); move || log.borrow_mut().push("second") }).forget();
+        window.advance(0);
+        assert_eq!(*log.borrow(), vec!["first", "microtask", "second"]);
+    }
+
+    struct SetWhenDone<'a>(Sleep<'a>, Rc<Cell<bool>>);
+
+    impl<'a> Future for SetWhenDone<'a> {
+        type Output = ();
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+            let this = self.get_mut();
+            let ready = Pin::new(&mut this.0).poll(cx).is_ready();
+            this.1.set(ready);
+            if ready { Poll::Ready(()) } else { Poll::Pending }
+        }
+    }
+
+    #[test]
+    fn test_sleep() {
+        let document = Box::leak(Box::new(init()));
+        let window = document.window();
+        let done = Rc::new(Cell::new(false));
+        spawn_local(SetWhenDone(window.sleep(50), done.clone()));
+        window.advance(49);
+        assert!(!done.get());
+        window.advance(1);
+        assert!(done.get());
+    }
+
+    #[test]
+    fn test_viewport() {
+        let document = init();
+        let window = document.window();
+        let resized = Rc::new(Cell::new(false));
+        let flag = resized.clone();
+        let _handle = window.on("resize", move |_| flag.set(true));
+        assert_eq!((window.inner_width(), window.inner_height(), window.device_pixel_ratio()), (1024, 768, 1.0));
+        window.resize(320, 480);
+        window.set_device_pixel_ratio(2.0);
+        assert_eq!((window.inner_width(), window.inner_height(), window.device_pixel_ratio()), (320, 480, 2.0));
+        assert!(resized.get());
+    }
+
+    #[test]
+    fn test_on_storage_change() {
+        let document = init();
+        let window = document.window();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let _local = { let events = events.clone(); LocalStorage.on_change(&window, move |e| events.borrow_mut().push(e)) };
+        let event = StorageEvent {
+            key: Some("a".to_string()),
+            old_value: None,
+            new_value: Some("1".to_string()),
+            url: "http://localhost/".to_string(),
+            area: StorageArea::Local,
+        };
+        window.dispatch_storage(StorageEvent { area: StorageArea::Session, ..event.clone() });
+        window.dispatch_storage(event.clone());
+        assert_eq!(*events.borrow(), vec![event]);
+    }
+}
@@ -0,0 +1,267 @@
+/* `HtmlNode` tests shared by every backend, included as a child module of each backend's
+ * `html_node.rs`. */
+
+use std::collections::HashSet;
+use std::iter::FromIterator;
+#[cfg(feature = "wasm-bindgen")]
+use wasm_bindgen_test::*;
+use super::*;
+
+pub(super) fn with_html(html: &str, action: fn(&Document)) {
+    let document = init();
+    let body = document.element_query("body").unwrap();
+    let elem = document.element_create("div").unwrap();
+    elem.html_append(html);
+    body.append(&elem);
+    action(&document);
+    elem.remove_self();
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_body_exists() {
+    let document = init();
+    assert!(document.element_query("body").is_some());
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_try_query_syntax_error() {
+    let document = init();
+    let error = document.try_element_query("#").unwrap_err();
+    assert_eq!(error.name, "SyntaxError");
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_query() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            assert!(doc.element_query(".my_class").is_some());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_tagname() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            assert_eq!(doc.element_query(".my_class").unwrap().tagname(), "div");
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_html_set_get() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.html_set("123");
+            assert_eq!(elem.html_get(), "123");
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_class_get() {
+    with_html(
+        r#"<div class="my_class my_class2"></div>"#,
+        |doc| {
+            let expect = ["my_class", "my_class2"];
+            let found = doc.element_query(".my_class").unwrap().class_get();
+            assert_eq!(found, HashSet::from_iter(expect.iter().map(|it| it.to_string())));
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_class_add() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            doc.element_query(".my_class").unwrap().class_add("added_class");
+            assert!(doc.element_query(".added_class").is_some());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_class_toggle() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.class_toggle("my_class");
+            assert!(doc.element_query(".my_class").is_none());
+            elem.class_toggle("my_class");
+            assert!(doc.element_query(".my_class").is_some());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_class_remove() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            doc.element_query(".my_class").unwrap().class_remove("my_class");
+            assert!(doc.element_query(".my_class").is_none());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_parent() {
+    with_html(
+        r#"<div class="parent"><div class="child"></div></div>"#,
+        |doc| {
+            let child = doc.element_query(".child").unwrap();
+            let parent_class = child.parent().unwrap().class_get().into_iter().next().unwrap();
+            assert_eq!(parent_class, "parent");
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_data_set_get() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.data_set("key", "value");
+            assert_eq!(elem.data_get("key"), Some("value".to_string()));
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_style_set_get() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.style_set_str("color", "red");
+            assert_eq!(elem.style_get_str("color"), "red".to_string());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_prop_set_get() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.prop_set_str("id", "value");
+            assert_eq!(elem.prop_get_str("id"), "value".to_string());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_data_style_prop() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.data_set("userId", "7");
+            elem.style_set_str("backgroundColor", "red");
+            elem.prop_set_str("id", "value");
+            elem.prop_set_i32("tabIndex", 3);
+            assert_eq!(elem.data_get("userId"), Some("7".to_string()));
+            assert_eq!(elem.style_get_str("backgroundColor"), "red");
+            assert_eq!(elem.prop_get_i32("tabIndex"), 3);
+            assert!(doc.element_query("#value[data-user-id='7']").is_some());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_prop_set_get_typed() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.prop_set_f64("custom", 0.25);
+            assert_eq!(elem.prop_get_f64("custom"), 0.25);
+            assert_eq!(elem.prop_get_i32("custom"), 0);
+            elem.prop_set_bool("hidden", true);
+            assert!(elem.prop_get_bool("hidden"));
+            assert!(doc.element_query(".my_class[hidden]").is_some());
+            elem.prop_set_bool("hidden", false);
+            assert!(!elem.prop_get_bool("hidden"));
+            assert!(doc.element_query(".my_class[hidden]").is_none());
+            elem.prop_set_bool("custom", false);
+            assert!(!elem.prop_get_bool("custom"));
+            assert!(elem.prop_get_f64("className").is_nan());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_append() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            let elem1 = doc.element_query(".my_class").unwrap();
+            let elem2 = doc.element_create("div").unwrap();
+            elem1.append(&elem2);
+            let parent_class = elem2.parent().unwrap().class_get().into_iter().next().unwrap();
+            assert_eq!(parent_class, "my_class");
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_remove_self() {
+    with_html(
+        r#"<div class="my_class"></div>"#,
+        |doc| {
+            doc.element_query(".my_class").unwrap().remove_self();
+            assert!(doc.element_query(".my_class").is_none());
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_html_append() {
+    with_html(
+        r#"<div class="my_class">aaa</div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.html_append("<b>bbb</b>");
+            assert_eq!(elem.html_get(), "aaa<b>bbb</b>");
+        }
+    )
+}
+
+#[cfg_attr(feature = "wasm-bindgen", wasm_bindgen_test)]
+#[cfg_attr(not(feature = "wasm-bindgen"), test)]
+fn test_html_prepend() {
+    with_html(
+        r#"<div class="my_class">aaa</div>"#,
+        |doc| {
+            let elem = doc.element_query(".my_class").unwrap();
+            elem.html_prepend("bbb");
+            assert_eq!(elem.html_get(), "bbbaaa");
+        }
+    )
+}